@@ -0,0 +1,337 @@
+use super::command_prelude::*;
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::error::{ErrorKind, MessageFormat};
+
+/// Some of the files in a batch could not be processed.
+#[derive(Debug)]
+pub struct BatchError {
+    /// Number of files which could not be processed.
+    pub failed: usize,
+    /// Number of files in the batch.
+    pub total: usize,
+}
+
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} of {} files failed", self.failed, self.total)
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// Template for the names of output files.
+///
+/// `{name}` is replaced by the file name of the input, `{stem}` by the
+/// file name without its extension and `{ext}` by the extension of the
+/// output format. Names are files in the output directory, so templates
+/// cannot hold paths.
+#[derive(Debug, Clone)]
+pub struct Template(String);
+
+impl std::str::FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                return Err("unterminated `{`".to_string());
+            };
+
+            match &rest[start + 1..start + end] {
+                "name" | "stem" | "ext" => {}
+                x => return Err(format!("unknown placeholder `{{{x}}}`")),
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        if s.is_empty() {
+            return Err("template is empty".to_string());
+        }
+        // Output files are written to the output directory itself.
+        if s.contains(['/', '\\']) || s == ".." {
+            return Err("template must be a file name, not a path".to_string());
+        }
+
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl Template {
+    /// Get the name of the output file for `input`.
+    fn render(&self, input: &Path, ext: Option<&str>) -> Result<String> {
+        let name = input.file_name().unwrap_or_default().to_string_lossy();
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+
+        let mut rendered = self.0.replace("{name}", &name).replace("{stem}", &stem);
+        if rendered.contains("{ext}") {
+            let Some(ext) = ext else {
+                bail!("`{{ext}}` cannot be used in this template");
+            };
+            rendered = rendered.replace("{ext}", ext);
+        }
+
+        let mut components = Path::new(&rendered).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            bail!("output file name `{rendered}` is not a file name");
+        }
+
+        Ok(rendered)
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BatchArgs {
+    #[clap(
+        long = "batch",
+        value_name = "INPUT",
+        num_args = 1..,
+        conflicts_with_all = ["input_file", "output_file"],
+        requires = "output_dir",
+        help = "Process many input files, or all files in input directories, at once"
+    )]
+    batch: Vec<PathBuf>,
+
+    #[clap(
+        short = 'o',
+        long = "output-dir",
+        requires = "batch",
+        help = "Directory to write the output files of a batch to"
+    )]
+    output_dir: Option<PathBuf>,
+
+    #[clap(
+        long = "name",
+        requires = "batch",
+        help = "Template for output file names in a batch, with the placeholders {name}, {stem} and {ext}"
+    )]
+    name: Option<Template>,
+
+    #[clap(
+        short = 'j',
+        long = "jobs",
+        requires = "batch",
+        help = "Number of files to process in parallel [default: number of CPUs]"
+    )]
+    jobs: Option<NonZeroUsize>,
+}
+
+/// A single file of a batch.
+#[derive(Debug)]
+pub struct Job {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+impl BatchArgs {
+    pub fn is_enabled(&self) -> bool {
+        !self.batch.is_empty()
+    }
+
+    /// Find all input files and the paths of their outputs.
+    ///
+    /// Directories are expanded to the files directly inside them.
+    /// `template` is used unless a template was given on the command line
+    /// and `ext` is the value of the `{ext}` placeholder, if it has one.
+    fn jobs(&self, template: &str, ext: Option<&str>) -> Result<Vec<Job>> {
+        let output_dir = self
+            .output_dir
+            .as_deref()
+            .context("no output directory given")?;
+
+        let template = match &self.name {
+            Some(x) => x.clone(),
+            None => template.parse().map_err(anyhow::Error::msg)?,
+        };
+
+        let mut inputs = Vec::new();
+        for path in &self.batch {
+            if util::is_stdio(path) {
+                bail!("stdin cannot be used in a batch");
+            }
+
+            if path.is_dir() {
+                let mut files = std::fs::read_dir(path)
+                    .with_context(|| format!("unable to read directory `{}`", path.display()))?
+                    .map(|entry| entry.map(|x| x.path()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("unable to read directory `{}`", path.display()))?;
+
+                files.retain(|x| x.is_file());
+                files.sort();
+                inputs.extend(files);
+            } else {
+                inputs.push(path.clone());
+            }
+        }
+
+        let mut outputs: HashMap<PathBuf, &Path> = HashMap::new();
+        let mut jobs = Vec::with_capacity(inputs.len());
+        for input in &inputs {
+            let output = output_dir.join(template.render(input, ext)?);
+
+            if let Some(other) = outputs.insert(output.clone(), input) {
+                bail!(
+                    "inputs `{}` and `{}` would both be written to `{}`",
+                    other.display(),
+                    input.display(),
+                    output.display()
+                );
+            }
+
+            jobs.push(Job {
+                input: input.clone(),
+                output,
+            });
+        }
+
+        Ok(jobs)
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonMessage<'a> {
+    Result {
+        input: &'a Path,
+        output: &'a Path,
+        ok: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        kind: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    Summary {
+        succeeded: usize,
+        failed: usize,
+    },
+}
+
+fn print_json(message: &JsonMessage) {
+    match serde_json::to_string(message) {
+        Ok(x) => println!("{x}"),
+        Err(e) => eprintln!("error: {e}"),
+    }
+}
+
+fn print_result(job: &Job, result: &Result<()>, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => match result {
+            Ok(()) => println!(
+                "ok      {} -> {}",
+                job.input.display(),
+                job.output.display()
+            ),
+            Err(e) => println!("failed  {}: {e:#}", job.input.display()),
+        },
+        MessageFormat::Json => {
+            let kind = result.as_ref().err().map(ErrorKind::of);
+            print_json(&JsonMessage::Result {
+                input: &job.input,
+                output: &job.output,
+                ok: result.is_ok(),
+                kind: kind.map(ErrorKind::name),
+                code: kind.map(ErrorKind::code),
+                message: result.as_ref().err().map(|e| format!("{e:#}")),
+            });
+        }
+    }
+}
+
+/// Run `f` on every file of the batch described by `args` and print the
+/// outcome of each one.
+///
+/// See [`BatchArgs::jobs`] for `template` and `ext`.
+pub fn run<F>(
+    global_args: &CliArgs,
+    args: &BatchArgs,
+    template: &str,
+    ext: Option<&str>,
+    f: F,
+) -> Result<()>
+where
+    F: Fn(&Job) -> Result<()> + Sync,
+{
+    let jobs = args.jobs(template, ext)?;
+
+    if let Some(dir) = &args.output_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("unable to create directory `{}`", dir.display()))?;
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.map_or(0, NonZeroUsize::get))
+        .build()
+        .context("unable to start worker threads")?;
+
+    let format = global_args.message_format;
+    let failed = pool.install(|| {
+        jobs.par_iter()
+            .map(|job| {
+                let result = f(job);
+                print_result(job, &result, format);
+                result.is_err()
+            })
+            .filter(|&failed| failed)
+            .count()
+    });
+
+    let total = jobs.len();
+    match format {
+        MessageFormat::Human => println!("{} succeeded, {failed} failed", total - failed),
+        MessageFormat::Json => print_json(&JsonMessage::Summary {
+            succeeded: total - failed,
+            failed,
+        }),
+    }
+
+    if failed != 0 {
+        return Err(BatchError { failed, total }.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let t: Template = "{stem}-out.{ext}".parse().unwrap();
+        assert_eq!(
+            t.render(Path::new("dir/data.tar.gz"), Some("png")).unwrap(),
+            "data.tar-out.png"
+        );
+
+        let t: Template = "{name}".parse().unwrap();
+        assert_eq!(t.render(Path::new("a.bin.png"), None).unwrap(), "a.bin.png");
+
+        let t: Template = "{stem}.{ext}".parse().unwrap();
+        assert!(t.render(Path::new("a.bin"), None).is_err());
+
+        assert!("{size}".parse::<Template>().is_err());
+        assert!("{name".parse::<Template>().is_err());
+        assert!("".parse::<Template>().is_err());
+
+        // Output files cannot be written outside the output directory.
+        for template in ["../{name}", "/tmp/{stem}", "out/{name}", "..\\{name}", ".."] {
+            assert!(template.parse::<Template>().is_err(), "{template}");
+        }
+        let t: Template = "{ext}".parse().unwrap();
+        assert!(t.render(Path::new("a.bin"), Some("..")).is_err());
+        assert!(t.render(Path::new("a.bin"), Some("")).is_err());
+    }
+}
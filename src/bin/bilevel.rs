@@ -0,0 +1,209 @@
+use std::io::{Read, Seek, Write};
+
+use anyhow::{bail, Result};
+use image::{GrayImage, Luma};
+
+/// Pack the pixels of `image` in rows of bits, starting each row on a new
+/// byte, with `1` for pixels closer to white than to black.
+pub fn pack_rows(image: &GrayImage) -> Vec<u8> {
+    image
+        .rows()
+        .flat_map(|row| {
+            row.collect::<Vec<_>>()
+                .chunks(8)
+                .map(|pixels| {
+                    pixels.iter().enumerate().fold(0u8, |acc, (i, pixel)| {
+                        acc | u8::from(pixel.0[0] >= 128) << (7 - i)
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Get an image from rows of bits packed by [`pack_rows()`].
+fn unpack_rows(width: u32, height: u32, bits: &[u8]) -> Option<GrayImage> {
+    let row_size = width.div_ceil(8) as usize;
+    if bits.len() < row_size * height as usize {
+        return None;
+    }
+
+    Some(GrayImage::from_fn(width, height, |x, y| {
+        let byte = bits[y as usize * row_size + x as usize / 8];
+        Luma([if byte << (x % 8) & 0x80 != 0 { 255 } else { 0 }])
+    }))
+}
+
+/// Tags of the image file directory of a TIFF file, in increasing order.
+mod tag {
+    pub const IMAGE_WIDTH: u16 = 256;
+    pub const IMAGE_LENGTH: u16 = 257;
+    pub const BITS_PER_SAMPLE: u16 = 258;
+    pub const COMPRESSION: u16 = 259;
+    pub const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+    pub const STRIP_OFFSETS: u16 = 273;
+    pub const SAMPLES_PER_PIXEL: u16 = 277;
+    pub const ROWS_PER_STRIP: u16 = 278;
+    pub const STRIP_BYTE_COUNTS: u16 = 279;
+}
+
+/// Write `pages` to `writer` as an uncompressed TIFF file with one bit
+/// per pixel, with each page in a single strip.
+pub fn write_tiff<W>(pages: &[GrayImage], mut writer: W) -> Result<()>
+where
+    W: Write,
+{
+    const SHORT: u16 = 3;
+    const LONG: u16 = 4;
+    const ENTRIES: u16 = 9;
+
+    let mut buf = Vec::new();
+    buf.extend(b"II*\0");
+    buf.extend(8u32.to_le_bytes());
+
+    // Each page is written as its directory followed by its pixels.
+    let directory_size = 2 + 12 * usize::from(ENTRIES) + 4;
+
+    for (i, page) in pages.iter().enumerate() {
+        let bits = pack_rows(page);
+        let pixels_offset = buf.len() + directory_size;
+        // Directories start on a word boundary.
+        let end = (pixels_offset + bits.len()).next_multiple_of(2);
+        let next = if i + 1 < pages.len() { end } else { 0 };
+
+        let (Ok(pixels_offset), Ok(size), Ok(next)) = (
+            u32::try_from(pixels_offset),
+            u32::try_from(bits.len()),
+            u32::try_from(next),
+        ) else {
+            bail!("image is too large for a TIFF file");
+        };
+
+        let entries = [
+            (tag::IMAGE_WIDTH, LONG, page.width()),
+            (tag::IMAGE_LENGTH, LONG, page.height()),
+            (tag::BITS_PER_SAMPLE, SHORT, 1),
+            (tag::COMPRESSION, SHORT, 1),
+            // Black is zero.
+            (tag::PHOTOMETRIC_INTERPRETATION, SHORT, 1),
+            (tag::STRIP_OFFSETS, LONG, pixels_offset),
+            (tag::SAMPLES_PER_PIXEL, SHORT, 1),
+            (tag::ROWS_PER_STRIP, LONG, page.height()),
+            (tag::STRIP_BYTE_COUNTS, LONG, size),
+        ];
+
+        buf.extend(ENTRIES.to_le_bytes());
+        for (tag, ty, value) in entries {
+            buf.extend(tag.to_le_bytes());
+            buf.extend(ty.to_le_bytes());
+            buf.extend(1u32.to_le_bytes());
+            // Values shorter than four bytes are left-justified.
+            match ty {
+                SHORT => buf.extend((value as u16).to_le_bytes().iter().chain(&[0, 0])),
+                _ => buf.extend(value.to_le_bytes()),
+            }
+        }
+        buf.extend(next.to_le_bytes());
+
+        buf.extend(bits);
+        buf.resize(end, 0);
+    }
+
+    writer.write_all(&buf)?;
+
+    Ok(())
+}
+
+/// Read an uncompressed TIFF file with one bit per pixel from `reader`,
+/// which the `image` crate does not support. Returns `None` if the image
+/// does not have one bit per pixel or is compressed.
+pub fn read_tiff<R>(mut reader: R) -> Result<Option<GrayImage>>
+where
+    R: Read,
+{
+    let mut file = Vec::new();
+    reader.read_to_end(&mut file)?;
+
+    let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(&file))?;
+    read_tiff_page(&mut decoder, &file)
+}
+
+/// Read the page `decoder` is at in the TIFF `file`, like [`read_tiff()`].
+pub fn read_tiff_page<R>(
+    decoder: &mut tiff::decoder::Decoder<R>,
+    file: &[u8],
+) -> Result<Option<GrayImage>>
+where
+    R: Read + Seek,
+{
+    use tiff::tags::{CompressionMethod, PhotometricInterpretation, Tag};
+
+    // The decoder of the `tiff` crate expects a byte per pixel for images
+    // with fewer bits per pixel, so only the tags are read with it.
+    let compression = decoder
+        .find_tag_unsigned::<u16>(Tag::Compression)?
+        .unwrap_or(CompressionMethod::None.to_u16());
+    if decoder.colortype()? != tiff::ColorType::Gray(1)
+        || compression != CompressionMethod::None.to_u16()
+    {
+        return Ok(None);
+    }
+
+    let (width, height) = decoder.dimensions()?;
+    let white_is_zero = decoder
+        .find_tag_unsigned::<u16>(Tag::PhotometricInterpretation)?
+        .is_some_and(|x| x == PhotometricInterpretation::WhiteIsZero.to_u16());
+
+    // Strips hold whole rows, so their bytes follow each other as packed rows.
+    let offsets = decoder.get_tag_u64_vec(Tag::StripOffsets)?;
+    let sizes = decoder.get_tag_u64_vec(Tag::StripByteCounts)?;
+    let mut bits = Vec::new();
+    for (offset, size) in offsets.into_iter().zip(sizes) {
+        let strip = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(size).ok())
+            .and_then(|(offset, size)| file.get(offset..offset.checked_add(size)?));
+        let Some(strip) = strip else {
+            bail!("strip of TIFF file is out of bounds");
+        };
+        bits.extend_from_slice(strip);
+    }
+
+    let Some(mut image) = unpack_rows(width, height, &bits) else {
+        bail!("TIFF file has too few pixels");
+    };
+
+    if white_is_zero {
+        image::imageops::invert(&mut image);
+    }
+
+    Ok(Some(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiff_round_trip() {
+        let image =
+            GrayImage::from_fn(13, 5, |x, y| Luma([if (x + y) % 3 == 0 { 0 } else { 255 }]));
+
+        let mut tiff = Vec::new();
+        write_tiff(std::slice::from_ref(&image), &mut tiff).unwrap();
+
+        let decoded = read_tiff(std::io::Cursor::new(&tiff)).unwrap();
+        assert_eq!(decoded, Some(image));
+    }
+
+    #[test]
+    fn test_pack_rows() {
+        let image = GrayImage::from_fn(10, 2, |x, _| Luma([if x == 0 { 0 } else { 200 }]));
+        let bits = pack_rows(&image);
+        assert_eq!(bits, [0x7f, 0xc0, 0x7f, 0xc0]);
+
+        let unpacked = unpack_rows(10, 2, &bits).unwrap();
+        assert_eq!(unpacked.get_pixel(0, 1).0, [0]);
+        assert_eq!(unpacked.get_pixel(9, 1).0, [255]);
+    }
+}
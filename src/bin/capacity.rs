@@ -0,0 +1,91 @@
+use super::command_prelude::*;
+
+use crate::formats::PixelFormat;
+use crate::sizing;
+
+#[derive(Debug, Clone, Copy)]
+enum Query {
+    Size(u64),
+    Dimensions(u32, u32),
+}
+
+/// Parse either a byte size (`4096`, `64K`, `1.5MiB`, `2MB`)
+/// or image dimensions (`1920x1080`).
+fn parse_query(s: &str) -> Result<Query, String> {
+    if let Some((x, y)) = s.split_once(['x', 'X']) {
+        let x = x
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid width: {e}"))?;
+        let y = y
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid height: {e}"))?;
+        return Ok(Query::Dimensions(x, y));
+    }
+
+    sizing::parse_size(s).map(Query::Size)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(
+        help = "Payload size in bytes (e.g. `4096`, `64K`, `2MB`) or image dimensions (e.g. `1920x1080`)",
+        value_parser = parse_query
+    )]
+    query: Query,
+
+    #[clap(flatten)]
+    sizing: sizing::SizingArgs,
+
+    #[clap(
+        short = 'p',
+        long = "pixel",
+        help = "Only show this pixel format instead of all of them"
+    )]
+    pixel_format: Option<PixelFormat>,
+
+    #[clap(long = "json", help = "Print the plans as JSON")]
+    json: bool,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    let options = args.sizing.options();
+
+    let pixel_formats = match args.pixel_format {
+        Some(x) => vec![x.into()],
+        None => imgcode::PixelFormat::ALL.to_vec(),
+    };
+
+    let plans = pixel_formats
+        .into_iter()
+        .map(|pixel_format| match args.query {
+            Query::Size(size) => imgcode::plan(pixel_format, size, &options),
+            Query::Dimensions(x, y) => Ok(imgcode::plan_for_dimensions(pixel_format, x, y)),
+        })
+        .collect::<imgcode::Result<Vec<_>>>()?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&plans)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<8} {:>13} {:>12} {:>8} {:>12} {:>12} {:>10}",
+        "pixel", "dimensions", "capacity", "header", "max payload", "payload", "padding"
+    );
+    for plan in plans {
+        println!(
+            "{:<8} {:>13} {:>12} {:>8} {:>12} {:>12} {:>10}",
+            plan.pixel_format.to_string(),
+            format!("{}x{}", plan.width, plan.height),
+            plan.capacity,
+            plan.header_size,
+            plan.max_payload_size,
+            plan.payload_size,
+            plan.padding
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use super::command_prelude::*;
+
+use std::path::PathBuf;
+
+use image::{DynamicImage, ImageFormat};
+use imgcode::{DecodeOptions, SecretKey};
+
+use crate::{keygen, sizing};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(
+        help = "Paths to images holding shares of a secret, in any order",
+        required = true
+    )]
+    share_files: Vec<PathBuf>,
+
+    #[clap(
+        short = 'o',
+        long = "output",
+        help = "Path to output file, or `-` for stdout",
+        default_value = "-"
+    )]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+
+    #[clap(flatten)]
+    placement: sizing::PlacementArgs,
+
+    #[clap(flatten)]
+    identity: keygen::IdentityArgs,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    let mut shares = Vec::with_capacity(args.share_files.len());
+    let mut format = None;
+    for path in &args.share_files {
+        let mut input = util::open_input(path)
+            .with_context(|| format!("unable to open share `{}`", path.display()))?;
+
+        let (i, container) = util::decode_image(&mut input, ImageFormat::from_path(path).ok())
+            .with_context(|| format!("unable to read share `{}`", path.display()))?;
+        shares.push(i);
+        format = format.or(container);
+    }
+
+    let identity = args.identity.identity()?;
+    let data = from_shares(
+        &shares,
+        format,
+        args.placement.options().as_ref(),
+        identity.as_ref(),
+    )?;
+
+    util::write_output(&args.output_file, args.force, &data)
+        .with_context(|| format!("unable to write output `{}`", args.output_file.display()))
+}
+
+/// Recover the secret split into `shares`, decoded from files in `format`,
+/// from the region and channels of each image given by `options`.
+///
+/// Without `options`, the data of GIF files is read like by
+/// [`util::read_with_options()`]. Shares encrypted for recipients are
+/// decrypted with `identity`.
+pub fn from_shares(
+    shares: &[DynamicImage],
+    format: Option<ImageFormat>,
+    options: Option<&DecodeOptions>,
+    identity: Option<&SecretKey>,
+) -> Result<Vec<u8>> {
+    let data = util::read_with_options(format, options.cloned(), |options| {
+        let options = keygen::with_identity(options, identity);
+        util::with_images!(shares, |v| imgcode::from_shares_with(v, &options))
+    })?;
+
+    Ok(data)
+}
@@ -1,39 +1,101 @@
 use super::command_prelude::*;
 
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use imgcode::SecretKey;
+
+use crate::error::MessageFormat;
+use crate::{batch, frames, keygen, sizing};
 
 #[derive(Debug, clap::Args)]
 pub struct Args {
-    #[clap(help = "Path to input file")]
-    input_file: PathBuf,
+    #[clap(
+        help = "Path to input file, or `-` for stdin",
+        required_unless_present = "batch"
+    )]
+    input_file: Option<PathBuf>,
+
+    #[clap(
+        help = "Path to output file, or `-` for stdout",
+        required_unless_present = "batch"
+    )]
+    output_file: Option<PathBuf>,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+
+    #[clap(flatten)]
+    placement: sizing::PlacementArgs,
 
-    #[clap(help = "Path to output file")]
-    output_file: PathBuf,
+    #[clap(flatten)]
+    identity: keygen::IdentityArgs,
+
+    #[clap(flatten)]
+    batch: batch::BatchArgs,
 }
 
-pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
-    let mut input = util::open_buffered_read(File::options().read(true), &args.input_file)
-        .with_context(|| format!("unable to open input `{}`", args.input_file.display()))?;
+pub fn command(global_args: &CliArgs, args: &Args) -> Result<()> {
+    let identity = args.identity.identity()?;
+
+    if args.batch.is_enabled() {
+        return batch::run(global_args, &args.batch, "{stem}", None, |job| {
+            decode(args, identity.as_ref(), &job.input, &job.output, false)
+        });
+    }
+
+    match (&args.input_file, &args.output_file) {
+        (Some(input_file), Some(output_file)) => {
+            let progress = global_args.message_format == MessageFormat::Human;
+            decode(args, identity.as_ref(), input_file, output_file, progress)
+        }
+        _ => unreachable!("input and output files are required outside of a batch"),
+    }
+}
 
-    let mut output = util::open_buffered_write(File::options().write(true), &args.output_file)
-        .with_context(|| format!("unable to open output `{}`", args.output_file.display()))?;
+/// Decode the image at `input_file` and write its contents to `output_file`,
+/// decrypting them with `identity` if they are encrypted for recipients.
+///
+/// A progress bar is drawn on stderr if `progress` is set and stderr is a terminal.
+fn decode(
+    args: &Args,
+    identity: Option<&SecretKey>,
+    input_file: &Path,
+    output_file: &Path,
+    progress: bool,
+) -> Result<()> {
+    let mut input = util::open_input(input_file)
+        .with_context(|| format!("unable to open input `{}`", input_file.display()))?;
 
-    let i = image::io::Reader::new(&mut input)
-        .with_guessed_format()?
-        .decode()?;
+    let hint = image::ImageFormat::from_path(input_file).ok();
+    let (mut frames, container) = frames::read_frames(&mut input, hint)?;
 
-    use image::DynamicImage;
-    let data = match i {
-        DynamicImage::ImageRgb8(v) => imgcode::from_image(v),
-        DynamicImage::ImageRgba8(v) => imgcode::from_image(v),
-        DynamicImage::ImageRgb32F(v) => imgcode::from_image(v),
-        DynamicImage::ImageRgba32F(v) => imgcode::from_image(v),
-        _ => bail!("unsupported image pixel format"),
-    }?;
+    if let Some(region) = args.placement.region {
+        sizing::check_region(region, frames[0].width(), frames[0].height())?;
+    }
 
-    output.write_all(&data)?;
+    let data = if frames.len() == 1 && container != Some(image::ImageFormat::Gif) {
+        let bar = util::progress_bar("decoding", progress);
+        let options = match (args.placement.options(), identity) {
+            (None, None) => None,
+            (options, identity) => {
+                Some(keygen::with_identity(options.unwrap_or_default(), identity))
+            }
+        };
+        let data = util::with_image!(frames.remove(0), |v| match &options {
+            Some(options) => imgcode::from_image_with(v, options),
+            None => imgcode::from_image_with_progress(v, util::report_progress(&bar)),
+        })?;
+        bar.finish_and_clear();
+        data
+    } else {
+        frames::from_frames(
+            &frames,
+            container,
+            args.placement.options().as_ref(),
+            identity,
+        )?
+    };
 
-    Ok(())
+    util::write_output(output_file, args.force, &data)
+        .with_context(|| format!("unable to write output `{}`", output_file.display()))
 }
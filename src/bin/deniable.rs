@@ -0,0 +1,211 @@
+use super::command_prelude::*;
+
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+use imgcode::EncodeOptions;
+
+use crate::encode;
+use crate::formats::{OutputFormat, PixelFormat};
+use crate::png::PngArgs;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Encrypt one or two files into an image which does not tell how many it holds
+    Encode(EncodeArgs),
+    /// Read the file encrypted with a password from an image
+    Decode(DecodeArgs),
+}
+
+#[derive(Debug, clap::Args)]
+struct EncodeArgs {
+    #[clap(help = "Path to input file, or `-` for stdin")]
+    input_file: PathBuf,
+
+    #[clap(help = "Path to output file, or `-` for stdout")]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+
+    #[clap(
+        long = "password-file",
+        value_name = "FILE",
+        help = "File holding the password of the input"
+    )]
+    password_file: PathBuf,
+
+    #[clap(
+        long = "second",
+        value_name = "FILE",
+        help = "Second file to encrypt into the image with its own password",
+        requires = "second_password_file"
+    )]
+    second_file: Option<PathBuf>,
+
+    #[clap(
+        long = "second-password-file",
+        value_name = "FILE",
+        help = "File holding the password of the second file",
+        requires = "second_file"
+    )]
+    second_password_file: Option<PathBuf>,
+
+    #[clap(
+        short = 'f',
+        long = "format",
+        help = "Format of the output image [default: from the output file extension, or png]"
+    )]
+    format: Option<OutputFormat>,
+
+    #[clap(
+        short = 'p',
+        long = "pixel",
+        help = "Format of the pixels in the image [default: depends on the output format]"
+    )]
+    pixel_format: Option<PixelFormat>,
+
+    #[clap(flatten)]
+    png: PngArgs,
+}
+
+#[derive(Debug, clap::Args)]
+struct DecodeArgs {
+    #[clap(help = "Path to image, or `-` for stdin")]
+    image_file: PathBuf,
+
+    #[clap(
+        short = 'o',
+        long = "output",
+        help = "Path to output file, or `-` for stdout",
+        default_value = "-"
+    )]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+
+    #[clap(
+        long = "password-file",
+        value_name = "FILE",
+        help = "File holding the password of the file to read"
+    )]
+    password_file: PathBuf,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    match &args.command {
+        Command::Encode(args) => encode(args),
+        Command::Decode(args) => decode(args),
+    }
+}
+
+/// Read the password held by the file at `path`, without the line
+/// break at its end.
+fn read_password(path: &Path) -> Result<Vec<u8>> {
+    let mut password = util::read_input(path)
+        .with_context(|| format!("unable to read password `{}`", path.display()))?;
+
+    for end in [b"\n".as_slice(), b"\r"] {
+        if password.ends_with(end) {
+            password.pop();
+        }
+    }
+
+    if password.is_empty() {
+        bail!("password `{}` is empty", path.display());
+    }
+    Ok(password)
+}
+
+fn encode(args: &EncodeArgs) -> Result<()> {
+    let format = args
+        .format
+        .or_else(|| OutputFormat::from_path(&args.output_file))
+        .unwrap_or(OutputFormat::Png);
+    let pixel_format = args
+        .pixel_format
+        .unwrap_or_else(|| format.default_pixel_format());
+
+    if !format.supports(pixel_format) {
+        return Err(imgcode::Error::UnsupportedFormat).with_context(|| {
+            format!(
+                "{format} images cannot hold {} pixels",
+                imgcode::PixelFormat::from(pixel_format)
+            )
+        });
+    }
+
+    // Only the red channel of GIF images keeps its value, which would
+    // leave the other channels as a tell of which bytes are random.
+    if format == OutputFormat::Gif {
+        return Err(imgcode::Error::UnsupportedFormat)
+            .context("gif images cannot hold deniable payloads");
+    }
+
+    let read_input = |path: &Path| {
+        util::read_input(path).with_context(|| format!("unable to read input `{}`", path.display()))
+    };
+
+    let mut payloads = vec![(
+        read_input(&args.input_file)?,
+        read_password(&args.password_file)?,
+    )];
+    if let (Some(file), Some(password)) = (&args.second_file, &args.second_password_file) {
+        payloads.push((read_input(file)?, read_password(password)?));
+    }
+
+    let payloads = payloads
+        .iter()
+        .map(|(data, password)| (data.as_slice(), password.as_slice()))
+        .collect::<Vec<_>>();
+    let image = encode_image(&payloads, pixel_format).map_err(|e| match e {
+        imgcode::Error::InvalidArgument => {
+            anyhow::Error::new(e).context("both passwords are the same")
+        }
+        e => e.into(),
+    })?;
+
+    let buf = encode::image_bytes(format, &image, false, &args.png)?;
+    util::write_output(&args.output_file, args.force, &buf)
+        .with_context(|| format!("unable to write output `{}`", args.output_file.display()))
+}
+
+/// Encrypt `payloads` into an image with `pixel_format` pixels.
+fn encode_image(
+    payloads: &[(&[u8], &[u8])],
+    pixel_format: PixelFormat,
+) -> imgcode::Result<DynamicImage> {
+    use imgcode::to_deniable_image;
+
+    let options = EncodeOptions::new();
+
+    Ok(match pixel_format {
+        PixelFormat::Rgb8 => to_deniable_image::<image::RgbImage>(payloads, &options)?.into(),
+        PixelFormat::Rgba8 => to_deniable_image::<image::RgbaImage>(payloads, &options)?.into(),
+        PixelFormat::Rgb32 => to_deniable_image::<image::Rgb32FImage>(payloads, &options)?.into(),
+        PixelFormat::Rgba32 => to_deniable_image::<image::Rgba32FImage>(payloads, &options)?.into(),
+    })
+}
+
+fn decode(args: &DecodeArgs) -> Result<()> {
+    let password = read_password(&args.password_file)?;
+
+    let mut input = util::open_input(&args.image_file)
+        .with_context(|| format!("unable to open image `{}`", args.image_file.display()))?;
+    let (image, _) = util::decode_image(
+        &mut input,
+        image::ImageFormat::from_path(&args.image_file).ok(),
+    )?;
+
+    let data = util::with_image!(image, |v| imgcode::from_deniable_image(v, &password))?;
+
+    util::write_output(&args.output_file, args.force, &data)
+        .with_context(|| format!("unable to write output `{}`", args.output_file.display()))
+}
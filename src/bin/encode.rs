@@ -1,96 +1,598 @@
 use super::command_prelude::*;
 
-use std::{fs::File, io::Read, path::PathBuf};
-
-#[derive(Debug, Clone, Copy, clap::ValueEnum)]
-enum OutputFormat {
-    Png,
-    Jpg,
-    Gif,
-    Ico,
-    Bmp,
-    OpenExr,
-    Tiff,
-}
+use std::io::{Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-impl From<OutputFormat> for image::ImageOutputFormat {
-    fn from(value: OutputFormat) -> Self {
-        use OutputFormat::*;
-        match value {
-            Png => Self::Png,
-            Jpg => Self::Jpeg(100),
-            Gif => Self::Gif,
-            Ico => Self::Ico,
-            Bmp => Self::Bmp,
-            OpenExr => Self::OpenExr,
-            Tiff => Self::Tiff,
-        }
-    }
-}
+use image::DynamicImage;
+use imgcode::{Channels, DecodeOptions, EncodeOptions, PublicKey, Region, SigningKey};
 
-#[derive(Debug, Clone, Copy, clap::ValueEnum)]
-enum PixelFormat {
-    Rgb8,
-    Rgba8,
-    Rgb32,
-    Rgba32,
-}
+use crate::error::MessageFormat;
+use crate::formats::{OutputFormat, PixelFormat};
+use crate::png::PngArgs;
+use crate::{batch, bilevel, frames, keygen, sizing, verify};
 
 #[derive(Debug, clap::Args)]
 pub struct Args {
-    #[clap(help = "Path to input file")]
-    input_file: PathBuf,
+    #[clap(
+        help = "Path to input file, or `-` for stdin",
+        required_unless_present = "batch"
+    )]
+    input_file: Option<PathBuf>,
+
+    #[clap(
+        help = "Path to output file, or `-` for stdout",
+        required_unless_present = "batch"
+    )]
+    output_file: Option<PathBuf>,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+
+    #[clap(flatten)]
+    batch: batch::BatchArgs,
 
-    #[clap(help = "Path to output file")]
-    output_file: PathBuf,
+    #[clap(flatten)]
+    sizing: sizing::SizingArgs,
+
+    #[clap(
+        long = "background",
+        value_name = "IMAGE",
+        help = "Write the data into a copy of an existing image instead of a new one",
+        conflicts_with_all = ["aspect_ratio", "width", "height", "exact", "preset", "max_dimension", "block_size", "markers", "bilevel"]
+    )]
+    background: Option<PathBuf>,
+
+    #[clap(
+        long = "region",
+        value_name = "X,Y,W,H",
+        help = "Region of the background image to write the data to [default: the whole image]",
+        value_parser = sizing::parse_region,
+        requires = "background"
+    )]
+    region: Option<Region>,
 
     #[clap(
-        short = 'r',
-        long = "ratio",
-        help = "Aspect ratio of the output image",
-        default_value = "1.0"
+        long = "channels",
+        help = "Channels of each pixel to write the data to, leaving the others untouched (e.g. `b` or `rgb`) [default: all]",
+        value_parser = sizing::parse_channels,
+        conflicts_with = "bilevel"
     )]
-    aspect_ratio: f64,
+    channels: Option<Channels>,
+
+    #[clap(
+        long = "label",
+        value_name = "TEXT",
+        help = "Draw a caption in a strip at the top of the image [default: the input file name, size and date]",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with_all = ["background", "bilevel"]
+    )]
+    label: Option<Option<String>>,
 
     #[clap(
         short = 'f',
         long = "format",
-        help = "Format of the output image",
-        default_value = "png"
+        help = "Format of the output image [default: from the output file extension, or png]"
     )]
-    format: OutputFormat,
+    format: Option<OutputFormat>,
+
+    #[clap(
+        long = "frames",
+        help = "Spread the data across the frames of an animated PNG or GIF, or the pages of a TIFF file",
+        default_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "background"
+    )]
+    frames: u32,
+
+    #[clap(
+        long = "shares",
+        value_name = "N",
+        help = "Split the input into N shares of a secret, each written to its own image \
+                next to the output with its number added to the name",
+        value_parser = clap::value_parser!(u8).range(1..),
+        requires = "threshold",
+        conflicts_with_all = ["background", "frames"]
+    )]
+    shares: Option<u8>,
+
+    #[clap(
+        long = "threshold",
+        value_name = "K",
+        help = "Number of shares needed to recover the input. Fewer reveal nothing about it",
+        value_parser = clap::value_parser!(u8).range(1..),
+        requires = "shares"
+    )]
+    threshold: Option<u8>,
+
+    #[clap(
+        long = "recipient",
+        value_name = "KEY",
+        help = "Encrypt the data for a public key, or the public key of a key file written \
+                by `imgcode keygen`. Can be given several times",
+        value_parser = keygen::parse_recipient,
+        conflicts_with_all = ["background", "verify"]
+    )]
+    recipients: Vec<PublicKey>,
+
+    #[clap(
+        long = "sign",
+        value_name = "KEY_FILE",
+        help = "Sign the image with the key file written by `imgcode keygen --signing`"
+    )]
+    sign: Option<PathBuf>,
 
     #[clap(
         short = 'p',
         long = "pixel",
-        help = "Format of the pixels in the image",
-        default_value = "rgb8"
+        help = "Format of the pixels in the image [default: depends on the output format]"
     )]
+    pixel_format: Option<PixelFormat>,
+
+    #[clap(flatten)]
+    png: PngArgs,
+
+    #[clap(
+        long = "verify",
+        help = "Decode the encoded image and check it holds the input before writing it"
+    )]
+    verify: bool,
+}
+
+pub fn command(global_args: &CliArgs, args: &Args) -> Result<()> {
+    if args.batch.is_enabled() {
+        let ext = args.format.unwrap_or(OutputFormat::Png).extension();
+        return batch::run(global_args, &args.batch, "{name}.{ext}", Some(ext), |job| {
+            encode(global_args, args, &job.input, &job.output, false)
+        });
+    }
+
+    match (&args.input_file, &args.output_file) {
+        (Some(input_file), Some(output_file)) => {
+            let progress = global_args.message_format == MessageFormat::Human;
+            encode(global_args, args, input_file, output_file, progress)
+        }
+        _ => unreachable!("input and output files are required outside of a batch"),
+    }
+}
+
+/// Encode the contents of `input_file` to an image at `output_file`.
+///
+/// A progress bar is drawn on stderr if `progress` is set and stderr is a terminal.
+fn encode(
+    global_args: &CliArgs,
+    args: &Args,
+    input_file: &Path,
+    output_file: &Path,
+    progress: bool,
+) -> Result<()> {
+    let inferred_format = if util::is_stdio(output_file) {
+        None
+    } else {
+        OutputFormat::from_path(output_file)
+    };
+
+    let format = match (args.format, inferred_format) {
+        (Some(format), Some(inferred)) if format != inferred => {
+            crate::error::warn(
+                &format!(
+                    "writing a {format} image to `{}` which looks like a {inferred} file",
+                    output_file.display()
+                ),
+                global_args.message_format,
+            );
+            format
+        }
+        (Some(format), _) | (None, Some(format)) => format,
+        (None, None) => OutputFormat::Png,
+    };
+
+    let pixel_format = args
+        .pixel_format
+        .unwrap_or_else(|| format.default_pixel_format());
+
+    if !format.supports(pixel_format) {
+        return Err(imgcode::Error::UnsupportedFormat).with_context(|| {
+            format!(
+                "{format} images cannot hold {} pixels",
+                imgcode::PixelFormat::from(pixel_format)
+            )
+        });
+    }
+
+    if args.frames > 1 && !frames::supports_frames(format) {
+        return Err(imgcode::Error::UnsupportedFormat)
+            .with_context(|| format!("{format} images cannot hold several frames"));
+    }
+
+    if let Some(channels) = args.channels {
+        if !channels.fits(pixel_format.into()) {
+            return Err(imgcode::Error::InvalidChannels).with_context(|| {
+                format!(
+                    "{} pixels do not have all of the channels `{channels}`",
+                    imgcode::PixelFormat::from(pixel_format)
+                )
+            });
+        }
+    }
+
+    // Each share is written to its own file.
+    let paths = match args.shares {
+        Some(count) => {
+            if count > 1 && util::is_stdio(output_file) {
+                bail!("{count} shares cannot all be written to stdout");
+            }
+            (0..usize::from(count))
+                .map(|i| util::numbered_path(output_file, i))
+                .collect()
+        }
+        None => vec![output_file.to_path_buf()],
+    };
+
+    if let (Some(count), Some(threshold)) = (args.shares, args.threshold) {
+        if threshold > count {
+            bail!("a threshold of {threshold} needs at least as many shares, not {count}");
+        }
+    }
+
+    let data = util::read_input(input_file)
+        .with_context(|| format!("unable to read input `{}`", input_file.display()))?;
+
+    let mut outputs = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let output = util::create_output(path, args.force)
+            .with_context(|| format!("unable to open output `{}`", path.display()))?;
+        outputs.push(output);
+    }
+
+    let signing_key = args
+        .sign
+        .as_deref()
+        .map(|x| keygen::read_secret::<SigningKey>(x, "signing key"))
+        .transpose()?;
+
+    let (images, placement) = match &args.background {
+        Some(path) => {
+            let (i, placement) = encode_into(
+                path,
+                args.region,
+                args.channels,
+                signing_key,
+                pixel_format,
+                &data,
+            )?;
+            (vec![i], placement)
+        }
+        None => {
+            let mut options = args.sizing.options();
+            let mut placement = None;
+
+            if !args.recipients.is_empty() {
+                options = options.recipients(args.recipients.iter().copied());
+            }
+            if let Some(key) = signing_key {
+                options = options.signing_key(key);
+            }
+
+            let channels = match args.channels {
+                None if format == OutputFormat::Gif && !args.sizing.is_bilevel() => {
+                    check_palette_safe(args, pixel_format)?;
+                    Some(Channels::RED)
+                }
+                channels => channels,
+            };
+
+            if let Some(channels) = channels {
+                options = options.channels(channels);
+                placement = Some(DecodeOptions::new().channels(channels));
+            }
+
+            if let Some(label) = &args.label {
+                let label = label.clone().unwrap_or_else(|| caption(input_file, &data));
+                options = options.label(label);
+            }
+
+            let images = if let (Some(count), Some(threshold)) = (args.shares, args.threshold) {
+                encode_shares(&options, threshold, count, pixel_format, &data)?
+            } else if args.frames > 1 {
+                encode_frames(&options, args.frames, pixel_format, &data)?
+            } else {
+                vec![encode_new(&options, pixel_format, &data, progress)?]
+            };
+
+            (images, placement)
+        }
+    };
+
+    let bilevel = args.sizing.is_bilevel();
+    let mut files = Vec::with_capacity(paths.len());
+    match images.as_slice() {
+        images if args.shares.is_some() => {
+            for i in images {
+                files.push(Cursor::new(image_bytes(format, i, bilevel, &args.png)?));
+            }
+        }
+        [i] => files.push(Cursor::new(image_bytes(format, i, bilevel, &args.png)?)),
+        images => {
+            // Like single images, frames are encoded in memory first.
+            let mut file = Cursor::new(Vec::new());
+            frames::write_frames(format, images, bilevel, &args.png, &mut file)?;
+            files.push(file);
+        }
+    }
+
+    if args.verify {
+        verify_files(
+            &mut files,
+            args.shares.is_some(),
+            format,
+            &data,
+            placement.as_ref(),
+        )
+        .with_context(|| {
+            format!(
+                "{format} image with {} pixels does not hold the input",
+                imgcode::PixelFormat::from(pixel_format)
+            )
+        })?;
+    }
+
+    for ((path, mut output), file) in paths.iter().zip(outputs).zip(files) {
+        output.write_all(file.get_ref())?;
+        output
+            .persist()
+            .with_context(|| format!("unable to write output `{}`", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Decode the encoded `files` in `format` and check that they hold
+/// `data`, either together as `shares` of it or as a single file.
+fn verify_files(
+    files: &mut [Cursor<Vec<u8>>],
+    shares: bool,
+    format: OutputFormat,
+    data: &[u8],
+    placement: Option<&DecodeOptions>,
+) -> Result<()> {
+    let hint = Some(format.image_format());
+
+    if shares {
+        let mut images = Vec::with_capacity(files.len());
+        for file in files {
+            file.set_position(0);
+            images.push(util::decode_image(file, hint)?.0);
+        }
+        return verify::check_shares(&images, hint, data, placement);
+    }
+
+    let file = &mut files[0];
+    file.set_position(0);
+    let (decoded, container) = frames::read_frames(file, hint)?;
+    verify::check_frames(decoded, container, data, placement, None)
+}
+
+/// Write `image` to `writer` in `format`. Images which are `bilevel` are
+/// written with one bit per pixel where the format allows it.
+pub fn write_image<W>(
+    format: OutputFormat,
+    image: &DynamicImage,
+    bilevel: bool,
+    png: &PngArgs,
+    writer: &mut W,
+) -> Result<()>
+where
+    W: Write + Seek,
+{
+    match format {
+        OutputFormat::Png if bilevel => png.write_bilevel_image(&image.to_luma8(), writer),
+        OutputFormat::Tiff if bilevel => bilevel::write_tiff(&[image.to_luma8()], writer),
+        _ => format.write_image(image, writer, png),
+    }
+}
+
+/// Get the bytes of `image` written in `format`, like [`write_image()`].
+///
+/// Image encoders need to seek in their output, which stdout does not
+/// support, so images are encoded in memory before being written out.
+pub fn image_bytes(
+    format: OutputFormat,
+    image: &DynamicImage,
+    bilevel: bool,
+    png: &PngArgs,
+) -> Result<Vec<u8>> {
+    let mut buf = Cursor::new(Vec::new());
+    write_image(format, image, bilevel, png, &mut buf)?;
+    Ok(buf.into_inner())
+}
+
+/// Write `data` to a new image with `pixel_format` pixels.
+fn encode_new(
+    options: &EncodeOptions,
+    pixel_format: PixelFormat,
+    data: &[u8],
+    progress: bool,
+) -> Result<DynamicImage> {
+    use imgcode::to_image_with_progress as to_image;
+
+    let bar = util::progress_bar("encoding", progress);
+    let report = util::report_progress(&bar);
+
+    let i: DynamicImage = match pixel_format {
+        PixelFormat::Rgb8 => to_image::<image::RgbImage, _>(data, options, report)?.into(),
+        PixelFormat::Rgba8 => to_image::<image::RgbaImage, _>(data, options, report)?.into(),
+        PixelFormat::Rgb32 => to_image::<image::Rgb32FImage, _>(data, options, report)?.into(),
+        PixelFormat::Rgba32 => to_image::<image::Rgba32FImage, _>(data, options, report)?.into(),
+    };
+    bar.finish_and_clear();
+
+    Ok(i)
+}
+
+/// Write `data` spread across `count` new images with `pixel_format` pixels.
+fn encode_frames(
+    options: &EncodeOptions,
+    count: u32,
     pixel_format: PixelFormat,
+    data: &[u8],
+) -> Result<Vec<DynamicImage>> {
+    use imgcode::to_frames;
+
+    fn into_dynamic<I: Into<DynamicImage>>(frames: Vec<I>) -> Vec<DynamicImage> {
+        frames.into_iter().map(Into::into).collect()
+    }
+
+    Ok(match pixel_format {
+        PixelFormat::Rgb8 => into_dynamic(to_frames::<image::RgbImage>(data, count, options)?),
+        PixelFormat::Rgba8 => into_dynamic(to_frames::<image::RgbaImage>(data, count, options)?),
+        PixelFormat::Rgb32 => into_dynamic(to_frames::<image::Rgb32FImage>(data, count, options)?),
+        PixelFormat::Rgba32 => {
+            into_dynamic(to_frames::<image::Rgba32FImage>(data, count, options)?)
+        }
+    })
 }
 
-pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
-    let mut input = util::open_buffered_read(File::options().read(true), &args.input_file)
-        .with_context(|| format!("unable to open input `{}`", args.input_file.display()))?;
+/// Split `data` into `count` shares written to new images with
+/// `pixel_format` pixels, any `threshold` of which recover it.
+fn encode_shares(
+    options: &EncodeOptions,
+    threshold: u8,
+    count: u8,
+    pixel_format: PixelFormat,
+    data: &[u8],
+) -> Result<Vec<DynamicImage>> {
+    use imgcode::to_shares;
+
+    fn into_dynamic<I: Into<DynamicImage>>(shares: Vec<I>) -> Vec<DynamicImage> {
+        shares.into_iter().map(Into::into).collect()
+    }
 
-    let mut output = util::open_buffered_write(File::options().write(true), &args.output_file)
-        .with_context(|| format!("unable to open input `{}`", args.output_file.display()))?;
+    Ok(match pixel_format {
+        PixelFormat::Rgb8 => into_dynamic(to_shares::<image::RgbImage>(
+            data, threshold, count, options,
+        )?),
+        PixelFormat::Rgba8 => into_dynamic(to_shares::<image::RgbaImage>(
+            data, threshold, count, options,
+        )?),
+        PixelFormat::Rgb32 => into_dynamic(to_shares::<image::Rgb32FImage>(
+            data, threshold, count, options,
+        )?),
+        PixelFormat::Rgba32 => into_dynamic(to_shares::<image::Rgba32FImage>(
+            data, threshold, count, options,
+        )?),
+    })
+}
 
-    let mut data = Vec::with_capacity(2048);
-    input
-        .read_to_end(&mut data)
-        .context("unable to read from input")?;
+/// Check that an image encoded with `args` only has the 256 colours a GIF
+/// image can hold once its data is restricted to the red channel.
+///
+/// Label strips and blocks add black and white pixels to the colours of
+/// the data, and GIF images only have fully transparent or opaque pixels,
+/// so these are only allowed in bilevel images.
+fn check_palette_safe(args: &Args, pixel_format: PixelFormat) -> Result<()> {
+    if pixel_format != PixelFormat::Rgb8 {
+        return Err(imgcode::Error::UnsupportedFormat)
+            .context("gif images only keep the data of rgb8 pixels outside of bilevel images");
+    }
 
-    match args.pixel_format {
-        PixelFormat::Rgb8 => imgcode::to_image::<image::RgbImage>(&data, args.aspect_ratio)
-            .write_to(&mut output, args.format)?,
-        PixelFormat::Rgba8 => imgcode::to_image::<image::RgbaImage>(&data, args.aspect_ratio)
-            .write_to(&mut output, args.format)?,
-        PixelFormat::Rgb32 => imgcode::to_image::<image::Rgb32FImage>(&data, args.aspect_ratio)
-            .write_to(&mut output, args.format)?,
-        PixelFormat::Rgba32 => imgcode::to_image::<image::Rgba32FImage>(&data, args.aspect_ratio)
-            .write_to(&mut output, args.format)?,
+    if args.label.is_some() || args.sizing.has_blocks() {
+        return Err(imgcode::Error::UnsupportedFormat).context(
+            "gif images only keep 256 colours, too few for a label or blocks \
+             outside of bilevel images",
+        );
     }
 
     Ok(())
 }
+
+/// Get the default label of an image holding `data` read from `input_file`:
+/// the file name, the size of the data and the current date.
+fn caption(input_file: &Path, data: &[u8]) -> String {
+    let name = match input_file.file_name() {
+        Some(name) if !util::is_stdio(input_file) => name.to_string_lossy(),
+        _ => "stdin".into(),
+    };
+
+    let size = indicatif::HumanBytes(data.len() as u64);
+    let days = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs() / 86_400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!("{name}  {size}  {year:04}-{month:02}-{day:02}")
+}
+
+/// Convert a number of days since 1970-01-01 to a (year, month, day) date.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01, so that leap days end each 400 year era.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Write `data` to `region` and `channels` of the image at `background`,
+/// converted to `pixel_format`, signed with `signing_key` if given.
+/// Returns the image and where in it the data was written to.
+fn encode_into(
+    background: &Path,
+    region: Option<Region>,
+    channels: Option<Channels>,
+    signing_key: Option<SigningKey>,
+    pixel_format: PixelFormat,
+    data: &[u8],
+) -> Result<(DynamicImage, Option<DecodeOptions>)> {
+    use imgcode::to_image_region_with as to_image;
+
+    let mut input = util::open_input(background)
+        .with_context(|| format!("unable to open background `{}`", background.display()))?;
+
+    let (background, _) =
+        util::decode_image(&mut input, image::ImageFormat::from_path(background).ok())
+            .context("unable to decode background image")?;
+
+    let region = region.unwrap_or_else(|| Region::full(background.width(), background.height()));
+    sizing::check_region(region, background.width(), background.height())?;
+
+    let mut options = EncodeOptions::new();
+    let mut placement = DecodeOptions::new().region(region);
+    if let Some(channels) = channels {
+        options = options.channels(channels);
+        placement = placement.channels(channels);
+    }
+    if let Some(key) = signing_key {
+        options = options.signing_key(key);
+    }
+
+    let i: DynamicImage = match pixel_format {
+        PixelFormat::Rgb8 => to_image(background.into_rgb8(), region, data, &options)?.into(),
+        PixelFormat::Rgba8 => to_image(background.into_rgba8(), region, data, &options)?.into(),
+        PixelFormat::Rgb32 => to_image(background.into_rgb32f(), region, data, &options)?.into(),
+        PixelFormat::Rgba32 => to_image(background.into_rgba32f(), region, data, &options)?.into(),
+    };
+
+    Ok((i, Some(placement)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(20_744), (2026, 10, 18));
+    }
+}
@@ -0,0 +1,352 @@
+use std::io;
+use std::process::ExitCode;
+
+/// Help text documenting the exit codes of the program.
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0   Success
+  1   Unclassified error
+  2   Invalid command line usage
+  3   I/O error
+  4   Input is not an imgcode image
+  5   Image was written with an unsupported imgcode version
+  6   Unsupported image or pixel format
+  7   Size limit exceeded
+  8   Payload has an unexpected type or cannot be deserialized
+  9   Output file exists
+  10  Image file is corrupt
+  11  Data does not fit in the requested image dimensions
+  12  Image does not hold the expected data
+  13  Some files of a batch could not be processed
+  14  Payload is encrypted and cannot be decrypted with the given identity or password
+  15  Image is not signed by the trusted key, or was altered after it was signed";
+
+/// Classes of failures reported through the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Other,
+    Usage,
+    Io,
+    NotAnImage,
+    UnsupportedVersion,
+    UnsupportedFormat,
+    SizeLimit,
+    InvalidPayload,
+    OutputExists,
+    CorruptImage,
+    InsufficientCapacity,
+    VerificationFailed,
+    BatchFailed,
+    DecryptionFailed,
+    SignatureFailed,
+}
+
+impl ErrorKind {
+    /// Classify `err` by the first error in its chain that has a known type.
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|e| {
+                if let Some(e) = e.downcast_ref::<imgcode::Error>() {
+                    Some(Self::from_imgcode(e))
+                } else if e.is::<crate::verify::VerifyError>() {
+                    Some(Self::VerificationFailed)
+                } else if e.is::<crate::batch::BatchError>() {
+                    Some(Self::BatchFailed)
+                } else if let Some(e) = e.downcast_ref::<image::ImageError>() {
+                    Some(Self::from_image(e))
+                } else {
+                    e.downcast_ref::<io::Error>().map(Self::from_io)
+                }
+            })
+            .unwrap_or(Self::Other)
+    }
+
+    fn from_imgcode(err: &imgcode::Error) -> Self {
+        use imgcode::Error;
+        match err {
+            Error::SizeLimit => Self::SizeLimit,
+            Error::UnsupportedFormat | Error::InvalidChannels => Self::UnsupportedFormat,
+            Error::InvalidDimensions | Error::InvalidArgument | Error::InvalidKey => Self::Usage,
+            Error::InsufficientCapacity => Self::InsufficientCapacity,
+            Error::InvalidHeader => Self::NotAnImage,
+            Error::UnsupportedVersion => Self::UnsupportedVersion,
+            Error::TypeMismatch | Error::Serialization(_) => Self::InvalidPayload,
+            Error::MissingData | Error::ChecksumMismatch => Self::CorruptImage,
+            Error::Encrypted | Error::WrongIdentity | Error::WrongPassword => {
+                Self::DecryptionFailed
+            }
+            Error::Unsigned | Error::BadSignature | Error::UntrustedSigner => Self::SignatureFailed,
+            Error::Io(e) => Self::from_io(e),
+            _ => Self::Other,
+        }
+    }
+
+    fn from_image(err: &image::ImageError) -> Self {
+        use image::ImageError;
+        match err {
+            ImageError::Decoding(_) => Self::CorruptImage,
+            ImageError::Unsupported(_) => Self::UnsupportedFormat,
+            ImageError::Limits(_) => Self::SizeLimit,
+            ImageError::IoError(e) => Self::from_io(e),
+            _ => Self::Other,
+        }
+    }
+
+    fn from_io(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::AlreadyExists => Self::OutputExists,
+            _ => Self::Io,
+        }
+    }
+
+    /// Exit code of the process for this kind of error.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Other => 1,
+            Self::Usage => 2,
+            Self::Io => 3,
+            Self::NotAnImage => 4,
+            Self::UnsupportedVersion => 5,
+            Self::UnsupportedFormat => 6,
+            Self::SizeLimit => 7,
+            Self::InvalidPayload => 8,
+            Self::OutputExists => 9,
+            Self::CorruptImage => 10,
+            Self::InsufficientCapacity => 11,
+            Self::VerificationFailed => 12,
+            Self::BatchFailed => 13,
+            Self::DecryptionFailed => 14,
+            Self::SignatureFailed => 15,
+        }
+    }
+
+    /// Stable name of this kind of error for machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Other => "other",
+            Self::Usage => "usage",
+            Self::Io => "io",
+            Self::NotAnImage => "not-an-image",
+            Self::UnsupportedVersion => "unsupported-version",
+            Self::UnsupportedFormat => "unsupported-format",
+            Self::SizeLimit => "size-limit",
+            Self::InvalidPayload => "invalid-payload",
+            Self::OutputExists => "output-exists",
+            Self::CorruptImage => "corrupt-image",
+            Self::InsufficientCapacity => "insufficient-capacity",
+            Self::VerificationFailed => "verification-failed",
+            Self::BatchFailed => "batch-failed",
+            Self::DecryptionFailed => "decryption-failed",
+            Self::SignatureFailed => "signature-failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl MessageFormat {
+    /// Find the message format in the raw command line arguments.
+    ///
+    /// Used when the arguments cannot be parsed.
+    pub fn from_raw_args() -> Self {
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let value = match arg.strip_prefix("--message-format") {
+                Some("") => args.next(),
+                Some(x) => x.strip_prefix('=').map(str::to_string),
+                None => continue,
+            };
+
+            if value.as_deref() == Some("json") {
+                return Self::Json;
+            }
+        }
+
+        Self::Human
+    }
+}
+
+#[derive(serde::Serialize)]
+struct JsonWarning<'a> {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    message: &'a str,
+}
+
+/// Print a warning to stderr in `format`.
+pub fn warn(message: &str, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => eprintln!("warning: {message}"),
+        MessageFormat::Json => match serde_json::to_string(&JsonWarning {
+            ty: "warning",
+            message,
+        }) {
+            Ok(x) => eprintln!("{x}"),
+            Err(e) => eprintln!("error: {e}"),
+        },
+    }
+}
+
+#[derive(serde::Serialize)]
+struct JsonError<'a> {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    kind: &'static str,
+    code: u8,
+    message: String,
+    causes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<&'a str>,
+}
+
+/// Print `err` to stderr in `format` and return the matching exit code.
+pub fn report(err: &anyhow::Error, format: MessageFormat) -> ExitCode {
+    let kind = ErrorKind::of(err);
+
+    match format {
+        MessageFormat::Human => eprintln!("error: {err:#}"),
+        MessageFormat::Json => print_json(&JsonError {
+            ty: "error",
+            kind: kind.name(),
+            code: kind.code(),
+            message: format!("{err:#}"),
+            causes: err.chain().map(ToString::to_string).collect(),
+            usage: None,
+        }),
+    }
+
+    ExitCode::from(kind.code())
+}
+
+/// Report an error from parsing the command line arguments.
+///
+/// `--help` and `--version` are also reported as errors by clap and
+/// are printed as usual regardless of `format`.
+pub fn report_usage(err: &clap::Error, format: MessageFormat) -> ExitCode {
+    let kind = ErrorKind::Usage;
+
+    if !err.use_stderr() {
+        let _ = err.print();
+        return ExitCode::SUCCESS;
+    }
+
+    if format == MessageFormat::Human {
+        let _ = err.print();
+        return ExitCode::from(kind.code());
+    }
+
+    let rendered = err.render().to_string();
+    let message = rendered
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("error: ")
+        .to_string();
+
+    print_json(&JsonError {
+        ty: "error",
+        kind: kind.name(),
+        code: kind.code(),
+        causes: vec![message.clone()],
+        message,
+        usage: Some(rendered.trim_end()),
+    });
+
+    ExitCode::from(kind.code())
+}
+
+fn print_json(err: &JsonError) {
+    match serde_json::to_string(err) {
+        Ok(x) => eprintln!("{x}"),
+        Err(e) => eprintln!("error: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    use anyhow::Context;
+
+    const ALL: [ErrorKind; 15] = [
+        ErrorKind::Other,
+        ErrorKind::Usage,
+        ErrorKind::Io,
+        ErrorKind::NotAnImage,
+        ErrorKind::UnsupportedVersion,
+        ErrorKind::UnsupportedFormat,
+        ErrorKind::SizeLimit,
+        ErrorKind::InvalidPayload,
+        ErrorKind::OutputExists,
+        ErrorKind::CorruptImage,
+        ErrorKind::InsufficientCapacity,
+        ErrorKind::VerificationFailed,
+        ErrorKind::BatchFailed,
+        ErrorKind::DecryptionFailed,
+        ErrorKind::SignatureFailed,
+    ];
+
+    #[test]
+    fn test_error_kind_of() {
+        let err = Err::<(), _>(imgcode::Error::InvalidHeader)
+            .context("unable to decode image `a.png`")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::NotAnImage);
+
+        let err = anyhow::Error::new(imgcode::Error::InvalidArgument);
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Usage);
+
+        let exists = io::Error::new(io::ErrorKind::AlreadyExists, "file exists");
+        let err = Err::<(), _>(exists)
+            .context("unable to write output `a.bin`")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::OutputExists);
+
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        let err = anyhow::Error::new(imgcode::Error::Io(missing));
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Io);
+
+        let image = imgcode::to_image::<image::RgbImage>(b"data", 1.0);
+        let err = crate::verify::check(image.into(), b"date", None).unwrap_err();
+        assert_eq!(ErrorKind::of(&err), ErrorKind::VerificationFailed);
+
+        let err = anyhow::Error::new(crate::batch::BatchError {
+            failed: 1,
+            total: 2,
+        });
+        assert_eq!(ErrorKind::of(&err), ErrorKind::BatchFailed);
+
+        let err = anyhow::anyhow!("password is empty").context("unable to read password");
+        assert_eq!(ErrorKind::of(&err), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_codes() {
+        let codes = ALL.map(ErrorKind::code);
+        assert_eq!(codes.iter().collect::<HashSet<_>>().len(), ALL.len());
+        assert_eq!(
+            ALL.map(ErrorKind::name)
+                .iter()
+                .collect::<HashSet<_>>()
+                .len(),
+            ALL.len()
+        );
+
+        // The help lists success and every kind, in order.
+        let listed = EXIT_CODES_HELP
+            .lines()
+            .skip(1)
+            .map(|x| x.split_whitespace().next().unwrap().parse::<u8>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(listed[0], 0);
+        assert_eq!(listed[1..], codes);
+    }
+}
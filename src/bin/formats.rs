@@ -0,0 +1,269 @@
+use std::io::{Seek, Write};
+
+use anyhow::{bail, Result};
+use image::DynamicImage;
+
+use crate::png::PngArgs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Png,
+    Jpg,
+    Gif,
+    Ico,
+    Bmp,
+    OpenExr,
+    Tiff,
+    Qoi,
+    /// Binary PPM
+    Pnm,
+    /// PNM with arbitrary channels (PAM)
+    Pam,
+    Tga,
+    Farbfeld,
+    /// Lossless WebP
+    Webp,
+}
+
+impl OutputFormat {
+    /// Get the format whose files usually have the extension of `path`.
+    pub fn from_path<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        let format = match ext.as_str() {
+            "png" => Self::Png,
+            "jpg" | "jpeg" => Self::Jpg,
+            "gif" => Self::Gif,
+            "ico" => Self::Ico,
+            "bmp" => Self::Bmp,
+            "exr" => Self::OpenExr,
+            "tif" | "tiff" => Self::Tiff,
+            "qoi" => Self::Qoi,
+            "pnm" | "ppm" => Self::Pnm,
+            "pam" => Self::Pam,
+            "tga" => Self::Tga,
+            "ff" | "farbfeld" => Self::Farbfeld,
+            "webp" => Self::Webp,
+            _ => return None,
+        };
+
+        Some(format)
+    }
+
+    /// Get the usual file extension of this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpg => "jpg",
+            Self::Gif => "gif",
+            Self::Ico => "ico",
+            Self::Bmp => "bmp",
+            Self::OpenExr => "exr",
+            Self::Tiff => "tiff",
+            Self::Qoi => "qoi",
+            Self::Pnm => "ppm",
+            Self::Pam => "pam",
+            Self::Tga => "tga",
+            Self::Farbfeld => "ff",
+            Self::Webp => "webp",
+        }
+    }
+
+    /// Get the format of the `image` crate images in this format are decoded as.
+    pub fn image_format(self) -> image::ImageFormat {
+        use image::ImageFormat;
+        match self {
+            Self::Png => ImageFormat::Png,
+            Self::Jpg => ImageFormat::Jpeg,
+            Self::Gif => ImageFormat::Gif,
+            Self::Ico => ImageFormat::Ico,
+            Self::Bmp => ImageFormat::Bmp,
+            Self::OpenExr => ImageFormat::OpenExr,
+            Self::Tiff => ImageFormat::Tiff,
+            Self::Qoi => ImageFormat::Qoi,
+            Self::Pnm | Self::Pam => ImageFormat::Pnm,
+            Self::Tga => ImageFormat::Tga,
+            Self::Farbfeld => ImageFormat::Farbfeld,
+            Self::Webp => ImageFormat::WebP,
+        }
+    }
+
+    /// Get the pixel format used for this format when none is given.
+    pub fn default_pixel_format(self) -> PixelFormat {
+        match self {
+            Self::OpenExr => PixelFormat::Rgb32,
+            Self::Ico | Self::Farbfeld | Self::Webp => PixelFormat::Rgba8,
+            _ => PixelFormat::Rgb8,
+        }
+    }
+
+    /// Check whether images with pixels in `pixel_format` can be
+    /// written in this format.
+    pub fn supports(self, pixel_format: PixelFormat) -> bool {
+        use OutputFormat::*;
+        use PixelFormat::*;
+        match self {
+            OpenExr => matches!(pixel_format, Rgb32 | Rgba32),
+            Jpg | Pnm | Pam => matches!(pixel_format, Rgb8),
+            Ico | Farbfeld | Webp => matches!(pixel_format, Rgba8),
+            Png | Gif | Bmp | Tiff | Qoi | Tga => {
+                matches!(pixel_format, Rgb8 | Rgba8)
+            }
+        }
+    }
+
+    /// Write `image` to `writer` in this format.
+    pub fn write_image<W>(self, image: &DynamicImage, writer: &mut W, png: &PngArgs) -> Result<()>
+    where
+        W: Write + Seek,
+    {
+        use image::codecs::pnm::{PnmSubtype, SampleEncoding};
+        use image::ImageOutputFormat as Format;
+
+        let format = match self {
+            Self::Png => return png.write_image(image, writer),
+            Self::Jpg => Format::Jpeg(100),
+            Self::Gif => Format::Gif,
+            Self::Ico => Format::Ico,
+            Self::Bmp => Format::Bmp,
+            Self::OpenExr => Format::OpenExr,
+            Self::Tiff => Format::Tiff,
+            Self::Qoi => Format::Qoi,
+            Self::Pnm => Format::Pnm(PnmSubtype::Pixmap(SampleEncoding::Binary)),
+            Self::Pam => Format::Pnm(PnmSubtype::ArbitraryMap),
+            Self::Tga => Format::Tga,
+            Self::Farbfeld => {
+                // Farbfeld only stores 16-bit channels. Widening
+                // 8-bit channels is lossless.
+                let image = DynamicImage::ImageRgba16(image.to_rgba16());
+                image.write_to(writer, Format::Farbfeld)?;
+                return Ok(());
+            }
+            Self::Webp => {
+                let color = match image {
+                    DynamicImage::ImageRgb8(_) => image_webp::ColorType::Rgb8,
+                    DynamicImage::ImageRgba8(_) => image_webp::ColorType::Rgba8,
+                    _ => bail!("lossless webp only supports 8-bit rgb and rgba pixels"),
+                };
+
+                image_webp::WebPEncoder::new(writer).encode(
+                    image.as_bytes(),
+                    image.width(),
+                    image.height(),
+                    color,
+                )?;
+                return Ok(());
+            }
+        };
+
+        image.write_to(writer, format)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use clap::ValueEnum;
+        match self.to_possible_value() {
+            Some(x) => write!(f, "{}", x.get_name()),
+            None => write!(f, "{self:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PixelFormat {
+    Rgb8,
+    Rgba8,
+    Rgb32,
+    Rgba32,
+}
+
+impl From<PixelFormat> for imgcode::PixelFormat {
+    fn from(value: PixelFormat) -> Self {
+        match value {
+            PixelFormat::Rgb8 => Self::Rgb8,
+            PixelFormat::Rgba8 => Self::Rgba8,
+            PixelFormat::Rgb32 => Self::Rgb32,
+            PixelFormat::Rgba32 => Self::Rgba32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use clap::ValueEnum;
+
+    fn encode(pixel_format: PixelFormat, data: &[u8]) -> DynamicImage {
+        match pixel_format {
+            PixelFormat::Rgb8 => imgcode::to_image::<image::RgbImage>(data, 1.0).into(),
+            PixelFormat::Rgba8 => imgcode::to_image::<image::RgbaImage>(data, 1.0).into(),
+            PixelFormat::Rgb32 => imgcode::to_image::<image::Rgb32FImage>(data, 1.0).into(),
+            PixelFormat::Rgba32 => imgcode::to_image::<image::Rgba32FImage>(data, 1.0).into(),
+        }
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(OutputFormat::from_path("a/b.PNG"), Some(OutputFormat::Png));
+        assert_eq!(
+            OutputFormat::from_path("b.exr"),
+            Some(OutputFormat::OpenExr)
+        );
+        assert_eq!(
+            OutputFormat::from_path("b.tar.ff"),
+            Some(OutputFormat::Farbfeld)
+        );
+        assert_eq!(OutputFormat::from_path("b.txt"), None);
+        assert_eq!(OutputFormat::from_path("-"), None);
+
+        for &format in OutputFormat::value_variants() {
+            let path = format!("b.{}", format.extension());
+            assert_eq!(OutputFormat::from_path(path), Some(format));
+        }
+    }
+
+    #[test]
+    fn test_default_pixel_format_supported() {
+        for &format in OutputFormat::value_variants() {
+            assert!(format.supports(format.default_pixel_format()), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn test_lossless_round_trip() {
+        let data = (0..=255u8).cycle().take(3000).collect::<Vec<_>>();
+
+        for &format in OutputFormat::value_variants() {
+            if matches!(format, OutputFormat::Jpg | OutputFormat::Gif) {
+                continue;
+            }
+
+            for &pixel_format in PixelFormat::value_variants() {
+                if !format.supports(pixel_format) {
+                    continue;
+                }
+
+                let mut file = Cursor::new(Vec::new());
+                format
+                    .write_image(&encode(pixel_format, &data), &mut file, &PngArgs::default())
+                    .unwrap_or_else(|e| panic!("{format:?} {pixel_format:?}: {e}"));
+
+                file.set_position(0);
+                let decoded = crate::util::decode_image(file, Some(format.image_format()))
+                    .unwrap_or_else(|e| panic!("{format:?} {pixel_format:?}: {e}"));
+                let decoded = crate::util::with_image!(decoded.0, |v| imgcode::from_image(v))
+                    .unwrap_or_else(|e| panic!("{format:?} {pixel_format:?}: {e}"));
+
+                assert_eq!(data, decoded, "{format:?} {pixel_format:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use super::command_prelude::*;
+
+use std::path::PathBuf;
+
+use image::DynamicImage;
+use imgcode::FountainOptions;
+
+use crate::encode;
+use crate::formats::{OutputFormat, PixelFormat};
+use crate::png::PngArgs;
+use crate::sizing;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Code a file into a set of images, any of which can be lost
+    Encode(EncodeArgs),
+    /// Read a file back from whichever images of its set arrived
+    Decode(DecodeArgs),
+}
+
+#[derive(Debug, clap::Args)]
+struct EncodeArgs {
+    #[clap(help = "Path to input file, or `-` for stdin")]
+    input_file: PathBuf,
+
+    #[clap(
+        help = "Path to the first output image. The others are written next to it \
+                with their number added to the name"
+    )]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output files if they exist")]
+    force: bool,
+
+    #[clap(
+        short = 'n',
+        long = "count",
+        help = "Number of images to write",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    count: u32,
+
+    #[clap(
+        long = "image-size",
+        value_name = "SIZE",
+        help = "Bytes of coded data in each image (e.g. `4096`, `64K`). Any images \
+                holding slightly more than the input in total can decode it",
+        default_value = "64K",
+        value_parser = sizing::parse_size
+    )]
+    image_size: u64,
+
+    #[clap(flatten)]
+    sizing: sizing::SizingArgs,
+
+    #[clap(
+        short = 'f',
+        long = "format",
+        help = "Format of the output images [default: from the output file extension, or png]"
+    )]
+    format: Option<OutputFormat>,
+
+    #[clap(
+        short = 'p',
+        long = "pixel",
+        help = "Format of the pixels in the images [default: depends on the output format]"
+    )]
+    pixel_format: Option<PixelFormat>,
+
+    #[clap(flatten)]
+    png: PngArgs,
+}
+
+#[derive(Debug, clap::Args)]
+struct DecodeArgs {
+    #[clap(
+        help = "Paths to the images which arrived, in any order",
+        required = true
+    )]
+    image_files: Vec<PathBuf>,
+
+    #[clap(
+        short = 'o',
+        long = "output",
+        help = "Path to output file, or `-` for stdout",
+        default_value = "-"
+    )]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+}
+
+pub fn command(global_args: &CliArgs, args: &Args) -> Result<()> {
+    match &args.command {
+        Command::Encode(args) => encode(args),
+        Command::Decode(args) => decode(global_args, args),
+    }
+}
+
+fn encode(args: &EncodeArgs) -> Result<()> {
+    let format = args
+        .format
+        .or_else(|| OutputFormat::from_path(&args.output_file))
+        .unwrap_or(OutputFormat::Png);
+    let pixel_format = args
+        .pixel_format
+        .unwrap_or_else(|| format.default_pixel_format());
+
+    if !format.supports(pixel_format) {
+        return Err(imgcode::Error::UnsupportedFormat).with_context(|| {
+            format!(
+                "{format} images cannot hold {} pixels",
+                imgcode::PixelFormat::from(pixel_format)
+            )
+        });
+    }
+
+    // Only the red channel of GIF images keeps its value, which the
+    // images of a set do not account for.
+    if format == OutputFormat::Gif && !args.sizing.is_bilevel() {
+        return Err(imgcode::Error::UnsupportedFormat)
+            .context("gif images of a fountain-coded set must be bilevel");
+    }
+
+    if args.count > 1 && util::is_stdio(&args.output_file) {
+        bail!("{} images cannot all be written to stdout", args.count);
+    }
+
+    let data = util::read_input(&args.input_file)
+        .with_context(|| format!("unable to read input `{}`", args.input_file.display()))?;
+
+    let images = encode_images(args, pixel_format, &data)?;
+
+    let mut files = Vec::with_capacity(images.len());
+    for (i, image) in images.iter().enumerate() {
+        let buf = encode::image_bytes(format, image, args.sizing.is_bilevel(), &args.png)?;
+        files.push((util::numbered_path(&args.output_file, i), buf));
+    }
+
+    util::write_outputs(files, args.force)
+}
+
+/// Code `data` into the images of a set with `pixel_format` pixels.
+fn encode_images(
+    args: &EncodeArgs,
+    pixel_format: PixelFormat,
+    data: &[u8],
+) -> Result<Vec<DynamicImage>> {
+    use imgcode::to_fountain_images;
+
+    fn into_dynamic<I: Into<DynamicImage>>(images: Vec<I>) -> Vec<DynamicImage> {
+        images.into_iter().map(Into::into).collect()
+    }
+
+    let fountain = FountainOptions::new().image_size(args.image_size);
+    let options = args.sizing.options();
+    let count = args.count;
+
+    Ok(match pixel_format {
+        PixelFormat::Rgb8 => into_dynamic(to_fountain_images::<image::RgbImage>(
+            data, count, &fountain, &options,
+        )?),
+        PixelFormat::Rgba8 => into_dynamic(to_fountain_images::<image::RgbaImage>(
+            data, count, &fountain, &options,
+        )?),
+        PixelFormat::Rgb32 => into_dynamic(to_fountain_images::<image::Rgb32FImage>(
+            data, count, &fountain, &options,
+        )?),
+        PixelFormat::Rgba32 => into_dynamic(to_fountain_images::<image::Rgba32FImage>(
+            data, count, &fountain, &options,
+        )?),
+    })
+}
+
+fn decode(global_args: &CliArgs, args: &DecodeArgs) -> Result<()> {
+    // Images are expected to go missing or arrive damaged, so those
+    // which cannot be read are left out rather than failing.
+    let mut images = Vec::with_capacity(args.image_files.len());
+    for path in &args.image_files {
+        let image = util::open_input(path)
+            .with_context(|| format!("unable to open image `{}`", path.display()))
+            .and_then(|mut input| {
+                let hint = image::ImageFormat::from_path(path).ok();
+                util::decode_image(&mut input, hint)
+                    .with_context(|| format!("unable to decode image `{}`", path.display()))
+            });
+
+        match image {
+            Ok((image, _)) => images.push(image),
+            Err(e) => crate::error::warn(&format!("{e:#}"), global_args.message_format),
+        }
+    }
+
+    let data = util::with_images!(&images, |v| imgcode::from_fountain_images(v))?;
+
+    util::write_output(&args.output_file, args.force, &data)
+        .with_context(|| format!("unable to write output `{}`", args.output_file.display()))
+}
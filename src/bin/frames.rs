@@ -0,0 +1,261 @@
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+
+use anyhow::{bail, Result};
+use image::codecs::gif::{GifDecoder, GifEncoder};
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat};
+use imgcode::{DecodeOptions, SecretKey};
+
+use crate::formats::OutputFormat;
+use crate::png::PngArgs;
+use crate::{bilevel, keygen, util};
+
+/// Check whether files in `format` can hold several frames.
+pub fn supports_frames(format: OutputFormat) -> bool {
+    matches!(
+        format,
+        OutputFormat::Png | OutputFormat::Gif | OutputFormat::Tiff
+    )
+}
+
+/// Write `frames`, which all have the dimensions and pixels of the first
+/// one, to `writer` as a single file in `format`: the frames of an
+/// animated PNG or GIF, or the pages of a TIFF file.
+///
+/// `bilevel` is set if the frames are black and white bilevel images,
+/// which are written with one bit per pixel where the format allows it.
+pub fn write_frames<W>(
+    format: OutputFormat,
+    frames: &[DynamicImage],
+    bilevel: bool,
+    png: &PngArgs,
+    writer: &mut W,
+) -> Result<()>
+where
+    W: Write + Seek,
+{
+    match format {
+        OutputFormat::Png => png.write_animation(frames, bilevel, writer),
+        OutputFormat::Gif => {
+            // Frames with at most 256 colours get an exact palette.
+            let frames = frames
+                .iter()
+                .map(|x| image::Frame::new(x.to_rgba8()))
+                .collect::<Vec<_>>();
+            GifEncoder::new(writer).encode_frames(frames)?;
+            Ok(())
+        }
+        OutputFormat::Tiff if bilevel => {
+            let pages = frames
+                .iter()
+                .map(DynamicImage::to_luma8)
+                .collect::<Vec<_>>();
+            bilevel::write_tiff(&pages, writer)
+        }
+        OutputFormat::Tiff => write_tiff(frames, writer),
+        _ => bail!("{format} files cannot hold several frames"),
+    }
+}
+
+/// Write `pages` as the pages of a TIFF file to `writer`.
+fn write_tiff<W>(pages: &[DynamicImage], writer: &mut W) -> Result<()>
+where
+    W: Write + Seek,
+{
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let mut encoder = TiffEncoder::new(writer)?;
+    for page in pages {
+        let (width, height) = (page.width(), page.height());
+        match page {
+            DynamicImage::ImageRgb8(x) => {
+                encoder.write_image::<colortype::RGB8>(width, height, x.as_raw())?;
+            }
+            DynamicImage::ImageRgba8(x) => {
+                encoder.write_image::<colortype::RGBA8>(width, height, x.as_raw())?;
+            }
+            _ => bail!("TIFF pages only support 8-bit rgb and rgba pixels"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Decode the frames of the image in `input`: the frames of an animated
+/// PNG or GIF, the pages of a TIFF file, or the image of other files.
+/// Also returns the format of the image.
+///
+/// The format is guessed like by [`util::decode_image()`](crate::util::decode_image).
+pub fn read_frames<R>(
+    mut input: R,
+    hint: Option<ImageFormat>,
+) -> Result<(Vec<DynamicImage>, Option<ImageFormat>)>
+where
+    R: BufRead + Seek,
+{
+    let format = image::io::Reader::new(&mut input)
+        .with_guessed_format()?
+        .format()
+        .or(hint);
+
+    let frames = match format {
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(&mut input)?;
+            if decoder.is_apng() {
+                // Frames are decoded as RGBA, so they are converted back
+                // to the pixels of the file.
+                let color = decoder.color_type();
+                let frames = decoder.apng().into_frames().collect_frames()?;
+                Some(
+                    frames
+                        .into_iter()
+                        .map(|x| match DynamicImage::from(x.into_buffer()) {
+                            x if color.has_alpha() => x,
+                            x if color.has_color() => x.into_rgb8().into(),
+                            x => x.into_luma8().into(),
+                        })
+                        .collect(),
+                )
+            } else {
+                None
+            }
+        }
+        Some(ImageFormat::Gif) => {
+            let frames = GifDecoder::new(&mut input)?
+                .into_frames()
+                .collect_frames()?;
+            Some(frames.into_iter().map(|x| x.into_buffer().into()).collect())
+        }
+        // Files with pages this cannot read are left to the `image` crate,
+        // which decodes their first page.
+        Some(ImageFormat::Tiff) => read_tiff(&mut input).ok().filter(|x| x.len() > 1),
+        _ => None,
+    };
+
+    if let Some(frames) = frames {
+        return Ok((frames, format));
+    }
+
+    input.seek(SeekFrom::Start(0))?;
+    let (image, format) = util::decode_image(input, hint)?;
+    Ok((vec![image], format))
+}
+
+/// Decode the pages of the TIFF file in `reader`.
+fn read_tiff<R>(mut reader: R) -> Result<Vec<DynamicImage>>
+where
+    R: Read,
+{
+    use tiff::decoder::{Decoder, DecodingResult};
+    use tiff::ColorType;
+
+    let mut file = Vec::new();
+    reader.read_to_end(&mut file)?;
+    let mut decoder = Decoder::new(std::io::Cursor::new(&file))?;
+
+    let mut pages = Vec::new();
+    loop {
+        let (width, height) = decoder.dimensions()?;
+        let page: Option<DynamicImage> = match decoder.colortype()? {
+            ColorType::Gray(1) => bilevel::read_tiff_page(&mut decoder, &file)?.map(Into::into),
+            ColorType::RGB(8) => match decoder.read_image()? {
+                DecodingResult::U8(x) => {
+                    image::RgbImage::from_raw(width, height, x).map(Into::into)
+                }
+                _ => None,
+            },
+            ColorType::RGBA(8) => match decoder.read_image()? {
+                DecodingResult::U8(x) => {
+                    image::RgbaImage::from_raw(width, height, x).map(Into::into)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let Some(page) = page else {
+            bail!("TIFF pages only support 8-bit rgb and rgba pixels, or one bit per pixel");
+        };
+        pages.push(page);
+
+        if !decoder.more_images() {
+            return Ok(pages);
+        }
+        decoder.next_image()?;
+    }
+}
+
+/// Read the data held by `frames`, decoded from a file in `format`, from
+/// the region and channels of each frame given by `options`.
+///
+/// Without `options`, the data of GIF files is read like by
+/// [`util::read_with_options()`]. Data encrypted for recipients is
+/// decrypted with `identity`.
+pub fn from_frames(
+    frames: &[DynamicImage],
+    format: Option<ImageFormat>,
+    options: Option<&DecodeOptions>,
+    identity: Option<&SecretKey>,
+) -> Result<Vec<u8>> {
+    let data = util::read_with_options(format, options.cloned(), |options| {
+        let options = keygen::with_identity(options, identity);
+        util::with_images!(frames, |v| imgcode::from_frames_with(v, &options))
+    })?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use imgcode::Channels;
+
+    #[test]
+    fn test_frames_round_trip() {
+        let data = (0..=255u8).cycle().take(3000).collect::<Vec<_>>();
+
+        for format in [OutputFormat::Png, OutputFormat::Gif, OutputFormat::Tiff] {
+            for bilevel in [false, true] {
+                let mut options = imgcode::EncodeOptions::new().bilevel(bilevel);
+                if format == OutputFormat::Gif && !bilevel {
+                    options = options.channels(Channels::RED);
+                }
+
+                let frames: Vec<image::RgbImage> = imgcode::to_frames(&data, 3, &options).unwrap();
+                let frames = frames.into_iter().map(Into::into).collect::<Vec<_>>();
+
+                let mut file = Cursor::new(Vec::new());
+                write_frames(format, &frames, bilevel, &PngArgs::default(), &mut file)
+                    .unwrap_or_else(|e| panic!("{format:?} {bilevel}: {e}"));
+
+                file.set_position(0);
+                let (decoded, container) = read_frames(file, None).unwrap();
+                assert_eq!(container, Some(format.image_format()));
+                assert_eq!(decoded.len(), 3, "{format:?} {bilevel}");
+
+                let decoded = from_frames(&decoded, container, None, None)
+                    .unwrap_or_else(|e| panic!("{format:?} {bilevel}: {e}"));
+                assert_eq!(decoded, data, "{format:?} {bilevel}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_frame() {
+        let data = vec![7u8; 100];
+        let image = DynamicImage::from(imgcode::to_image::<image::RgbImage>(&data, 1.0));
+
+        let mut file = Cursor::new(Vec::new());
+        image
+            .write_to(&mut file, image::ImageOutputFormat::Tiff)
+            .unwrap();
+
+        file.set_position(0);
+        let (frames, _) = read_frames(file, None).unwrap();
+        assert_eq!(frames, [image]);
+        assert!(!supports_frames(OutputFormat::Bmp));
+    }
+}
@@ -0,0 +1,82 @@
+use super::command_prelude::*;
+
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(help = "Path to image, or `-` for stdin")]
+    image_file: PathBuf,
+
+    #[clap(long = "json", help = "Print information as JSON")]
+    json: bool,
+}
+
+#[derive(serde::Serialize)]
+struct Output {
+    container: Option<String>,
+    #[serde(flatten)]
+    info: imgcode::Info,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    let mut input = util::open_input(&args.image_file)
+        .with_context(|| format!("unable to open image `{}`", args.image_file.display()))?;
+
+    let (i, container) = util::decode_image(
+        &mut input,
+        image::ImageFormat::from_path(&args.image_file).ok(),
+    )?;
+    let container = container.map(|x| format!("{x:?}").to_ascii_lowercase());
+
+    let info = util::with_image!(i, |v| imgcode::probe(&v))?;
+
+    if args.json {
+        let output = Output { container, info };
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        println!("version:      {}", info.version);
+        if let Some(container) = container {
+            println!("container:    {container}");
+        }
+        println!("dimensions:   {}x{}", info.width, info.height);
+        println!("pixel format: {}", info.pixel_format);
+        match info.block_size {
+            Some(size) if info.markers => {
+                println!("block size:   {size}x{size} pixels, in a frame of markers");
+            }
+            Some(size) => println!("block size:   {size}x{size} pixels"),
+            None => {}
+        }
+        if info.bilevel {
+            println!("bilevel:      one bit per pixel");
+        }
+        if info.frames > 1 {
+            println!("frame:        {} of {}", info.frame + 1, info.frames);
+        }
+        if info.threshold != 0 {
+            println!(
+                "share:        {}, {} needed to recover the payload",
+                info.share, info.threshold
+            );
+        }
+        match info.recipients {
+            0 => {}
+            1 => println!("encrypted:    for 1 recipient"),
+            n => println!("encrypted:    for {n} recipients"),
+        }
+        if let Some(signer) = &info.signer {
+            println!("signed by:    {signer}");
+        }
+        if info.label_rows != 0 {
+            println!("label strip:  {} rows", info.label_rows);
+        }
+        println!("payload size: {} bytes", info.payload_size);
+        println!("capacity:     {} bytes", info.capacity);
+        println!("utilisation:  {:.2}%", info.utilisation * 100.0);
+        if info.tag != 0 {
+            println!("type tag:     {:#018x}", info.tag);
+        }
+    }
+
+    Ok(())
+}
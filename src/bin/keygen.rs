@@ -0,0 +1,155 @@
+use super::command_prelude::*;
+
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use imgcode::{DecodeOptions, PublicKey, SecretKey, SigningKey, VerifyingKey};
+
+/// Start of the comment line of a key file holding its public key.
+const PUBLIC_KEY_COMMENT: &str = "# public key: ";
+/// Start of the comment line of a signing key file holding its
+/// verifying key.
+const VERIFYING_KEY_COMMENT: &str = "# verifying key: ";
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(
+        short = 'o',
+        long = "output",
+        help = "Path to the key file to write, or `-` for stdout",
+        default_value = "-"
+    )]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+
+    #[clap(
+        long = "signing",
+        help = "Generate a key to sign images with instead of one to encrypt them for"
+    )]
+    signing: bool,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    if args.signing {
+        let secret = SigningKey::generate().context("unable to generate a key")?;
+        write_key_file(
+            args,
+            VERIFYING_KEY_COMMENT,
+            &secret.verifying_key(),
+            &secret,
+        )
+    } else {
+        let secret = SecretKey::generate().context("unable to generate a key")?;
+        write_key_file(args, PUBLIC_KEY_COMMENT, &secret.public_key(), &secret)
+    }
+}
+
+/// Write `secret` to the output key file, after a comment starting with
+/// `comment` holding its `public` key.
+fn write_key_file(
+    args: &Args,
+    comment: &str,
+    public: &impl Display,
+    secret: &impl Display,
+) -> Result<()> {
+    let mut output = util::create_secret_output(&args.output_file, args.force)
+        .with_context(|| format!("unable to open output `{}`", args.output_file.display()))?;
+    writeln!(output, "{comment}{public}")?;
+    writeln!(output, "{secret}")?;
+    output
+        .persist()
+        .with_context(|| format!("unable to write output `{}`", args.output_file.display()))?;
+
+    // The public key is already in the output when it is stdout.
+    if !util::is_stdio(&args.output_file) {
+        eprintln!("{}{public}", comment.trim_start_matches("# "));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, clap::Args)]
+pub struct IdentityArgs {
+    #[clap(
+        long = "identity",
+        value_name = "KEY_FILE",
+        help = "Key file written by `imgcode keygen` to decrypt data encrypted for its public key"
+    )]
+    identity: Option<PathBuf>,
+}
+
+impl IdentityArgs {
+    /// Read the secret key from the identity file, if one was given.
+    pub fn identity(&self) -> Result<Option<SecretKey>> {
+        self.identity
+            .as_deref()
+            .map(|x| read_secret(x, "identity"))
+            .transpose()
+    }
+}
+
+/// Add `identity`, if any, to `options`.
+pub fn with_identity(options: DecodeOptions, identity: Option<&SecretKey>) -> DecodeOptions {
+    match identity {
+        Some(identity) => options.identity(identity.clone()),
+        None => options,
+    }
+}
+
+/// Read the secret key from the key file at `path`, written by
+/// [`command()`] and called `what` in errors. Lines starting with `#`
+/// are comments.
+pub fn read_secret<K>(path: &Path, what: &str) -> Result<K>
+where
+    K: FromStr<Err = imgcode::Error>,
+{
+    let mut text = String::new();
+    util::open_input(path)
+        .and_then(|mut x| x.read_to_string(&mut text))
+        .with_context(|| format!("unable to read {what} `{}`", path.display()))?;
+
+    let key = text
+        .lines()
+        .map(str::trim)
+        .find(|x| !x.is_empty() && !x.starts_with('#'))
+        .unwrap_or_default();
+
+    key.parse()
+        .with_context(|| format!("{what} `{}` does not hold a matching key", path.display()))
+}
+
+/// Parse a key given either as is or as the path to a key file, whose
+/// public key is read from its comment starting with `comment`.
+fn parse_public<K>(s: &str, comment: &str) -> Result<K, String>
+where
+    K: FromStr,
+{
+    if let Ok(key) = s.parse() {
+        return Ok(key);
+    }
+
+    let text = std::fs::read_to_string(s)
+        .map_err(|e| format!("not a key, nor a readable key file: {e}"))?;
+
+    text.lines()
+        .map(str::trim)
+        .map(|x| x.strip_prefix(comment).unwrap_or(x))
+        .find_map(|x| x.parse().ok())
+        .ok_or_else(|| format!("key file `{s}` does not hold a matching public key"))
+}
+
+/// Parse a recipient given either as a public key or as the path to a
+/// key file.
+pub fn parse_recipient(s: &str) -> Result<PublicKey, String> {
+    parse_public(s, PUBLIC_KEY_COMMENT)
+}
+
+/// Parse a trusted signer given either as a verifying key or as the path
+/// to a signing key file.
+pub fn parse_verifying_key(s: &str) -> Result<VerifyingKey, String> {
+    parse_public(s, VERIFYING_KEY_COMMENT)
+}
@@ -1,12 +1,31 @@
+use std::process::ExitCode;
+
 use anyhow::Result;
 use clap::Parser;
 
+mod batch;
+mod bilevel;
+mod error;
+mod formats;
+mod frames;
+mod pdf;
+mod png;
+mod sizing;
 mod util;
 
+mod capacity;
+mod combine;
 mod decode;
+mod deniable;
 mod encode;
+mod fountain;
+mod info;
+mod keygen;
+mod paper;
+mod verify;
+mod verify_sig;
 
-pub(self) mod command_prelude {
+mod command_prelude {
     pub(crate) use super::CliArgs;
     pub(crate) use crate::util;
 
@@ -15,29 +34,69 @@ pub(self) mod command_prelude {
 
 #[derive(Debug, clap::Subcommand)]
 enum CliCommands {
+    /// Show how much data images of some dimensions can hold, or the dimensions a payload needs
+    Capacity(capacity::Args),
+    /// Recover a secret from images holding enough of its shares
+    Combine(combine::Args),
+    /// Read the file held by an image
     Decode(decode::Args),
+    /// Hide one or two password-protected files in an image
+    Deniable(deniable::Args),
+    /// Write a file into an image
     Encode(encode::Args),
+    /// Code a file into images that decode from any sufficient subset
+    Fountain(fountain::Args),
+    /// Show the header of an image and how full it is
+    Info(info::Args),
+    /// Generate a key pair to encrypt images for or sign them with
+    Keygen(keygen::Args),
+    /// Back up a file on printable pages
+    Paper(paper::Args),
+    /// Check that an image holds exactly the original file
+    Verify(verify::Args),
+    /// Check that an image was signed by a trusted key
+    VerifySig(verify_sig::Args),
 }
 
 #[derive(Debug, Parser)]
+#[clap(after_help = error::EXIT_CODES_HELP)]
 pub struct CliArgs {
     #[clap(subcommand)]
     command: CliCommands,
-}
 
-fn try_main() -> Result<()> {
-    let global_args = CliArgs::parse();
+    #[clap(
+        long = "message-format",
+        help = "Format of error messages",
+        global = true,
+        default_value = "human"
+    )]
+    message_format: error::MessageFormat,
+}
 
+fn try_main(global_args: &CliArgs) -> Result<()> {
     match &global_args.command {
-        CliCommands::Decode(cmd_args) => decode::command(&global_args, cmd_args),
-        CliCommands::Encode(cmd_args) => encode::command(&global_args, cmd_args),
+        CliCommands::Capacity(cmd_args) => capacity::command(global_args, cmd_args),
+        CliCommands::Combine(cmd_args) => combine::command(global_args, cmd_args),
+        CliCommands::Decode(cmd_args) => decode::command(global_args, cmd_args),
+        CliCommands::Deniable(cmd_args) => deniable::command(global_args, cmd_args),
+        CliCommands::Encode(cmd_args) => encode::command(global_args, cmd_args),
+        CliCommands::Fountain(cmd_args) => fountain::command(global_args, cmd_args),
+        CliCommands::Info(cmd_args) => info::command(global_args, cmd_args),
+        CliCommands::Keygen(cmd_args) => keygen::command(global_args, cmd_args),
+        CliCommands::Paper(cmd_args) => paper::command(global_args, cmd_args),
+        CliCommands::Verify(cmd_args) => verify::command(global_args, cmd_args),
+        CliCommands::VerifySig(cmd_args) => verify_sig::command(global_args, cmd_args),
     }
 }
 
-fn main() {
-    if let Err(e) = try_main() {
-        eprintln!("error: {e:#}");
-    }
+fn main() -> ExitCode {
+    let global_args = match CliArgs::try_parse() {
+        Ok(x) => x,
+        Err(e) => return error::report_usage(&e, error::MessageFormat::from_raw_args()),
+    };
 
-    std::process::exit(-1);
+    match try_main(&global_args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => error::report(&e, global_args.message_format),
+    }
 }
@@ -0,0 +1,170 @@
+use super::command_prelude::*;
+
+use std::path::{Path, PathBuf};
+
+use image::GrayImage;
+use imgcode::PaperOptions;
+
+use crate::png::{self, PngArgs};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Lay out a file on printable pages
+    Encode(EncodeArgs),
+    /// Read a file back from scans of its pages
+    Decode(DecodeArgs),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PaperFormat {
+    /// One PNG image per page
+    Png,
+    /// A PDF document with all pages
+    Pdf,
+}
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl From<PageSize> for imgcode::PageSize {
+    fn from(value: PageSize) -> Self {
+        match value {
+            PageSize::A4 => Self::A4,
+            PageSize::Letter => Self::Letter,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct EncodeArgs {
+    #[clap(help = "Path to input file, or `-` for stdin")]
+    input_file: PathBuf,
+
+    #[clap(
+        help = "Path to output file, or `-` for stdout. PNG pages after the first \
+                are written next to it with the page number added to the name"
+    )]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output files if they exist")]
+    force: bool,
+
+    #[clap(
+        long = "dpi",
+        help = "Resolution of the pages in dots per inch",
+        default_value = "300",
+        value_parser = clap::value_parser!(u32).range(50..=1200)
+    )]
+    dpi: u32,
+
+    #[clap(long = "page", help = "Size of the pages", default_value = "a4")]
+    page_size: PageSize,
+
+    #[clap(
+        short = 'f',
+        long = "format",
+        help = "Format of the output [default: from the output file extension, or png]"
+    )]
+    format: Option<PaperFormat>,
+
+    #[clap(flatten)]
+    png: PngArgs,
+}
+
+#[derive(Debug, clap::Args)]
+struct DecodeArgs {
+    #[clap(help = "Paths to scanned pages, in any order", required = true)]
+    scan_files: Vec<PathBuf>,
+
+    #[clap(
+        short = 'o',
+        long = "output",
+        help = "Path to output file, or `-` for stdout",
+        default_value = "-"
+    )]
+    output_file: PathBuf,
+
+    #[clap(long = "force", help = "Overwrite the output file if it exists")]
+    force: bool,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    match &args.command {
+        Command::Encode(args) => encode(args),
+        Command::Decode(args) => decode(args),
+    }
+}
+
+fn encode(args: &EncodeArgs) -> Result<()> {
+    let is_pdf = |path: &Path| {
+        path.extension()
+            .is_some_and(|x| x.eq_ignore_ascii_case("pdf"))
+    };
+    let format = args.format.unwrap_or(if is_pdf(&args.output_file) {
+        PaperFormat::Pdf
+    } else {
+        PaperFormat::Png
+    });
+
+    let data = util::read_input(&args.input_file)
+        .with_context(|| format!("unable to read input `{}`", args.input_file.display()))?;
+
+    let options = PaperOptions::new()
+        .dpi(args.dpi)
+        .page_size(args.page_size.into());
+    let pages = imgcode::to_pages(&data, &options)?;
+
+    let files = match format {
+        PaperFormat::Pdf => {
+            let mut pdf = Vec::new();
+            crate::pdf::write_pages(&pages, args.dpi, &mut pdf)?;
+            vec![(args.output_file.clone(), pdf)]
+        }
+        PaperFormat::Png => {
+            if pages.len() > 1 && util::is_stdio(&args.output_file) {
+                bail!(
+                    "the data needs {} pages, which cannot all be written to stdout as PNG images",
+                    pages.len()
+                );
+            }
+
+            let mut files = Vec::with_capacity(pages.len());
+            for (i, page) in pages.into_iter().enumerate() {
+                let mut buf = Vec::new();
+                args.png.write_image(&page.into(), &mut buf)?;
+                png::set_resolution(&mut buf, args.dpi);
+                files.push((util::numbered_path(&args.output_file, i), buf));
+            }
+            files
+        }
+    };
+
+    util::write_outputs(files, args.force)
+}
+
+fn decode(args: &DecodeArgs) -> Result<()> {
+    let mut scans: Vec<GrayImage> = Vec::with_capacity(args.scan_files.len());
+    for path in &args.scan_files {
+        let mut input = util::open_input(path)
+            .with_context(|| format!("unable to open scan `{}`", path.display()))?;
+
+        let (i, _) = util::decode_image(&mut input, image::ImageFormat::from_path(path).ok())
+            .with_context(|| format!("unable to read scan `{}`", path.display()))?;
+        scans.push(i.into_luma8());
+    }
+
+    let data = imgcode::from_pages(&scans)?;
+
+    util::write_output(&args.output_file, args.force, &data)
+        .with_context(|| format!("unable to write output `{}`", args.output_file.display()))
+}
@@ -0,0 +1,159 @@
+use std::io::Write;
+
+use anyhow::Result;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use image::GrayImage;
+
+use crate::bilevel;
+
+/// Write `pages` as a PDF document to `writer`, with each page the size
+/// of its image printed at `dpi` pixels per inch.
+///
+/// The images are stored with one bit per pixel, so pixels are either
+/// black or white depending on which they are closest to.
+pub fn write_pages<W>(pages: &[GrayImage], dpi: u32, mut writer: W) -> Result<()>
+where
+    W: Write,
+{
+    let mut pdf = Pdf::default();
+
+    // The catalog and the page tree come first, as `1 0 obj` and `2 0 obj`,
+    // followed by the page, contents and image of each page.
+    let page_ids = (0..pages.len()).map(|i| 3 + 3 * i).collect::<Vec<_>>();
+
+    pdf.object(b"<< /Type /Catalog /Pages 2 0 R >>");
+    let kids = page_ids
+        .iter()
+        .map(|id| format!("{id} 0 R"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    pdf.object(format!("<< /Type /Pages /Kids [{kids}] /Count {} >>", pages.len()).as_bytes());
+
+    for (page, id) in pages.iter().zip(page_ids) {
+        let (width, height) = (points(page.width(), dpi), points(page.height(), dpi));
+
+        pdf.object(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {width} {height}] \
+                 /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                id + 2,
+                id + 1
+            )
+            .as_bytes(),
+        );
+
+        let contents = format!("q {width} 0 0 {height} 0 0 cm /Im0 Do Q");
+        pdf.stream("", contents.as_bytes());
+
+        let image = compress(&bilevel::pack_rows(page))?;
+        pdf.stream(
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} \
+                 /ColorSpace /DeviceGray /BitsPerComponent 1 /Filter /FlateDecode",
+                page.width(),
+                page.height()
+            ),
+            &image,
+        );
+    }
+
+    writer.write_all(&pdf.finish())?;
+    Ok(())
+}
+
+/// Get the length in points of `pixels` printed at `dpi`.
+fn points(pixels: u32, dpi: u32) -> String {
+    let points = f64::from(pixels) * 72.0 / f64::from(dpi);
+    format!("{points:.2}")
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// A PDF document being written, with the offset of each of its objects.
+#[derive(Default)]
+struct Pdf {
+    buf: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl Pdf {
+    fn start_object(&mut self) {
+        if self.buf.is_empty() {
+            self.buf.extend(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+        }
+
+        self.offsets.push(self.buf.len());
+        self.buf
+            .extend(format!("{} 0 obj\n", self.offsets.len()).as_bytes());
+    }
+
+    /// Add an object holding `value`.
+    fn object(&mut self, value: &[u8]) {
+        self.start_object();
+        self.buf.extend(value);
+        self.buf.extend(b"\nendobj\n");
+    }
+
+    /// Add a stream object holding `data`, with the entries of its
+    /// dictionary other than its length in `dict`.
+    fn stream(&mut self, dict: &str, data: &[u8]) {
+        self.start_object();
+        self.buf
+            .extend(format!("<< {dict} /Length {} >>\nstream\n", data.len()).as_bytes());
+        self.buf.extend(data);
+        self.buf.extend(b"\nendstream\nendobj\n");
+    }
+
+    /// Add the cross-reference table and trailer and get the document.
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.buf.len();
+        let count = self.offsets.len() + 1;
+
+        self.buf
+            .extend(format!("xref\n0 {count}\n0000000000 65535 f \n").as_bytes());
+        for offset in &self.offsets {
+            self.buf
+                .extend(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        self.buf.extend(
+            format!("trailer\n<< /Size {count} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n")
+                .as_bytes(),
+        );
+
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_pages() {
+        let page = GrayImage::from_fn(300, 150, |x, _| image::Luma([if x < 9 { 0 } else { 255 }]));
+
+        let mut pdf = Vec::new();
+        write_pages(&[page.clone(), page], 300, &mut pdf).unwrap();
+
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/MediaBox [0 0 72.00 36.00]"));
+
+        // Every object starts at the offset given in the cross-reference
+        // table, which is found at the end of the document.
+        let xref = pdf.windows(5).rposition(|x| x == b"xref\n").unwrap();
+        let table = std::str::from_utf8(&pdf[xref..]).unwrap();
+        for (i, entry) in table.lines().skip(3).take(8).enumerate() {
+            let offset = entry[..10].parse::<usize>().unwrap();
+            let object = format!("{} 0 obj", i + 1);
+            assert!(pdf[offset..].starts_with(object.as_bytes()));
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::io::{Cursor, Write};
+
+use anyhow::{bail, Result};
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ColorType, DynamicImage, GrayImage, ImageEncoder};
+
+use crate::bilevel;
+
+/// Maximum number of rows encoded with every filter to find the best one.
+const SAMPLE_ROWS: u32 = 32;
+
+/// Number of evenly spaced bands of rows the sample is made of.
+const SAMPLE_BANDS: u32 = 4;
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum Compression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+impl From<Compression> for CompressionType {
+    fn from(value: Compression) -> Self {
+        match value {
+            Compression::Fast => Self::Fast,
+            Compression::Default => Self::Default,
+            Compression::Best => Self::Best,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum Filter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    /// Choose a filter for each row
+    Adaptive,
+    /// Try all filters on a sample of rows and use the one
+    /// producing the smallest output
+    #[default]
+    Auto,
+}
+
+impl Filter {
+    const CANDIDATES: [FilterType; 6] = [
+        FilterType::NoFilter,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Avg,
+        FilterType::Paeth,
+        FilterType::Adaptive,
+    ];
+}
+
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct PngArgs {
+    #[clap(
+        long = "png-compression",
+        help = "Compression level of PNG images",
+        default_value = "default"
+    )]
+    compression: Compression,
+
+    #[clap(
+        long = "png-filter",
+        help = "Filter applied to the rows of PNG images before compression",
+        default_value = "auto"
+    )]
+    filter: Filter,
+}
+
+impl PngArgs {
+    /// Write `image` as a PNG to `writer`.
+    ///
+    /// Only the `IHDR`, `IDAT` and `IEND` chunks are written. In particular
+    /// there are no `gAMA`, `sRGB` or `iCCP` chunks which could make viewers
+    /// or converters alter the values of the pixels.
+    pub fn write_image<W>(&self, image: &DynamicImage, writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        let filter = match self.filter {
+            Filter::None => FilterType::NoFilter,
+            Filter::Sub => FilterType::Sub,
+            Filter::Up => FilterType::Up,
+            Filter::Avg => FilterType::Avg,
+            Filter::Paeth => FilterType::Paeth,
+            Filter::Adaptive => FilterType::Adaptive,
+            Filter::Auto => self.best_filter(image)?,
+        };
+
+        let (width, height) = (image.width(), image.height());
+        encode(
+            image.as_bytes(),
+            width,
+            height,
+            image.color(),
+            writer,
+            self.compression.into(),
+            filter,
+        )
+    }
+
+    /// Write the black and white `image` as a PNG with one bit per pixel
+    /// to `writer`.
+    ///
+    /// Rows are only a few bytes long, so [`Filter::Auto`] lets the
+    /// encoder choose a filter for each row instead of sampling them.
+    pub fn write_bilevel_image<W>(&self, image: &GrayImage, writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        let mut encoder = self.raw_encoder(writer, image.width(), image.height());
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::One);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&bilevel::pack_rows(image))?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Write `frames`, which all have the dimensions and pixels of the
+    /// first one, as an animated PNG to `writer`. The frames of bilevel
+    /// images are written with one bit per pixel.
+    ///
+    /// As for bilevel images, [`Filter::Auto`] lets the encoder choose
+    /// a filter for each row.
+    pub fn write_animation<W>(
+        &self,
+        frames: &[DynamicImage],
+        bilevel: bool,
+        writer: W,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let Some(first) = frames.first() else {
+            bail!("an animated PNG needs at least one frame");
+        };
+
+        let mut encoder = self.raw_encoder(writer, first.width(), first.height());
+        let (color, depth) = match first {
+            _ if bilevel => (png::ColorType::Grayscale, png::BitDepth::One),
+            DynamicImage::ImageRgb8(_) => (png::ColorType::Rgb, png::BitDepth::Eight),
+            DynamicImage::ImageRgba8(_) => (png::ColorType::Rgba, png::BitDepth::Eight),
+            _ => bail!("animated PNG frames only support 8-bit rgb and rgba pixels"),
+        };
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        encoder.set_animated(u32::try_from(frames.len())?, 0)?;
+
+        let mut writer = encoder.write_header()?;
+        for frame in frames {
+            if bilevel {
+                writer.write_image_data(&bilevel::pack_rows(&frame.to_luma8()))?;
+            } else {
+                writer.write_image_data(frame.as_bytes())?;
+            }
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Get an encoder of the `png` crate for a `width`x`height` image
+    /// with the compression and filter of these arguments.
+    fn raw_encoder<W>(&self, writer: W, width: u32, height: u32) -> png::Encoder<'static, W>
+    where
+        W: Write,
+    {
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_compression(match self.compression {
+            Compression::Fast => png::Compression::Fast,
+            Compression::Default => png::Compression::Default,
+            Compression::Best => png::Compression::Best,
+        });
+
+        let filter = match self.filter {
+            Filter::None => png::FilterType::NoFilter,
+            Filter::Sub => png::FilterType::Sub,
+            Filter::Up => png::FilterType::Up,
+            Filter::Avg => png::FilterType::Avg,
+            Filter::Paeth => png::FilterType::Paeth,
+            Filter::Adaptive | Filter::Auto => {
+                encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+                png::FilterType::Sub
+            }
+        };
+        encoder.set_filter(filter);
+
+        encoder
+    }
+
+    /// Find the filter which compresses a sample of the rows of `image` best.
+    fn best_filter(&self, image: &DynamicImage) -> Result<FilterType> {
+        let (sample, sample_height) = sample_rows(image);
+
+        let mut best = (FilterType::Adaptive, usize::MAX);
+        for filter in Filter::CANDIDATES {
+            let mut buf = Cursor::new(Vec::new());
+            encode(
+                &sample,
+                image.width(),
+                sample_height,
+                image.color(),
+                &mut buf,
+                self.compression.into(),
+                filter,
+            )?;
+
+            if buf.get_ref().len() < best.1 {
+                best = (filter, buf.get_ref().len());
+            }
+        }
+
+        Ok(best.0)
+    }
+}
+
+fn encode<W>(
+    buf: &[u8],
+    width: u32,
+    height: u32,
+    color: ColorType,
+    writer: W,
+    compression: CompressionType,
+    filter: FilterType,
+) -> Result<()>
+where
+    W: Write,
+{
+    PngEncoder::new_with_quality(writer, compression, filter)
+        .write_image(buf, width, height, color)?;
+
+    Ok(())
+}
+
+/// Add a `pHYs` chunk to the PNG file `png` giving its resolution as
+/// `dpi` pixels per inch, so that it is printed at the intended size.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn set_resolution(png: &mut Vec<u8>, dpi: u32) {
+    // Signature and `IHDR` chunk, which must come first.
+    const IHDR_END: usize = 8 + 12 + 13;
+
+    let pixels_per_metre = (f64::from(dpi) / 0.0254).round() as u32;
+
+    let mut chunk = b"pHYs".to_vec();
+    chunk.extend(pixels_per_metre.to_be_bytes());
+    chunk.extend(pixels_per_metre.to_be_bytes());
+    chunk.push(1);
+
+    let mut bytes = 9u32.to_be_bytes().to_vec();
+    bytes.extend(&chunk);
+    bytes.extend(crc32fast::hash(&chunk).to_be_bytes());
+
+    png.splice(IHDR_END..IHDR_END, bytes);
+}
+
+/// Get the pixels of up to [`SAMPLE_ROWS`] rows of `image` along with the
+/// number of rows. The rows are taken from [`SAMPLE_BANDS`] evenly spaced
+/// bands of consecutive rows so filters which look at the previous row are
+/// evaluated fairly.
+fn sample_rows(image: &DynamicImage) -> (Vec<u8>, u32) {
+    let bytes = image.as_bytes();
+    let height = image.height();
+
+    if height <= SAMPLE_ROWS {
+        return (bytes.to_vec(), height);
+    }
+
+    let row_len = bytes.len() / height as usize;
+    let band_len = row_len * (SAMPLE_ROWS / SAMPLE_BANDS) as usize;
+    let stride = row_len * (height / SAMPLE_BANDS) as usize;
+
+    let sample = (0..SAMPLE_BANDS as usize)
+        .flat_map(|band| &bytes[band * stride..band * stride + band_len])
+        .copied()
+        .collect();
+
+    (sample, SAMPLE_ROWS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::ValueEnum;
+
+    /// Get the types of the chunks in the PNG file `png`.
+    fn chunk_types(png: &[u8]) -> Vec<[u8; 4]> {
+        let mut chunks = Vec::new();
+
+        let mut rest = &png[8..];
+        while rest.len() >= 12 {
+            let len = u32::from_be_bytes(rest[0..4].try_into().unwrap()) as usize;
+            chunks.push(rest[4..8].try_into().unwrap());
+            rest = &rest[12 + len..];
+        }
+
+        chunks
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let data = (0..10_000u32)
+            .map(|x| (x.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect::<Vec<_>>();
+        let image = DynamicImage::from(imgcode::to_image::<image::RgbaImage>(&data, 1.0));
+
+        for &compression in Compression::value_variants() {
+            for &filter in Filter::value_variants() {
+                let args = PngArgs {
+                    compression,
+                    filter,
+                };
+
+                let mut png = Vec::new();
+                args.write_image(&image, &mut png).unwrap();
+
+                assert_eq!(
+                    chunk_types(&png),
+                    [*b"IHDR", *b"IDAT", *b"IEND"],
+                    "{compression:?} {filter:?}"
+                );
+
+                let decoded = image::load_from_memory(&png).unwrap();
+                assert_eq!(
+                    imgcode::from_image(decoded.into_rgba8()).unwrap(),
+                    data,
+                    "{compression:?} {filter:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bilevel_png() {
+        let image = GrayImage::from_fn(21, 3, |x, y| {
+            image::Luma([if x % 5 == y { 0 } else { 255 }])
+        });
+
+        for &filter in Filter::value_variants() {
+            let args = PngArgs {
+                filter,
+                ..Default::default()
+            };
+
+            let mut png = Vec::new();
+            args.write_bilevel_image(&image, &mut png).unwrap();
+            assert_eq!(png[24], 1, "{filter:?} bit depth");
+
+            let decoded = image::load_from_memory(&png).unwrap();
+            assert_eq!(decoded.into_luma8(), image, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn test_set_resolution() {
+        let image = DynamicImage::new_luma8(4, 4);
+        let mut png = Vec::new();
+        PngArgs::default().write_image(&image, &mut png).unwrap();
+
+        set_resolution(&mut png, 300);
+        assert_eq!(chunk_types(&png), [*b"IHDR", *b"pHYs", *b"IDAT", *b"IEND"]);
+        assert_eq!(png[41..45], 11811u32.to_be_bytes());
+
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded, image);
+    }
+
+    #[test]
+    fn test_sample_rows() {
+        let image = DynamicImage::new_rgb8(7, 1000);
+
+        let (sample, height) = sample_rows(&image);
+        assert_eq!(height, SAMPLE_ROWS);
+        assert_eq!(sample.len(), 7 * 3 * SAMPLE_ROWS as usize);
+
+        let image = DynamicImage::new_rgb8(7, 10);
+        assert_eq!(sample_rows(&image).1, 10);
+    }
+}
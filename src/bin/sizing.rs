@@ -0,0 +1,419 @@
+use anyhow::Context;
+use imgcode::{Channels, DecodeOptions, Dimensions, EncodeOptions, Region};
+
+/// Named image dimensions.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Preset {
+    /// 1280x720
+    #[clap(name = "720p")]
+    Hd,
+    /// 1920x1080
+    #[clap(name = "1080p")]
+    FullHd,
+    /// 2560x1440
+    #[clap(name = "1440p")]
+    Qhd,
+    /// 3840x2160
+    #[clap(name = "4k")]
+    Uhd,
+    /// Aspect ratio of 1:1
+    Square,
+    /// Aspect ratio of 16:9
+    Widescreen,
+    /// Aspect ratio of 9:16
+    Portrait,
+}
+
+impl From<Preset> for Dimensions {
+    fn from(value: Preset) -> Self {
+        match value {
+            Preset::Hd => Self::Exact(1280, 720),
+            Preset::FullHd => Self::Exact(1920, 1080),
+            Preset::Qhd => Self::Exact(2560, 1440),
+            Preset::Uhd => Self::Exact(3840, 2160),
+            Preset::Square => Self::Ratio(1.0),
+            Preset::Widescreen => Self::Ratio(16.0 / 9.0),
+            Preset::Portrait => Self::Ratio(9.0 / 16.0),
+        }
+    }
+}
+
+/// Parse a byte size (`4096`, `64K`, `1.5MiB`, `2MB`).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        x => return Err(format!("unknown unit `{x}`")),
+    };
+
+    if let Ok(n) = number.parse::<u64>() {
+        return n
+            .checked_mul(multiplier)
+            .ok_or_else(|| "size is too large".to_string());
+    }
+
+    let n: f64 = number.parse().map_err(|e| format!("invalid size: {e}"))?;
+    #[allow(clippy::cast_precision_loss)]
+    let (size, max) = ((n * multiplier as f64).ceil(), u64::MAX as f64);
+
+    // Casting would saturate sizes past the largest `u64`.
+    if size >= max {
+        return Err("size is too large".to_string());
+    }
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    Ok(size as u64)
+}
+
+/// Parse an aspect ratio given either as a number (`1.5`)
+/// or as `width:height` (`16:9`).
+pub fn parse_ratio(s: &str) -> Result<f64, String> {
+    let ratio = match s.split_once(':') {
+        Some((x, y)) => {
+            let x: f64 = x
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid width: {e}"))?;
+            let y: f64 = y
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid height: {e}"))?;
+            x / y
+        }
+        None => s.trim().parse().map_err(|e| format!("{e}"))?,
+    };
+
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err("aspect ratio must be positive and non-zero".to_string())
+    }
+}
+
+/// Parse dimensions given as `WIDTHxHEIGHT` (`1920x1080`).
+pub fn parse_dimensions(s: &str) -> Result<(u32, u32), String> {
+    let (x, y) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| "expected dimensions as `WIDTHxHEIGHT`".to_string())?;
+
+    let x = x
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid width: {e}"))?;
+    let y = y
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid height: {e}"))?;
+
+    if x == 0 || y == 0 {
+        return Err("dimensions must be non-zero".to_string());
+    }
+    Ok((x, y))
+}
+
+/// Parse a region of an image given as `X,Y,WIDTH,HEIGHT` (`0,0,640,32`).
+pub fn parse_region(s: &str) -> Result<Region, String> {
+    let values = s
+        .split(',')
+        .map(|x| x.trim().parse::<u32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values[..] {
+        [_, _, 0, _] | [_, _, _, 0] => Err("region must not be empty".to_string()),
+        [x, y, width, height] => Ok(Region::new(x, y, width, height)),
+        _ => Err("expected region as `X,Y,WIDTH,HEIGHT`".to_string()),
+    }
+}
+
+/// Parse a set of channels given as letters (`b`, `rgb`, `ga`).
+pub fn parse_channels(s: &str) -> Result<Channels, String> {
+    let mut channels = None;
+
+    for c in s.trim().chars() {
+        let channel = match c.to_ascii_lowercase() {
+            'r' => Channels::RED,
+            'g' => Channels::GREEN,
+            'b' => Channels::BLUE,
+            'a' => Channels::ALPHA,
+            x => return Err(format!("unknown channel `{x}`")),
+        };
+
+        channels = Some(channels.map_or(channel, |x| x | channel));
+    }
+
+    channels.ok_or_else(|| "expected channels as letters of `rgba`".to_string())
+}
+
+/// Check that `region` fits in an image with dimensions `width`x`height`.
+pub fn check_region(region: Region, width: u32, height: u32) -> anyhow::Result<()> {
+    if region.fits(width, height) {
+        return Ok(());
+    }
+
+    Err(imgcode::Error::InvalidDimensions).with_context(|| {
+        format!(
+            "region {},{},{},{} does not fit in a {width}x{height} image",
+            region.x, region.y, region.width, region.height
+        )
+    })
+}
+
+/// Where in an image the data was written to.
+#[derive(Debug, clap::Args)]
+pub struct PlacementArgs {
+    #[clap(
+        long = "region",
+        value_name = "X,Y,W,H",
+        help = "Region of the image the data was written to",
+        value_parser = parse_region
+    )]
+    pub region: Option<Region>,
+
+    #[clap(
+        long = "channels",
+        help = "Channels of each pixel the data was written to (e.g. `b` or `rgb`)",
+        value_parser = parse_channels
+    )]
+    pub channels: Option<Channels>,
+}
+
+impl PlacementArgs {
+    /// Get the decoding options for the placement arguments,
+    /// or `None` if the data fills the whole image.
+    pub fn options(&self) -> Option<DecodeOptions> {
+        if self.region.is_none() && self.channels.is_none() {
+            return None;
+        }
+
+        let options = DecodeOptions::new();
+        let options = match self.region {
+            Some(region) => options.region(region),
+            None => options,
+        };
+
+        Some(match self.channels {
+            Some(channels) => options.channels(channels),
+            None => options,
+        })
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SizingArgs {
+    #[clap(
+        short = 'r',
+        long = "ratio",
+        help = "Aspect ratio of the output image (e.g. `1.5` or `16:9`)",
+        value_parser = parse_ratio,
+        conflicts_with_all = ["width", "height", "exact", "preset"]
+    )]
+    aspect_ratio: Option<f64>,
+
+    #[clap(
+        long = "width",
+        help = "Width of the output image",
+        conflicts_with_all = ["exact", "preset"]
+    )]
+    width: Option<u32>,
+
+    #[clap(
+        long = "height",
+        help = "Height of the output image",
+        conflicts_with_all = ["exact", "preset"]
+    )]
+    height: Option<u32>,
+
+    #[clap(
+        long = "exact",
+        value_name = "WxH",
+        help = "Exact dimensions of the output image",
+        value_parser = parse_dimensions,
+        conflicts_with = "preset"
+    )]
+    exact: Option<(u32, u32)>,
+
+    #[clap(
+        long = "preset",
+        help = "Named dimensions or aspect ratio of the output image"
+    )]
+    preset: Option<Preset>,
+
+    #[clap(
+        long = "max-dimension",
+        help = "Maximum width and height of the output image"
+    )]
+    max_dimension: Option<u32>,
+
+    #[clap(
+        long = "block-size",
+        value_name = "PIXELS",
+        help = "Draw each data pixel as a square block, so the image can still be decoded after being upscaled",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    block_size: Option<u32>,
+
+    #[clap(
+        long = "markers",
+        help = "Surround the data with finder and timing patterns, so the image can still be decoded after adding a border, cropping its edges or scaling it by a fraction"
+    )]
+    markers: bool,
+
+    #[clap(
+        long = "bilevel",
+        help = "Store one bit per black or white pixel, written as a 1-bit image to PNG and TIFF files"
+    )]
+    bilevel: bool,
+}
+
+impl SizingArgs {
+    /// Check whether the image stores one bit per pixel.
+    pub fn is_bilevel(&self) -> bool {
+        self.bilevel
+    }
+
+    /// Check whether the image draws its data as blocks.
+    pub fn has_blocks(&self) -> bool {
+        self.block_size.is_some() || self.markers
+    }
+
+    /// Get the encoding options for the sizing arguments.
+    pub fn options(&self) -> EncodeOptions {
+        let dimensions = match (self.width, self.height) {
+            (Some(x), Some(y)) => Dimensions::Exact(x, y),
+            (Some(x), None) => Dimensions::Width(x),
+            (None, Some(y)) => Dimensions::Height(y),
+            (None, None) => {
+                if let Some((x, y)) = self.exact {
+                    Dimensions::Exact(x, y)
+                } else if let Some(preset) = self.preset {
+                    preset.into()
+                } else {
+                    Dimensions::Ratio(self.aspect_ratio.unwrap_or(1.0))
+                }
+            }
+        };
+
+        let mut options = EncodeOptions::new().dimensions(dimensions);
+
+        if let Some(max) = self.max_dimension {
+            options = options.max_dimension(max);
+        }
+
+        if let Some(size) = self.block_size {
+            options = options.block_size(size);
+        }
+
+        options.markers(self.markers).bilevel(self.bilevel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("64K"), Ok(64 << 10));
+        assert_eq!(parse_size("1.5MiB"), Ok(3 << 19));
+        assert_eq!(parse_size(" 2 MB "), Ok(2_000_000));
+        assert_eq!(parse_size("0.5b"), Ok(1));
+
+        assert!(parse_size("16EiB").is_err());
+        assert!(parse_size("1.5.2K").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("").is_err());
+
+        // Too large for a `u64`, whether parsed as an integer or not.
+        assert!(parse_size("18446744073709551615").is_ok());
+        assert!(parse_size("17179869184G").is_err());
+        assert!(parse_size("99999999999999999999").is_err());
+        assert!(parse_size("99999999999999999999.5G").is_err());
+    }
+
+    #[test]
+    fn test_parse_ratio() {
+        assert_eq!(parse_ratio("1.5"), Ok(1.5));
+        assert_eq!(parse_ratio("16:9"), Ok(16.0 / 9.0));
+        assert_eq!(parse_ratio(" 4 : 3 "), Ok(4.0 / 3.0));
+
+        for s in ["0", "0:9", "16:0", "-1", "inf", "16/9", "16:9:1", ":9", ""] {
+            assert!(parse_ratio(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_presets() {
+        let preset = |s| Dimensions::from(Preset::from_str(s, false).unwrap());
+
+        assert_eq!(preset("1080p"), Dimensions::Exact(1920, 1080));
+        assert_eq!(preset("4k"), Dimensions::Exact(3840, 2160));
+        assert_eq!(preset("square"), Dimensions::Ratio(1.0));
+        assert_eq!(preset("portrait"), Dimensions::Ratio(9.0 / 16.0));
+        assert!(Preset::from_str("1080", false).is_err());
+    }
+
+    #[test]
+    fn test_parse_dimensions() {
+        assert_eq!(parse_dimensions("1920x1080"), Ok((1920, 1080)));
+        assert_eq!(parse_dimensions(" 64 X 32 "), Ok((64, 32)));
+
+        for s in [
+            "0x1080",
+            "1920x0",
+            "4294967296x1",
+            "1920*1080",
+            "1920x",
+            "x1080",
+            "",
+        ] {
+            assert!(parse_dimensions(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(parse_region("0,0,640,32"), Ok(Region::new(0, 0, 640, 32)));
+        assert_eq!(parse_region("8, 16, 1, 2"), Ok(Region::new(8, 16, 1, 2)));
+
+        for s in [
+            "0,0,0,32",
+            "0,0,640,0",
+            "0,0,4294967296,32",
+            "0,0,640",
+            "0,0,640,32,1",
+            "0;0;640;32",
+            "0,0,640,",
+            "",
+        ] {
+            assert!(parse_region(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_parse_channels() {
+        assert_eq!(parse_channels("b"), Ok(Channels::BLUE));
+        assert_eq!(
+            parse_channels("RGB"),
+            Ok(Channels::RED | Channels::GREEN | Channels::BLUE)
+        );
+        assert_eq!(parse_channels("ga"), Ok(Channels::GREEN | Channels::ALPHA));
+
+        for s in ["", "x", "rgbx", "r,g"] {
+            assert!(parse_channels(s).is_err(), "{s}");
+        }
+    }
+}
@@ -1,6 +1,17 @@
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Result};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Result, Seek, Stdout, Write};
+use std::path::{Path, PathBuf};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use tempfile::NamedTempFile;
+
+/// Check whether `path` refers to stdin or stdout (`-`).
+pub fn is_stdio<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    path.as_ref() == Path::new("-")
+}
 
 pub fn open_buffered_read<P>(options: &mut OpenOptions, path: P) -> Result<BufReader<File>>
 where
@@ -12,12 +23,402 @@ where
     Ok(f)
 }
 
-pub fn open_buffered_write<P>(options: &mut OpenOptions, path: P) -> Result<BufWriter<File>>
+/// An input file or stdin.
+///
+/// Stdin is read into memory when opened because image decoders
+/// need to seek in their input.
+pub enum Input {
+    Stdin(io::Cursor<Vec<u8>>),
+    File(BufReader<File>),
+}
+
+/// Open `path` for reading, or stdin if `path` is `-`.
+pub fn open_input<P>(path: P) -> Result<Input>
 where
     P: AsRef<Path>,
 {
-    let f = options.write(true).open(path.as_ref())?;
-    let f = BufWriter::new(f);
+    if is_stdio(&path) {
+        let mut data = Vec::with_capacity(2048);
+        io::stdin().lock().read_to_end(&mut data)?;
+        Ok(Input::Stdin(io::Cursor::new(data)))
+    } else {
+        open_buffered_read(File::options().read(true), path).map(Input::File)
+    }
+}
 
-    Ok(f)
+/// Read all of `path`, or stdin if `path` is `-`.
+pub fn read_input<P>(path: P) -> Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let mut data = Vec::with_capacity(2048);
+    open_input(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            Self::Stdin(x) => x.read(buf),
+            Self::File(x) => x.read(buf),
+        }
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        match self {
+            Self::Stdin(x) => x.fill_buf(),
+            Self::File(x) => x.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Self::Stdin(x) => x.consume(amt),
+            Self::File(x) => x.consume(amt),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: io::SeekFrom) -> Result<u64> {
+        match self {
+            Self::Stdin(x) => x.seek(pos),
+            Self::File(x) => x.seek(pos),
+        }
+    }
+}
+
+/// An output file or stdout.
+///
+/// Files are written to a temporary file next to the destination
+/// which only replaces it once [`Output::persist`] is called. If the
+/// output is dropped before that, the temporary file is removed and
+/// the destination is left untouched.
+pub enum Output {
+    Stdout(BufWriter<Stdout>),
+    File {
+        file: BufWriter<NamedTempFile>,
+        path: PathBuf,
+        force: bool,
+    },
+}
+
+/// Create an output writing to `path`, or stdout if `path` is `-`.
+///
+/// Unless `force` is set, fails if `path` already exists.
+pub fn create_output<P>(path: P, force: bool) -> Result<Output>
+where
+    P: AsRef<Path>,
+{
+    // Give the final file the usual permissions instead of the
+    // owner-only ones of temporary files. The umask still applies.
+    create_output_with_mode(path.as_ref(), force, 0o666)
+}
+
+/// Write `data` to `path`, or stdout if `path` is `-`, like
+/// [`create_output()`] followed by [`Output::persist()`].
+pub fn write_output<P>(path: P, force: bool, data: &[u8]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let mut output = create_output(path, force)?;
+    output.write_all(data)?;
+    output.persist()
+}
+
+/// Write each of `files` to its path, like [`write_output()`], opening
+/// every output first so that none is written if any of them exists.
+pub fn write_outputs(files: Vec<(PathBuf, Vec<u8>)>, force: bool) -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    let mut outputs = Vec::with_capacity(files.len());
+    for (path, data) in files {
+        let output = create_output(&path, force)
+            .with_context(|| format!("unable to open output `{}`", path.display()))?;
+        outputs.push((path, output, data));
+    }
+
+    for (path, mut output, data) in outputs {
+        output
+            .write_all(&data)
+            .and_then(|()| output.persist())
+            .with_context(|| format!("unable to write output `{}`", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Like [`create_output()`], but the file is only readable by its owner,
+/// for secrets like keys.
+pub fn create_secret_output<P>(path: P, force: bool) -> Result<Output>
+where
+    P: AsRef<Path>,
+{
+    create_output_with_mode(path.as_ref(), force, 0o600)
+}
+
+/// Create an output writing to `path`, which gets the permissions of
+/// `mode` on Unix.
+fn create_output_with_mode(path: &Path, force: bool, mode: u32) -> Result<Output> {
+    if is_stdio(path) {
+        return Ok(Output::Stdout(BufWriter::new(io::stdout())));
+    }
+
+    if !force && path.try_exists()? {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "file exists (use --force to overwrite)",
+        ));
+    }
+
+    let dir = match path.parent() {
+        Some(x) if !x.as_os_str().is_empty() => x,
+        _ => Path::new("."),
+    };
+
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".imgcode-").suffix(".tmp");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(mode));
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let file = builder.tempfile_in(dir)?;
+
+    Ok(Output::File {
+        file: BufWriter::new(file),
+        path: path.to_path_buf(),
+        force,
+    })
+}
+
+impl Output {
+    /// Flush the output and move it to its destination.
+    pub fn persist(self) -> Result<()> {
+        match self {
+            Self::Stdout(mut x) => x.flush(),
+            Self::File { file, path, force } => {
+                let file = file.into_inner().map_err(io::IntoInnerError::into_error)?;
+                file.as_file().sync_all()?;
+
+                if force {
+                    file.persist(&path)?;
+                } else {
+                    file.persist_noclobber(&path)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Self::Stdout(x) => x.write(buf),
+            Self::File { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Stdout(x) => x.flush(),
+            Self::File { file, .. } => file.flush(),
+        }
+    }
+}
+
+/// Get the path of file `i` of a set of files written to `path`: `path`
+/// itself for the first file, and `path` with the number of the file
+/// added to its name for the others.
+pub fn numbered_path(path: &Path, i: usize) -> PathBuf {
+    if i == 0 {
+        return path.to_path_buf();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{}.{}", i + 1, ext.to_string_lossy()),
+        None => format!("{stem}-{}", i + 1),
+    };
+
+    path.with_file_name(name)
+}
+
+/// Decode the image in `reader`.
+///
+/// The format is guessed from the contents of the image, falling back
+/// to `hint` for formats without a signature like TGA.
+pub fn decode_image<R>(
+    mut input: R,
+    hint: Option<image::ImageFormat>,
+) -> image::ImageResult<(image::DynamicImage, Option<image::ImageFormat>)>
+where
+    R: BufRead + Seek,
+{
+    let mut reader = image::io::Reader::new(&mut input).with_guessed_format()?;
+
+    if reader.format().is_none() {
+        if let Some(format) = hint {
+            reader.set_format(format);
+        }
+    }
+
+    let format = reader.format();
+    match reader.decode() {
+        Ok(image) => Ok((image, format)),
+        // The `image` crate cannot read TIFF files with one bit per pixel.
+        Err(e @ image::ImageError::Unsupported(_)) if format == Some(image::ImageFormat::Tiff) => {
+            input.rewind()?;
+            match crate::bilevel::read_tiff(input) {
+                Ok(Some(image)) => Ok((image.into(), format)),
+                _ => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Create a progress bar with `message` for the bytes of a payload.
+///
+/// The bar is hidden unless it is `enabled` and stderr is a terminal.
+pub fn progress_bar(message: &'static str, enabled: bool) -> ProgressBar {
+    if !enabled || !io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    let style = ProgressStyle::with_template("{msg:>8} [{wide_bar}] {bytes}/{total_bytes} ({eta})")
+        .expect("invalid progress bar template")
+        .progress_chars("=> ");
+
+    ProgressBar::new(0).with_style(style).with_message(message)
+}
+
+/// Get an [`imgcode::Progress`] observer which updates `bar`.
+pub fn report_progress(bar: &ProgressBar) -> impl FnMut(u64, u64) -> imgcode::Control + '_ {
+    |processed, total| {
+        bar.set_length(total);
+        bar.set_position(processed);
+        imgcode::Control::Continue
+    }
+}
+
+/// Evaluate `$body`, which must be an [`imgcode::Result`], with `$image` bound
+/// to the typed image inside the [`image::DynamicImage`] `$dynamic`.
+///
+/// Evaluates to [`imgcode::Error::UnsupportedFormat`] for unsupported pixel formats.
+macro_rules! with_image {
+    ($dynamic:expr, |$image:ident| $body:expr) => {{
+        use image::DynamicImage;
+        match $dynamic {
+            DynamicImage::ImageRgb8($image) => $body,
+            DynamicImage::ImageRgba8($image) => $body,
+            DynamicImage::ImageRgb32F($image) => $body,
+            DynamicImage::ImageRgba32F($image) => $body,
+            // 16-bit images come from formats like farbfeld that only
+            // store 16-bit channels. Narrowing them back is lossless.
+            DynamicImage::ImageRgb16(v) => {
+                let $image = DynamicImage::ImageRgb16(v).into_rgb8();
+                $body
+            }
+            DynamicImage::ImageRgba16(v) => {
+                let $image = DynamicImage::ImageRgba16(v).into_rgba8();
+                $body
+            }
+            // Grey images come from formats like 1-bit PNG and TIFF that
+            // store bilevel images. Widening them to RGB is lossless.
+            DynamicImage::ImageLuma8(v) => {
+                let $image = DynamicImage::ImageLuma8(v).into_rgb8();
+                $body
+            }
+            _ => Err(imgcode::Error::UnsupportedFormat),
+        }
+    }};
+}
+
+pub(crate) use with_image;
+
+/// Evaluate `$body`, which must be an [`imgcode::Result`], with `$images`
+/// bound to an iterator over the images of the slice `$dynamic` converted
+/// to the pixels of the first one, like by [`with_image!`].
+///
+/// Evaluates to [`imgcode::Error::UnsupportedFormat`] if the first image has
+/// an unsupported pixel format.
+macro_rules! with_images {
+    ($dynamic:expr, |$images:ident| $body:expr) => {{
+        use image::DynamicImage;
+        let dynamic: &[DynamicImage] = $dynamic;
+        match dynamic.first() {
+            None
+            | Some(
+                DynamicImage::ImageRgb8(_)
+                | DynamicImage::ImageRgb16(_)
+                | DynamicImage::ImageLuma8(_),
+            ) => {
+                let $images = dynamic.iter().map(DynamicImage::to_rgb8);
+                $body
+            }
+            Some(DynamicImage::ImageRgba8(_) | DynamicImage::ImageRgba16(_)) => {
+                let $images = dynamic.iter().map(DynamicImage::to_rgba8);
+                $body
+            }
+            Some(DynamicImage::ImageRgb32F(_)) => {
+                let $images = dynamic.iter().map(DynamicImage::to_rgb32f);
+                $body
+            }
+            Some(DynamicImage::ImageRgba32F(_)) => {
+                let $images = dynamic.iter().map(DynamicImage::to_rgba32f);
+                $body
+            }
+            _ => Err(imgcode::Error::UnsupportedFormat),
+        }
+    }};
+}
+
+pub(crate) use with_images;
+
+/// Read data with `read` from the region and channels given by `options`,
+/// or from the default ones for files in `format` if there are none.
+///
+/// The data of GIF files is read from the red channel first, where it is
+/// written to keep to the 256 colours of each frame, then from the whole
+/// pixel for files written without a channel mask.
+pub fn read_with_options<T, F>(
+    format: Option<image::ImageFormat>,
+    options: Option<imgcode::DecodeOptions>,
+    mut read: F,
+) -> imgcode::Result<T>
+where
+    F: FnMut(imgcode::DecodeOptions) -> imgcode::Result<T>,
+{
+    use imgcode::{Channels, DecodeOptions};
+
+    match options {
+        Some(options) => read(options),
+        None if format == Some(image::ImageFormat::Gif) => {
+            read(DecodeOptions::new().channels(Channels::RED))
+                .or_else(|e| read(DecodeOptions::new()).map_err(|_| e))
+        }
+        None => read(DecodeOptions::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbered_path() {
+        let path = Path::new("out/backup.png");
+        assert_eq!(numbered_path(path, 0), path);
+        assert_eq!(numbered_path(path, 1), Path::new("out/backup-2.png"));
+        assert_eq!(numbered_path(Path::new("backup"), 2), Path::new("backup-3"));
+    }
 }
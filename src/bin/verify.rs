@@ -0,0 +1,219 @@
+use super::command_prelude::*;
+
+use std::path::PathBuf;
+
+use image::{DynamicImage, ImageFormat};
+use imgcode::{DecodeOptions, SecretKey};
+
+use crate::{combine, frames, keygen, sizing};
+
+/// The data in an image differs from the original.
+#[derive(Debug)]
+pub struct VerifyError {
+    /// Offset of the first byte which differs.
+    offset: u64,
+    /// Coordinates of the pixel holding the first byte which differs.
+    pixel: Option<(u32, u32)>,
+    decoded_len: u64,
+    original_len: u64,
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.offset == self.decoded_len.min(self.original_len) {
+            write!(
+                f,
+                "image holds {} bytes but the original has {} bytes",
+                self.decoded_len, self.original_len
+            )?;
+        } else {
+            write!(f, "first mismatch at byte offset {}", self.offset)?;
+        }
+
+        if let Some((x, y)) = self.pixel {
+            write!(f, " (pixel {x},{y})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Decode `image` and check that it holds exactly `original`.
+///
+/// `options` gives where the data was written to if it does not
+/// fill the whole image.
+pub fn check(image: DynamicImage, original: &[u8], options: Option<&DecodeOptions>) -> Result<()> {
+    let (decoded, pixel_of) = util::with_image!(image, |v| {
+        let decoded = match options {
+            Some(options) => imgcode::from_image_with(&v, options),
+            None => imgcode::from_image(&v),
+        };
+
+        decoded.map(|decoded| {
+            // Pixel positions are only known for payloads covering the whole image.
+            let pixel_of = |offset| {
+                options.map_or_else(|| imgcode::payload_byte_position(&v, offset), |_| None)
+            };
+            let offset = first_mismatch(&decoded, original);
+            (decoded, offset.map(|x| (x, pixel_of(x))))
+        })
+    })?;
+
+    match pixel_of {
+        None => Ok(()),
+        Some((offset, pixel)) => Err(VerifyError {
+            offset,
+            pixel,
+            decoded_len: decoded.len() as u64,
+            original_len: original.len() as u64,
+        }
+        .into()),
+    }
+}
+
+/// Decode `frames`, decoded from a file in `format`, and check that they
+/// hold exactly `original`.
+///
+/// A single frame is checked with [`check()`], which also finds the pixel
+/// holding the first byte which differs. Data encrypted for recipients is
+/// decrypted with `identity`.
+pub fn check_frames(
+    mut frames: Vec<DynamicImage>,
+    format: Option<ImageFormat>,
+    original: &[u8],
+    options: Option<&DecodeOptions>,
+    identity: Option<&SecretKey>,
+) -> Result<()> {
+    if frames.len() == 1 && format != Some(ImageFormat::Gif) {
+        let options = match identity {
+            Some(_) => Some(keygen::with_identity(
+                options.cloned().unwrap_or_default(),
+                identity,
+            )),
+            None => options.cloned(),
+        };
+        return check(frames.remove(0), original, options.as_ref());
+    }
+
+    let decoded = frames::from_frames(&frames, format, options, identity)?;
+    match first_mismatch(&decoded, original) {
+        None => Ok(()),
+        Some(offset) => Err(VerifyError {
+            offset,
+            pixel: None,
+            decoded_len: decoded.len() as u64,
+            original_len: original.len() as u64,
+        }
+        .into()),
+    }
+}
+
+/// Recover the secret split into `shares`, decoded from files in
+/// `format`, and check that it is exactly `original`.
+pub fn check_shares(
+    shares: &[DynamicImage],
+    format: Option<ImageFormat>,
+    original: &[u8],
+    options: Option<&DecodeOptions>,
+) -> Result<()> {
+    let decoded = combine::from_shares(shares, format, options, None)?;
+    match first_mismatch(&decoded, original) {
+        None => Ok(()),
+        Some(offset) => Err(VerifyError {
+            offset,
+            pixel: None,
+            decoded_len: decoded.len() as u64,
+            original_len: original.len() as u64,
+        }
+        .into()),
+    }
+}
+
+/// Get the offset of the first byte which differs between `a` and `b`,
+/// or where the shorter one ends if they have different lengths.
+fn first_mismatch(a: &[u8], b: &[u8]) -> Option<u64> {
+    let offset = a
+        .iter()
+        .zip(b)
+        .position(|(a, b)| a != b)
+        .unwrap_or(a.len().min(b.len()));
+
+    (offset != a.len() || a.len() != b.len()).then_some(offset as u64)
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(help = "Path to image, or `-` for stdin")]
+    image_file: PathBuf,
+
+    #[clap(help = "Path to the original file, or `-` for stdin")]
+    original_file: PathBuf,
+
+    #[clap(flatten)]
+    placement: sizing::PlacementArgs,
+
+    #[clap(flatten)]
+    identity: keygen::IdentityArgs,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    if util::is_stdio(&args.image_file) && util::is_stdio(&args.original_file) {
+        bail!("only one of the image and the original can be read from stdin");
+    }
+
+    let mut input = util::open_input(&args.image_file)
+        .with_context(|| format!("unable to open image `{}`", args.image_file.display()))?;
+
+    let original = util::read_input(&args.original_file)
+        .with_context(|| format!("unable to read original `{}`", args.original_file.display()))?;
+
+    let (frames, format) =
+        frames::read_frames(&mut input, ImageFormat::from_path(&args.image_file).ok())?;
+
+    let identity = args.identity.identity()?;
+    check_frames(
+        frames,
+        format,
+        &original,
+        args.placement.options().as_ref(),
+        identity.as_ref(),
+    )?;
+
+    println!(
+        "ok: image holds all {} bytes of the original",
+        original.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_mismatch() {
+        assert_eq!(first_mismatch(b"abc", b"abc"), None);
+        assert_eq!(first_mismatch(b"abc", b"abd"), Some(2));
+        assert_eq!(first_mismatch(b"abc", b"ab"), Some(2));
+        assert_eq!(first_mismatch(b"", b"a"), Some(0));
+    }
+
+    #[test]
+    fn test_check() {
+        let data = vec![0x5a; 500];
+        let image = DynamicImage::from(imgcode::to_image::<image::RgbImage>(&data, 1.0));
+
+        assert!(check(image.clone(), &data, None).is_ok());
+
+        let mut other = data.clone();
+        other[100] = 0;
+        let err = check(image, &other, None).unwrap_err();
+        let err = err.downcast_ref::<VerifyError>().unwrap();
+
+        assert_eq!(err.offset, 100);
+        assert!(err.pixel.is_some());
+    }
+}
@@ -0,0 +1,72 @@
+use super::command_prelude::*;
+
+use std::path::PathBuf;
+
+use image::{DynamicImage, ImageFormat};
+use imgcode::{DecodeOptions, VerifyingKey};
+
+use crate::{frames, keygen, sizing};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(help = "Path to image, or `-` for stdin")]
+    image_file: PathBuf,
+
+    #[clap(
+        long = "key",
+        value_name = "KEY",
+        help = "Verifying key of the trusted signer, or a signing key file written by \
+                `imgcode keygen --signing`",
+        value_parser = keygen::parse_verifying_key
+    )]
+    key: VerifyingKey,
+
+    #[clap(flatten)]
+    placement: sizing::PlacementArgs,
+}
+
+pub fn command(_global_args: &CliArgs, args: &Args) -> Result<()> {
+    let mut input = util::open_input(&args.image_file)
+        .with_context(|| format!("unable to open image `{}`", args.image_file.display()))?;
+
+    let (frames, format) =
+        frames::read_frames(&mut input, ImageFormat::from_path(&args.image_file).ok())?;
+
+    // Each frame carries its own signature.
+    let count = frames.len();
+    for (i, frame) in frames.into_iter().enumerate() {
+        verify_frame(frame, format, args.placement.options(), &args.key).with_context(|| {
+            match count {
+                1 => format!("`{}` is not trusted", args.image_file.display()),
+                _ => format!("frame {} of {count} is not trusted", i + 1),
+            }
+        })?;
+    }
+
+    println!("ok: signed by {}", args.key);
+
+    Ok(())
+}
+
+/// Check that `frame`, decoded from a file in `format`, is signed by
+/// `trusted`, reading it from the region and channels given by `options`.
+///
+/// Without `options`, GIF frames are read like by
+/// [`util::read_with_options()`].
+fn verify_frame(
+    frame: DynamicImage,
+    format: Option<ImageFormat>,
+    options: Option<DecodeOptions>,
+    trusted: &VerifyingKey,
+) -> Result<()> {
+    let verify = |options: DecodeOptions| {
+        let options = options.verifying_key(*trusted);
+        util::with_image!(frame.clone(), |v| imgcode::verify_signature_with(
+            &v, &options
+        ))
+    };
+
+    util::read_with_options(format, options, verify)?;
+
+    Ok(())
+}
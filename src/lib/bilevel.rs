@@ -0,0 +1,123 @@
+use image::RgbImage;
+
+use crate::cursor::ImageCursor;
+use crate::file::Header;
+use crate::frame::is_dark;
+use crate::label::pixel_bytes;
+use crate::traits::{Image, ImageMut};
+use crate::{Error, Result};
+
+/// Number of pixels of a bilevel image whose bits make up one pixel
+/// of the packed image holding its bytes.
+const PIXELS_PER_PACKED: u64 = 24;
+
+/// Get an image to write the bytes held by a bilevel `width`x`height`
+/// image to, as a single row of pixels.
+///
+/// # Errors
+///
+/// - The bilevel image has too many pixels
+pub fn packed_image(width: u32, height: u32) -> Result<RgbImage> {
+    let pixels = (u64::from(width) * u64::from(height)).div_ceil(PIXELS_PER_PACKED);
+    let pixels = u32::try_from(pixels).map_err(|_| Error::SizeLimit)?;
+
+    Ok(RgbImage::new(pixels, 1))
+}
+
+/// Get the number of bytes a bilevel `width`x`height` image can hold.
+pub fn capacity(width: u32, height: u32) -> u64 {
+    u64::from(width) * u64::from(height) / 8
+}
+
+/// Draw the bits of the bytes of `packed`, most significant first, as the
+/// pixels of a new `width`x`height` image row by row: black for `1` and
+/// white for `0`.
+#[allow(clippy::cast_possible_truncation)]
+pub fn expand<I>(packed: &RgbImage, width: u32, height: u32) -> I
+where
+    I: ImageMut,
+{
+    let (white, black) = (pixel_bytes::<I>(true), pixel_bytes::<I>(false));
+    let bytes = packed.as_raw();
+
+    let mut image = I::new_with_dimensions(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let i = u64::from(y) * u64::from(width) + u64::from(x);
+            let bit = bytes[(i / 8) as usize] >> (7 - i % 8) & 1 != 0;
+
+            if let Some(dst) = image.get_pixel_mut(x, y) {
+                dst.copy_from_slice(if bit { &black } else { &white });
+            }
+        }
+    }
+
+    image
+}
+
+/// Read the bytes held by the bilevel `image`, taking pixels closer to
+/// black than to white as `1`.
+///
+/// Returns `None` if the bytes do not start with a header, in which case
+/// `image` was not written as a bilevel image.
+pub fn pack<I>(image: &I) -> Option<RgbImage>
+where
+    I: Image,
+{
+    let mut packed = packed_image(image.width(), image.height()).ok()?;
+
+    // Check the header first so that other images are rejected quickly.
+    read_bits(image, &mut packed, Header::SIZE_V5 as u64 * 8);
+    Header::read_from(ImageCursor::new(&packed))
+        .ok()
+        .filter(|header| !header.legacy)?;
+
+    read_bits(image, &mut packed, u64::MAX);
+    Some(packed)
+}
+
+/// Set the bits of `packed` held by the first `count` pixels of `image`
+/// which are dark.
+#[allow(clippy::cast_possible_truncation)]
+fn read_bits<I>(image: &I, packed: &mut RgbImage, count: u64)
+where
+    I: Image,
+{
+    let width = u64::from(image.width());
+    let bytes: &mut [u8] = packed;
+
+    let pixels = (0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y)));
+    for (i, (x, y)) in pixels
+        .enumerate()
+        .take(count.min(width * u64::from(image.height())) as usize)
+    {
+        if image.get_pixel(x, y).is_some_and(is_dark::<I>) {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_pack() {
+        assert_eq!(packed_image(5, 4).unwrap().width(), 1);
+        assert_eq!(capacity(5, 4), 2);
+
+        let packed = RgbImage::from_raw(1, 1, vec![0b1010_0000, 0xff, 0x0f]).unwrap();
+        let image: image::RgbaImage = expand(&packed, 5, 4);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [255; 4]);
+        assert_eq!(image.get_pixel(2, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(3, 1).0, [0, 0, 0, 255]);
+
+        let mut bits = packed_image(5, 4).unwrap();
+        read_bits(&image, &mut bits, u64::MAX);
+        assert_eq!(bits.as_raw(), &[0b1010_0000, 0xff, 0]);
+
+        // Images without a header are not bilevel images.
+        assert_eq!(pack(&image), None);
+    }
+}
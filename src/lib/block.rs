@@ -0,0 +1,181 @@
+use crate::label::pixel_bytes;
+use crate::private::Sealed;
+use crate::traits::{Image, ImageMut};
+
+/// Draw each pixel of `image` as a `size`x`size` block, below a calibration
+/// row of blocks alternating between white and black.
+///
+/// The calibration row lets the decoder find the size of the blocks, even
+/// after the image has been upscaled.
+pub fn scale_up<I>(image: &I, size: u32) -> I
+where
+    I: ImageMut,
+{
+    let (white, black) = (pixel_bytes::<I>(true), pixel_bytes::<I>(false));
+
+    draw(image.width(), image.height() + 1, size, |x, y| match y {
+        0 if x % 2 == 0 => Some(white.as_slice()),
+        0 => Some(black.as_slice()),
+        _ => image.get_pixel(x, y - 1),
+    })
+}
+
+/// Draw a new image of `width`x`height` blocks of `size`x`size` pixels,
+/// with the pixels of each block set to the ones returned by `block`.
+pub fn draw<'a, I, F>(width: u32, height: u32, size: u32, block: F) -> I
+where
+    I: ImageMut,
+    F: Fn(u32, u32) -> Option<&'a [u8]>,
+{
+    let mut image = I::new_with_dimensions(width * size, height * size);
+
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let Some(pixel) = block(x / size, y / size) else {
+                continue;
+            };
+
+            if let Some(dst) = image.get_pixel_mut(x, y) {
+                dst.copy_from_slice(pixel);
+            }
+        }
+    }
+
+    image
+}
+
+/// Find the size of the blocks of an image written by [`scale_up()`]
+/// from its calibration row. Returns `None` if `image` has no calibration row.
+pub fn detect<I>(image: &I) -> Option<u32>
+where
+    I: Image,
+{
+    let (white, black) = (pixel_bytes::<I>(true), pixel_bytes::<I>(false));
+
+    let size = (0..image.width())
+        .take_while(|&x| image.get_pixel(x, 0) == Some(white.as_slice()))
+        .count();
+    let size = u32::try_from(size).ok().filter(|&x| x != 0)?;
+
+    let (width, height) = (image.width(), image.height());
+    if width % size != 0 || height % size != 0 || height / size < 2 {
+        return None;
+    }
+
+    // Every pixel of the calibration row has the colour of its block.
+    let calibrated = (0..size).all(|y| {
+        (0..width).all(|x| {
+            let expected = if (x / size) % 2 == 0 { &white } else { &black };
+            image.get_pixel(x, y) == Some(expected.as_slice())
+        })
+    });
+
+    calibrated.then_some(size)
+}
+
+/// Pixels sampled from each block of an image with blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    /// Column of the pixel sampled from each column of blocks.
+    pub columns: Vec<u32>,
+    /// Row of the pixel sampled from each row of blocks.
+    pub rows: Vec<u32>,
+    /// Side of a block in pixels, rounded if the image was scaled
+    /// by a fraction.
+    pub block_size: u32,
+    /// Whether the blocks were located with the markers of a frame.
+    pub markers: bool,
+}
+
+impl Grid {
+    /// Get the grid of an image with dimensions `width`x`height`
+    /// written by [`scale_up()`] with blocks of `size` pixels, sampling
+    /// the centre of each block below the calibration row.
+    pub fn uniform(width: u32, height: u32, size: u32) -> Self {
+        let centres = |n: u32| (0..n).map(move |i| i * size + size / 2);
+
+        Self {
+            columns: centres(width / size).collect(),
+            rows: centres(height / size).skip(1).collect(),
+            block_size: size,
+            markers: false,
+        }
+    }
+}
+
+/// View of an image with blocks with one pixel per block.
+pub struct Blocks<I> {
+    image: I,
+    grid: Grid,
+}
+
+impl<I> Blocks<I> {
+    pub fn new(image: I, grid: Grid) -> Self {
+        Self { image, grid }
+    }
+}
+
+impl<I> Sealed for Blocks<I> {}
+impl<I> Image for Blocks<I>
+where
+    I: Image,
+{
+    type ChannelType = I::ChannelType;
+    const CHANNEL_NUM: u32 = I::CHANNEL_NUM;
+    const PIXEL_FORMAT: crate::PixelFormat = I::PIXEL_FORMAT;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn width(&self) -> u32 {
+        self.grid.columns.len() as u32
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn height(&self) -> u32 {
+        self.grid.rows.len() as u32
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        let x = *self.grid.columns.get(x as usize)?;
+        let y = *self.grid.rows.get(y as usize)?;
+        self.image.get_pixel(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_up_detect() {
+        let image = image::RgbImage::from_raw(3, 2, (0..18).collect()).unwrap();
+
+        let scaled = scale_up(&image, 2);
+        assert_eq!(scaled.dimensions(), (6, 6));
+        assert_eq!(scaled.get_pixel(1, 1).0, [255; 3]);
+        assert_eq!(scaled.get_pixel(2, 0).0, [0; 3]);
+        assert_eq!(scaled.get_pixel(5, 5).0, [15, 16, 17]);
+        assert_eq!(detect(&scaled), Some(2));
+
+        // Nearest neighbour upscaling multiplies the size of the blocks.
+        let upscaled = image::imageops::resize(&scaled, 18, 18, image::imageops::Nearest);
+        assert_eq!(detect(&upscaled), Some(6));
+
+        let grid = Grid::uniform(18, 18, 6);
+        assert_eq!(
+            (grid.columns.as_slice(), grid.rows.as_slice()),
+            (&[3, 9, 15][..], &[9, 15][..])
+        );
+
+        let blocks = Blocks::new(&upscaled, grid);
+        assert_eq!((blocks.width(), blocks.height()), (3, 2));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(blocks.get_pixel(x, y), Some(pixel.0.as_slice()));
+        }
+
+        assert_eq!(detect(&image), None);
+        assert_eq!(
+            detect(&image::RgbImage::from_pixel(4, 4, image::Rgb([255; 3]))),
+            None
+        );
+    }
+}
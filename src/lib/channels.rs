@@ -0,0 +1,101 @@
+use std::ops::BitOr;
+
+use crate::traits::PixelFormat;
+
+/// Set of channels of a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Channels(u8);
+
+impl Channels {
+    pub const RED: Self = Self(1 << 0);
+    pub const GREEN: Self = Self(1 << 1);
+    pub const BLUE: Self = Self(1 << 2);
+    pub const ALPHA: Self = Self(1 << 3);
+
+    pub const RGB: Self = Self(Self::RED.0 | Self::GREEN.0 | Self::BLUE.0);
+    pub const RGBA: Self = Self(Self::RGB.0 | Self::ALPHA.0);
+
+    /// Get all channels of pixels in `pixel_format`.
+    #[must_use]
+    pub fn all(pixel_format: PixelFormat) -> Self {
+        match pixel_format.channel_count() {
+            3 => Self::RGB,
+            _ => Self::RGBA,
+        }
+    }
+
+    /// Get the number of channels in the set.
+    #[must_use]
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check whether all channels of `other` are in the set.
+    #[must_use]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check whether the set is not empty and pixels in `pixel_format`
+    /// have all of its channels.
+    #[must_use]
+    pub fn fits(self, pixel_format: PixelFormat) -> bool {
+        !self.is_empty() && Self::all(pixel_format).contains(self)
+    }
+
+    /// Get the number of bytes of a pixel in `pixel_format` taken up
+    /// by the channels in the set.
+    #[must_use]
+    pub fn pixel_size(self, pixel_format: PixelFormat) -> u32 {
+        pixel_format.pixel_size() / pixel_format.channel_count() * self.count()
+    }
+
+    /// Get the indices of the channels in the set, in pixel order.
+    pub(crate) fn indices(self) -> impl Iterator<Item = usize> {
+        (0..4).filter(move |i| self.0 & (1 << i) != 0)
+    }
+}
+
+impl BitOr for Channels {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::fmt::Display for Channels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in self.indices() {
+            write!(f, "{}", ['r', 'g', 'b', 'a'][i])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channels() {
+        let blue_alpha = Channels::BLUE | Channels::ALPHA;
+
+        assert_eq!(blue_alpha.count(), 2);
+        assert_eq!(blue_alpha.indices().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(blue_alpha.to_string(), "ba");
+
+        assert!(blue_alpha.fits(PixelFormat::Rgba8));
+        assert!(!blue_alpha.fits(PixelFormat::Rgb8));
+        assert!(!Channels(0).fits(PixelFormat::Rgb8));
+
+        assert_eq!(Channels::BLUE.pixel_size(PixelFormat::Rgb32), 4);
+        assert_eq!(blue_alpha.pixel_size(PixelFormat::Rgba8), 2);
+    }
+}
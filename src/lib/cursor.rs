@@ -1,18 +1,23 @@
 use std::io::{self, prelude::*};
 
-use crate::traits::Image;
+use crate::channels::Channels;
+use crate::region::Region;
+use crate::traits::{Image, ImageMut};
+use crate::{Error, Result};
 
+/// Reads and writes bytes to some channels of the pixels of a region
+/// of an image, row by row.
 #[allow(clippy::module_name_repetitions)]
 pub struct ImageCursor<I> {
     image: I,
+    region: Region,
+    channels: Channels,
+    /// Number of bytes of each pixel in `channels`.
+    pixel_size: u32,
     pos: u64,
 }
 
 impl<I> ImageCursor<I> {
-    pub fn new(image: I) -> Self {
-        Self { image, pos: 0 }
-    }
-
     pub fn into_image(self) -> I {
         self.image
     }
@@ -22,37 +27,130 @@ impl<I> ImageCursor<I>
 where
     I: Image,
 {
-    /// Get the total amount of bytes this image can hold.
+    /// Create a cursor over the whole of `image`.
+    pub fn new(image: I) -> Self {
+        let region = Region::full(image.width(), image.height());
+        Self {
+            image,
+            region,
+            channels: Channels::all(I::PIXEL_FORMAT),
+            pixel_size: I::PIXEL_SIZE,
+            pos: 0,
+        }
+    }
+
+    /// Create a cursor restricted to `region` of `image`.
+    ///
+    /// # Errors
+    ///
+    /// - `region` is empty or does not fit in `image`
+    pub fn with_region(image: I, region: Region) -> Result<Self> {
+        if !region.fits(image.width(), image.height()) {
+            return Err(Error::InvalidDimensions);
+        }
+
+        Ok(Self {
+            region,
+            ..Self::new(image)
+        })
+    }
+
+    /// Restrict the cursor to `channels` of each pixel.
+    ///
+    /// # Errors
+    ///
+    /// - `channels` is empty or the pixels of `I` do not have all of them
+    pub fn with_channels(self, channels: Channels) -> Result<Self> {
+        if !channels.fits(I::PIXEL_FORMAT) {
+            return Err(Error::InvalidChannels);
+        }
+
+        Ok(Self {
+            channels,
+            pixel_size: channels.pixel_size(I::PIXEL_FORMAT),
+            ..self
+        })
+    }
+
+    /// Get the total amount of bytes the region of this image can hold.
     #[must_use]
     pub fn capacity(&self) -> u64 {
-        u64::from(self.image.width()) * u64::from(self.image.height()) * u64::from(I::PIXEL_SIZE)
+        u64::from(self.region.width) * u64::from(self.region.height) * u64::from(self.pixel_size)
+    }
+
+    /// Get the amount of bytes a row of the region can hold.
+    #[must_use]
+    pub fn row_size(&self) -> u64 {
+        u64::from(self.region.width) * u64::from(self.pixel_size)
+    }
+
+    /// Get the amount of bytes each pixel can hold.
+    #[must_use]
+    pub fn pixel_size(&self) -> u32 {
+        self.pixel_size
     }
 
     /// Get the `x` and `y` position of the pixel `pos` is pointing at.
     /// Also returns the offset in that pixel.
     ///
+    /// The position is relative to the top left corner of the region.
+    ///
     /// Returns:
     ///
     /// (`x coordinate`, `y coordinate`, `offset inside pixel`)
     #[allow(clippy::cast_possible_truncation)]
-    fn xyrem_from_pos(&self, pos: u64) -> (u32, u32, u64) {
-        let width = self.image.width() * I::PIXEL_SIZE;
+    pub(crate) fn xyrem_from_pos(&self, pos: u64) -> (u32, u32, u64) {
+        let pixel_size = u64::from(self.pixel_size);
+        let width = u64::from(self.region.width) * pixel_size;
 
-        let y = (pos / u64::from(width)) as u32;
+        let y = (pos / width).min(u64::from(u32::MAX)) as u32;
 
-        let y_width = u64::from(y) * u64::from(width);
+        let y_width = u64::from(y) * width;
 
-        let x = ((pos - y_width) / u64::from(I::PIXEL_SIZE)) as u32;
-        let r = pos - y_width - u64::from(x) * u64::from(I::PIXEL_SIZE);
+        let x = ((pos - y_width) / pixel_size) as u32;
+        let r = pos - y_width - u64::from(x) * pixel_size;
 
         (x, y, r)
     }
 
+    /// Get the position in the image of the pixel `pos` is pointing at
+    /// and the offset in that pixel. Returns `None` if `pos` is outside
+    /// the region.
+    fn pixel_from_pos(&self, pos: u64) -> Option<(u32, u32, u64)> {
+        let (x, y, r) = self.xyrem_from_pos(pos);
+
+        if y >= self.region.height {
+            return None;
+        }
+
+        Some((self.region.x + x, self.region.y + y, r))
+    }
+
+    /// Get the indices of the bytes of a pixel in `channels`, starting
+    /// from byte `offset` of those channels.
+    fn channel_bytes(channels: Channels, offset: u64) -> impl Iterator<Item = usize> {
+        let channel_size = (I::PIXEL_SIZE / I::CHANNEL_NUM) as usize;
+
+        #[allow(clippy::cast_possible_truncation)]
+        channels
+            .indices()
+            .flat_map(move |i| i * channel_size..(i + 1) * channel_size)
+            .skip(offset as usize)
+    }
+
     /// Read the pixel pointed to by `pos` into buf. Returns
     /// the number of bytes read from the pixel into buf. Returns
     /// `None` if `pos` is out-of-bounds.
     fn read_pixel_to_buf(&mut self, buf: &mut [u8]) -> Option<usize> {
-        let (pixel_x, pixel_y, pixel_offset) = self.xyrem_from_pos(self.pos);
+        let (pixel_x, pixel_y, pixel_offset) = self.pixel_from_pos(self.pos)?;
+
+        if self.pixel_size != I::PIXEL_SIZE {
+            let bytes = Self::channel_bytes(self.channels, pixel_offset);
+            let pixel = self.image.get_pixel(pixel_x, pixel_y)?;
+
+            let i = bytes.zip(buf).map(|(src, dst)| *dst = pixel[src]).count();
+            return Some(i);
+        }
 
         let pixel = self.image.get_pixel(pixel_x, pixel_y)?;
         #[allow(clippy::cast_possible_truncation)]
@@ -61,12 +159,25 @@ where
         let i = copy_min_len(unread_pixel, buf);
         Some(i)
     }
+}
 
+impl<I> ImageCursor<I>
+where
+    I: ImageMut,
+{
     /// Write `buf` to the pixel pointed by `pos`. Returns the
     /// number of bytes written from `buf` into the pixel. Returns
     /// `None` if `pos` is out-of-bounds.
     fn write_buf_to_pixel(&mut self, buf: &[u8]) -> Option<usize> {
-        let (pixel_x, pixel_y, pixel_offset) = self.xyrem_from_pos(self.pos);
+        let (pixel_x, pixel_y, pixel_offset) = self.pixel_from_pos(self.pos)?;
+
+        if self.pixel_size != I::PIXEL_SIZE {
+            let bytes = Self::channel_bytes(self.channels, pixel_offset);
+            let pixel = self.image.get_pixel_mut(pixel_x, pixel_y)?;
+
+            let i = bytes.zip(buf).map(|(dst, src)| pixel[dst] = *src).count();
+            return Some(i);
+        }
 
         let pixel = self.image.get_pixel_mut(pixel_x, pixel_y)?;
         #[allow(clippy::cast_possible_truncation)]
@@ -86,12 +197,12 @@ where
 
         loop {
             let i = match self.read_pixel_to_buf(&mut buf[bytes_read..]) {
-                Some(i) if i == 0 => break,
+                Some(0) | None => break,
                 Some(i) => i,
-                None => break,
             };
 
             bytes_read += i;
+            #[allow(clippy::cast_possible_wrap)]
             self.seek(io::SeekFrom::Current(i as i64))?;
         }
 
@@ -101,19 +212,19 @@ where
 
 impl<I> Write for ImageCursor<I>
 where
-    I: Image,
+    I: ImageMut,
 {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         let mut bytes_written = 0;
 
         loop {
             let i = match self.write_buf_to_pixel(&buf[bytes_written..]) {
-                Some(i) if i == 0 => break,
+                Some(0) | None => break,
                 Some(i) => i,
-                None => break,
             };
 
             bytes_written += i;
+            #[allow(clippy::cast_possible_wrap)]
             self.seek(io::SeekFrom::Current(i as i64))?;
         }
 
@@ -170,7 +281,7 @@ mod tests {
         assert_eq!(cursor.write(&[0x06, 0x07, 0x08, 0x09]).unwrap(), 1);
 
         cursor.seek(io::SeekFrom::Start(0)).unwrap();
-        let mut buf = vec![0u8; 6];
+        let mut buf = [0u8; 6];
 
         assert_eq!(cursor.read(&mut buf[0..2]).unwrap(), 2);
         assert_eq!(&buf[0..2], &[0x01, 0x02]);
@@ -197,4 +308,44 @@ mod tests {
         assert_eq!(cursor.read(&mut buf).unwrap(), 0);
         assert_eq!(buf, data);
     }
+
+    #[test]
+    fn test_rw_region() {
+        let image = image::RgbImage::from_pixel(4, 3, image::Rgb([0xff; 3]));
+        let mut cursor = ImageCursor::with_region(image, Region::new(1, 1, 2, 2)).unwrap();
+
+        assert_eq!(cursor.capacity(), 12);
+        assert_eq!(cursor.write(&[0u8; 16]).unwrap(), 12);
+
+        let image = cursor.into_image();
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let inside = (1..3).contains(&x) && (1..3).contains(&y);
+            assert_eq!(pixel.0 == [0; 3], inside, "{x},{y}");
+        }
+
+        assert!(ImageCursor::with_region(&image, Region::new(3, 0, 2, 1)).is_err());
+    }
+
+    #[test]
+    fn test_rw_channels() {
+        let image = image::RgbaImage::from_pixel(2, 1, image::Rgba([0xff; 4]));
+        let mut cursor = ImageCursor::new(image)
+            .with_channels(Channels::GREEN | Channels::ALPHA)
+            .unwrap();
+
+        assert_eq!(cursor.capacity(), 4);
+        assert_eq!(cursor.write(&[1, 2, 3, 4, 5]).unwrap(), 4);
+
+        cursor.seek(io::SeekFrom::Start(1)).unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(cursor.read(&mut buf).unwrap(), 3);
+        assert_eq!(buf, [2, 3, 4, 0]);
+
+        let image = cursor.into_image();
+        assert_eq!(image.as_raw(), &[0xff, 1, 0xff, 2, 0xff, 3, 0xff, 4]);
+
+        assert!(ImageCursor::new(image::RgbImage::new(1, 1))
+            .with_channels(Channels::ALPHA)
+            .is_err());
+    }
 }
@@ -0,0 +1,293 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+
+use crate::cursor::ImageCursor;
+use crate::recipient::TAG_SIZE;
+use crate::traits::{Image, ImageMut};
+use crate::{DecodeOptions, EncodeOptions, Error, Result};
+
+/// Size of the random salt at the start of the image, which the keys of
+/// both passwords are derived with.
+const SALT_SIZE: usize = 16;
+/// Size of the encrypted length at the start of a slot holding a payload.
+const LENGTH_SIZE: usize = 8 + TAG_SIZE;
+/// Number of slots the image is split into, whether or not they all
+/// hold a payload.
+const SLOTS: u64 = 2;
+
+/// Cost of deriving a key from a password with scrypt: 2^15 rounds of
+/// 8 blocks, which takes 32 MiB of memory.
+const LOG_N: u8 = 15;
+const BLOCKS: u32 = 8;
+
+/// Nonces of the length and of the payload sealed in a slot, which are
+/// only ever used once with the key of a password and the salt of an image.
+const LENGTH_NONCE: u8 = 0;
+const PAYLOAD_NONCE: u8 = 1;
+
+/// Derive the key of `password` for the image with `salt`.
+fn derive_key(password: &[u8], salt: &[u8]) -> [u8; 32] {
+    let params = scrypt::Params::new(LOG_N, BLOCKS, 1, 32).expect("valid scrypt parameters");
+    let mut key = [0; 32];
+    scrypt::scrypt(password, salt, &params, &mut key).expect("32 bytes is a valid key length");
+    key
+}
+
+fn nonce(n: u8) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[0] = n;
+    nonce
+}
+
+/// Encrypt `data` with `key` and the nonce numbered `n`.
+fn seal(key: &[u8; 32], n: u8, data: &[u8]) -> Vec<u8> {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt(&nonce(n), data)
+        .expect("data fits in a single message")
+}
+
+/// Decrypt `data` sealed with `key` and the nonce numbered `n`, if it was
+/// sealed with them and not altered.
+fn open(key: &[u8; 32], n: u8, data: &[u8]) -> Option<Vec<u8>> {
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(&nonce(n), data)
+        .ok()
+}
+
+/// Write one or two `payloads` to a new image, each encrypted with its
+/// own password, so that each password only reveals its own payload.
+///
+/// Nothing in the image tells how many payloads it holds: there is no
+/// header, each payload is put in one of two slots in a random order,
+/// and everything around them is random bytes, like the payloads. Read
+/// them back with [`from_deniable_image()`].
+///
+/// The image is large enough for two payloads the size of the largest
+/// one. Only the dimensions and channels in `options` are used.
+///
+/// # Errors
+///
+/// - There are no payloads or more than two
+/// - Both payloads have the same password
+/// - Random numbers could not be read from the operating system
+/// - See [`plan()`](crate::plan)
+pub fn to_deniable_image<I>(payloads: &[(&[u8], &[u8])], options: &EncodeOptions) -> Result<I>
+where
+    I: ImageMut,
+{
+    if payloads.is_empty() || payloads.len() as u64 > SLOTS {
+        return Err(Error::InvalidArgument);
+    }
+    if payloads.len() == 2 && payloads[0].1 == payloads[1].1 {
+        return Err(Error::InvalidArgument);
+    }
+
+    let largest = payloads.iter().map(|(x, _)| x.len()).max().unwrap_or(0) as u64;
+    let slot_size = (LENGTH_SIZE + TAG_SIZE) as u64 + largest;
+
+    let options = EncodeOptions {
+        dimensions: options.dimensions,
+        max_dimension: options.max_dimension,
+        channels: options.channels,
+        ..Default::default()
+    };
+    let plan = crate::plan(
+        I::PIXEL_FORMAT,
+        SALT_SIZE as u64 + SLOTS * slot_size,
+        &options,
+    )?;
+
+    let mut image = ImageCursor::new(I::new_with_dimensions(plan.width, plan.height));
+    if let Some(channels) = options.channels {
+        image = image.with_channels(channels)?;
+    }
+
+    // Fill the image with random bytes first, which leaves the salt at
+    // its start and hides the unused slot and the end of each payload.
+    let mut fill = vec![0; usize::try_from(image.capacity()).map_err(|_| Error::SizeLimit)?];
+    getrandom::getrandom(&mut fill).map_err(|e| Error::Io(e.into()))?;
+    image.write_all(&fill)?;
+
+    let salt = &fill[..SALT_SIZE];
+    let slot_size = (image.capacity() - SALT_SIZE as u64) / SLOTS;
+
+    let mut order = [0; 1];
+    getrandom::getrandom(&mut order).map_err(|e| Error::Io(e.into()))?;
+    let slots = match order[0] & 1 {
+        0 => [0, 1],
+        _ => [1, 0],
+    };
+
+    for (&(data, password), slot) in payloads.iter().zip(slots) {
+        let key = derive_key(password, salt);
+
+        image.seek(SeekFrom::Start(SALT_SIZE as u64 + slot * slot_size))?;
+        image.write_all(&seal(
+            &key,
+            LENGTH_NONCE,
+            &(data.len() as u64).to_be_bytes(),
+        ))?;
+        image.write_all(&seal(&key, PAYLOAD_NONCE, data))?;
+    }
+
+    Ok(image.into_image())
+}
+
+/// Read the payload encrypted with `password` from an image written by
+/// [`to_deniable_image()`].
+///
+/// # Errors
+///
+/// See [`from_deniable_image_with()`]
+pub fn from_deniable_image<I>(image: I, password: &[u8]) -> Result<Vec<u8>>
+where
+    I: Image,
+{
+    from_deniable_image_with(image, password, &DecodeOptions::new())
+}
+
+/// Read the payload encrypted with `password` from the region and
+/// channels of `image` given by `options`.
+///
+/// # Errors
+///
+/// - The region in `options` is empty or does not fit in `image`
+/// - The pixels of `image` do not have the channels in `options`
+/// - No payload is encrypted with `password`, which cannot be told apart
+///   from the image not holding deniable payloads at all
+/// - The payload was altered after it was written
+pub fn from_deniable_image_with<I>(
+    image: I,
+    password: &[u8],
+    options: &DecodeOptions,
+) -> Result<Vec<u8>>
+where
+    I: Image,
+{
+    let mut image = match options.region {
+        Some(region) => ImageCursor::with_region(image, region)?,
+        None => ImageCursor::new(image),
+    };
+    if let Some(channels) = options.channels {
+        image = image.with_channels(channels)?;
+    }
+
+    let capacity = image.capacity();
+    if capacity < SALT_SIZE as u64 + SLOTS * (LENGTH_SIZE + TAG_SIZE) as u64 {
+        return Err(Error::WrongPassword);
+    }
+
+    let mut salt = [0; SALT_SIZE];
+    image.read_exact(&mut salt)?;
+    let key = derive_key(password, &salt);
+    let slot_size = (capacity - SALT_SIZE as u64) / SLOTS;
+
+    for slot in 0..SLOTS {
+        image.seek(SeekFrom::Start(SALT_SIZE as u64 + slot * slot_size))?;
+
+        let mut length = [0; LENGTH_SIZE];
+        image.read_exact(&mut length)?;
+        let Some(length) = open(&key, LENGTH_NONCE, &length) else {
+            continue;
+        };
+
+        // The length is authenticated, so it only exceeds the slot if the
+        // image was written by something else.
+        let length = u64::from_be_bytes(length.try_into().map_err(|_| Error::InvalidHeader)?);
+        let sealed = length
+            .checked_add(TAG_SIZE as u64)
+            .filter(|&x| x <= slot_size - LENGTH_SIZE as u64)
+            .ok_or(Error::InvalidHeader)?;
+
+        let mut data = vec![0; usize::try_from(sealed).map_err(|_| Error::SizeLimit)?];
+        image.read_exact(&mut data)?;
+        return open(&key, PAYLOAD_NONCE, &data).ok_or(Error::ChecksumMismatch);
+    }
+
+    Err(Error::WrongPassword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{RgbImage, RgbaImage};
+
+    use crate::Channels;
+
+    #[test]
+    fn test_two_payloads() {
+        let public = b"notes for the editor".to_vec();
+        let hidden = (0..=255u8).cycle().take(300).collect::<Vec<_>>();
+
+        let payloads = [(&public[..], &b"decoy"[..]), (&hidden[..], &b"real"[..])];
+        let image: RgbImage = to_deniable_image(&payloads, &EncodeOptions::new()).unwrap();
+
+        assert_eq!(from_deniable_image(&image, b"decoy").unwrap(), public);
+        assert_eq!(from_deniable_image(&image, b"real").unwrap(), hidden);
+        assert!(matches!(
+            from_deniable_image(&image, b"guess"),
+            Err(Error::WrongPassword)
+        ));
+
+        // There is no header to find.
+        assert!(matches!(crate::probe(&image), Err(Error::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_one_payload() {
+        let data = b"only one".to_vec();
+        let options = EncodeOptions::new().channels(Channels::BLUE);
+
+        let single: RgbaImage = to_deniable_image(&[(&data[..], b"pass")], &options).unwrap();
+        let both: RgbaImage =
+            to_deniable_image(&[(&data[..], b"pass"), (&data[..], b"other")], &options).unwrap();
+
+        // One payload takes up as much room as two.
+        assert_eq!(single.dimensions(), both.dimensions());
+
+        let decode_options = DecodeOptions::new().channels(Channels::BLUE);
+        assert_eq!(
+            from_deniable_image_with(&single, b"pass", &decode_options).unwrap(),
+            data
+        );
+        assert!(matches!(
+            from_deniable_image(&single, b"pass"),
+            Err(Error::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_payloads() {
+        let options = EncodeOptions::new();
+        assert!(matches!(
+            to_deniable_image::<RgbImage>(&[], &options),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            to_deniable_image::<RgbImage>(&[(b"a", b"same"), (b"b", b"same")], &options),
+            Err(Error::InvalidArgument)
+        ));
+
+        let image: RgbImage = to_deniable_image(&[(b"data", b"pass")], &options).unwrap();
+        let mut cursor = ImageCursor::new(image);
+        let slot_size = (cursor.capacity() - SALT_SIZE as u64) / SLOTS;
+
+        // Flip the first byte of the payload in either slot.
+        for slot in 0..SLOTS {
+            let pos = (SALT_SIZE + LENGTH_SIZE) as u64 + slot * slot_size;
+            let mut byte = [0];
+            cursor.seek(SeekFrom::Start(pos)).unwrap();
+            cursor.read_exact(&mut byte).unwrap();
+            cursor.seek(SeekFrom::Start(pos)).unwrap();
+            cursor.write_all(&[byte[0] ^ 1]).unwrap();
+        }
+
+        assert!(matches!(
+            from_deniable_image(cursor.into_image(), b"pass"),
+            Err(Error::ChecksumMismatch)
+        ));
+    }
+}
@@ -5,6 +5,24 @@ use std::io;
 pub enum Error {
     SizeLimit,
     UnsupportedFormat,
+    InvalidDimensions,
+    InvalidArgument,
+    InsufficientCapacity,
+    InvalidChannels,
+    InvalidHeader,
+    UnsupportedVersion,
+    TypeMismatch,
+    Cancelled,
+    MissingData,
+    ChecksumMismatch,
+    InvalidKey,
+    Encrypted,
+    WrongIdentity,
+    WrongPassword,
+    Unsigned,
+    BadSignature,
+    UntrustedSigner,
+    Serialization(bincode::Error),
     Io(io::Error),
 }
 
@@ -13,6 +31,24 @@ impl std::fmt::Display for Error {
         match self {
             Self::SizeLimit => write!(f, "size limit exceeded"),
             Self::UnsupportedFormat => write!(f, "unsupported image format"),
+            Self::InvalidDimensions => write!(f, "invalid image dimensions"),
+            Self::InvalidArgument => write!(f, "invalid argument"),
+            Self::InsufficientCapacity => write!(f, "data does not fit in the image"),
+            Self::InvalidChannels => write!(f, "pixels do not have the requested channels"),
+            Self::InvalidHeader => write!(f, "not an imgcode image"),
+            Self::UnsupportedVersion => write!(f, "unsupported imgcode format version"),
+            Self::TypeMismatch => write!(f, "image does not contain a value of the requested type"),
+            Self::Cancelled => write!(f, "operation cancelled"),
+            Self::MissingData => write!(f, "some of the data could not be read"),
+            Self::ChecksumMismatch => write!(f, "data does not match its checksum"),
+            Self::InvalidKey => write!(f, "invalid imgcode key"),
+            Self::Encrypted => write!(f, "data is encrypted and no identity was given"),
+            Self::WrongIdentity => write!(f, "data is not encrypted for the given identity"),
+            Self::WrongPassword => write!(f, "no payload is encrypted with the given password"),
+            Self::Unsigned => write!(f, "image is not signed"),
+            Self::BadSignature => write!(f, "image was altered after it was signed"),
+            Self::UntrustedSigner => write!(f, "image is not signed by the trusted key"),
+            Self::Serialization(bincode_err) => write!(f, "{bincode_err}"),
             Self::Io(io_err) => write!(f, "{io_err}"),
         }
     }
@@ -26,4 +62,10 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<bincode::Error> for Error {
+    fn from(value: bincode::Error) -> Self {
+        Self::Serialization(value)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
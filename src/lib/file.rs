@@ -1,33 +1,245 @@
 use std::io::{self, prelude::*};
 
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Header {
+    /// Size of the payload in bytes.
     pub size: u64,
+    /// Tag identifying the type of the payload. `0` means raw bytes.
+    pub tag: u64,
+    /// Number of rows at the top of the image taken up by a label strip.
+    /// The payload starts on the first row after it.
+    pub label_rows: u32,
+    /// Index of the frame holding this part of the payload, if it is
+    /// spread across several frames.
+    pub frame: u32,
+    /// Number of frames the payload is spread across.
+    pub frames: u32,
+    /// Index of the share of a secret the payload is, starting from `1`.
+    pub share: u8,
+    /// Number of shares needed to recover the secret, or `0` if the
+    /// payload is not a share.
+    pub threshold: u8,
+    /// Key of the encrypted payload wrapped for each of its recipients.
+    pub recipients: Vec<[u8; Self::RECIPIENT_SIZE]>,
+    /// Public key of the signer followed by its signature of the header
+    /// and the digest of the payload, if the image is signed.
+    pub signature: Option<[u8; Self::SIGNATURE_SIZE]>,
+    /// Whether the header was written before headers started with
+    /// [`Header::MAGIC`], in which case it only holds the size.
+    pub legacy: bool,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            tag: 0,
+            label_rows: 0,
+            frame: 0,
+            frames: 1,
+            share: 0,
+            threshold: 0,
+            recipients: Vec::new(),
+            signature: None,
+            legacy: false,
+        }
+    }
 }
 
 impl Header {
-    pub const SIZE: usize = 8;
+    pub const MAGIC: [u8; 4] = *b"IMGC";
+    /// Latest version of the format.
+    pub const VERSION: u8 = 6;
+
+    /// Size of a legacy header, which only holds the size of the payload.
+    pub const SIZE_LEGACY: usize = 8;
+    /// Size of a version 1 header, which is written for images without
+    /// a label strip.
+    pub const SIZE: usize = 21;
+    /// Size of a version 2 header.
+    pub const SIZE_V2: usize = Self::SIZE + 4;
+    /// Size of a version 3 header, which is only written for payloads
+    /// spread across several frames.
+    pub const SIZE_V3: usize = Self::SIZE_V2 + 8;
+    /// Size of a version 4 header, which is only written for shares of
+    /// a secret.
+    pub const SIZE_V4: usize = Self::SIZE_V3 + 2;
+    /// Size of a version 5 header before the wrapped keys of its
+    /// recipients, which is only written for encrypted payloads.
+    pub const SIZE_V5: usize = Self::SIZE_V4 + 1;
+    /// Size of the key wrapped for each recipient.
+    pub const RECIPIENT_SIZE: usize = 80;
+    /// Size of the public key and signature at the end of a version 6
+    /// header, which is only written for signed images.
+    pub const SIGNATURE_SIZE: usize = 96;
+
+    /// Get the oldest version of the format which can describe the header,
+    /// or `0` for a legacy header.
+    #[must_use]
+    pub fn version(&self) -> u8 {
+        if self.legacy {
+            0
+        } else if self.signature.is_some() {
+            Self::VERSION
+        } else if !self.recipients.is_empty() {
+            5
+        } else if self.threshold != 0 {
+            4
+        } else if self.frames > 1 {
+            3
+        } else if self.label_rows != 0 {
+            2
+        } else {
+            1
+        }
+    }
 
-    /// Write the header to `writer` returning the number
-    /// of bytes written.
+    /// Get the number of bytes the header takes up when written.
+    #[must_use]
+    pub fn encoded_size(&self) -> usize {
+        match self.version() {
+            0 => Self::SIZE_LEGACY,
+            1 => Self::SIZE,
+            2 => Self::SIZE_V2,
+            3 => Self::SIZE_V3,
+            4 => Self::SIZE_V4,
+            5 => Self::SIZE_V5 + self.recipients.len() * Self::RECIPIENT_SIZE,
+            _ => {
+                Self::SIZE_V5 + self.recipients.len() * Self::RECIPIENT_SIZE + Self::SIGNATURE_SIZE
+            }
+        }
+    }
+
+    /// Write the header to `writer`.
     pub fn write_to<W>(&self, mut writer: W) -> io::Result<()>
     where
         W: Write,
     {
-        let _ = writer.write(&self.size.to_be_bytes())?;
+        let version = self.version();
+        if version == 0 {
+            return writer.write_all(&self.size.to_be_bytes());
+        }
+
+        writer.write_all(&Self::MAGIC)?;
+        writer.write_all(&[version])?;
+        writer.write_all(&self.size.to_be_bytes())?;
+        writer.write_all(&self.tag.to_be_bytes())?;
+        if version >= 2 {
+            writer.write_all(&self.label_rows.to_be_bytes())?;
+        }
+        if version >= 3 {
+            writer.write_all(&self.frame.to_be_bytes())?;
+            writer.write_all(&self.frames.to_be_bytes())?;
+        }
+        if version >= 4 {
+            writer.write_all(&[self.share, self.threshold])?;
+        }
+        if version >= 5 {
+            let count = u8::try_from(self.recipients.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many recipients"))?;
+            writer.write_all(&[count])?;
+            for recipient in &self.recipients {
+                writer.write_all(recipient)?;
+            }
+        }
+        if let Some(signature) = &self.signature {
+            writer.write_all(signature)?;
+        }
         Ok(())
     }
 
     /// Read the header from `reader`.
-    pub fn read_from<R>(mut reader: R) -> io::Result<Self>
+    ///
+    /// Bytes which do not start with [`Header::MAGIC`] are read as a
+    /// legacy header, so callers should check the size it holds against
+    /// the bytes available. Legacy headers of empty payloads are rejected.
+    ///
+    /// Returns [`Error::InvalidHeader`] if `reader` does not start
+    /// with a valid header.
+    pub fn read_from<R>(mut reader: R) -> Result<Self>
     where
         R: Read,
     {
-        let mut buf = vec![0u8; Self::SIZE];
-        reader.read_exact(&mut buf)?;
+        let mut read_exact = |buf: &mut [u8]| {
+            reader.read_exact(buf).map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => Error::InvalidHeader,
+                _ => Error::Io(e),
+            })
+        };
+
+        let mut buf = [0u8; Self::SIZE_V5];
+        read_exact(&mut buf[..Self::MAGIC.len()])?;
+
+        if buf[0..4] != Self::MAGIC {
+            read_exact(&mut buf[Self::MAGIC.len()..Self::SIZE_LEGACY])?;
+            let size = u64::from_be_bytes(buf[..Self::SIZE_LEGACY].try_into().unwrap());
+
+            // An empty payload could not be told apart from a blank image.
+            if size == 0 {
+                return Err(Error::InvalidHeader);
+            }
+            return Ok(Self {
+                size,
+                legacy: true,
+                ..Default::default()
+            });
+        }
+        read_exact(&mut buf[Self::MAGIC.len()..Self::SIZE])?;
+
+        let size = match buf[4] {
+            1 => Self::SIZE,
+            2 => Self::SIZE_V2,
+            3 => Self::SIZE_V3,
+            4 => Self::SIZE_V4,
+            5 | 6 => Self::SIZE_V5,
+            _ => return Err(Error::UnsupportedVersion),
+        };
+        read_exact(&mut buf[Self::SIZE..size])?;
+
+        let u32_at = |i: usize| u32::from_be_bytes(buf[i..i + 4].try_into().unwrap());
+        let (frame, frames) = match buf[4] {
+            3.. => (u32_at(25), u32_at(29)),
+            _ => (0, 1),
+        };
+        let (share, threshold) = match buf[4] {
+            4.. => (buf[33], buf[34]),
+            _ => (0, 0),
+        };
+
+        // Frames past the last one cannot be found, so the payload
+        // could never be read whole, and shares are numbered from `1`.
+        if frame >= frames || (threshold != 0 && share == 0) {
+            return Err(Error::InvalidHeader);
+        }
+
+        let mut recipients =
+            vec![[0u8; Self::RECIPIENT_SIZE]; if buf[4] >= 5 { buf[35].into() } else { 0 }];
+        for recipient in &mut recipients {
+            read_exact(recipient)?;
+        }
+
+        let signature = match buf[4] {
+            6 => {
+                let mut signature = [0u8; Self::SIGNATURE_SIZE];
+                read_exact(&mut signature)?;
+                Some(signature)
+            }
+            _ => None,
+        };
 
         Ok(Self {
-            size: u64::from_be_bytes(buf[0..8].try_into().unwrap()),
+            size: u64::from_be_bytes(buf[5..13].try_into().unwrap()),
+            tag: u64::from_be_bytes(buf[13..21].try_into().unwrap()),
+            label_rows: if buf[4] >= 2 { u32_at(21) } else { 0 },
+            frame,
+            frames,
+            share,
+            threshold,
+            recipients,
+            signature,
+            legacy: false,
         })
     }
 }
@@ -38,7 +250,11 @@ mod tests {
 
     #[test]
     fn test_header_read_write() {
-        let h1 = Header { size: 42 };
+        let h1 = Header {
+            size: 42,
+            tag: 7,
+            ..Default::default()
+        };
 
         let mut buf = vec![0u8; Header::SIZE];
         h1.write_to(buf.as_mut_slice()).unwrap();
@@ -46,5 +262,155 @@ mod tests {
         let h2 = Header::read_from(buf.as_slice()).unwrap();
 
         assert_eq!(h1, h2);
+        assert_eq!(buf[4], 1);
+    }
+
+    #[test]
+    fn test_header_v2_read_write() {
+        let h1 = Header {
+            size: 42,
+            label_rows: 24,
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        h1.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), Header::SIZE_V2);
+        assert_eq!(buf[4], 2);
+
+        assert_eq!(Header::read_from(buf.as_slice()).unwrap(), h1);
+        assert!(matches!(
+            Header::read_from(&buf[..Header::SIZE]),
+            Err(Error::InvalidHeader)
+        ));
+
+        buf[4] = 7;
+        assert!(matches!(
+            Header::read_from(buf.as_slice()),
+            Err(Error::UnsupportedVersion)
+        ));
+    }
+
+    #[test]
+    fn test_header_v3_read_write() {
+        let h1 = Header {
+            size: 42,
+            frame: 2,
+            frames: 3,
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        h1.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), Header::SIZE_V3);
+        assert_eq!(buf[4], 3);
+        assert_eq!(h1.encoded_size(), Header::SIZE_V3);
+
+        assert_eq!(Header::read_from(buf.as_slice()).unwrap(), h1);
+
+        // The index of the frame must be below the number of frames.
+        buf[28] = 3;
+        assert!(matches!(
+            Header::read_from(buf.as_slice()),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_header_v4_read_write() {
+        let h1 = Header {
+            size: 42,
+            share: 2,
+            threshold: 3,
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        h1.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), Header::SIZE_V4);
+        assert_eq!(buf[4], 4);
+
+        assert_eq!(Header::read_from(buf.as_slice()).unwrap(), h1);
+
+        // Shares are numbered from one.
+        buf[33] = 0;
+        assert!(matches!(
+            Header::read_from(buf.as_slice()),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_header_v5_read_write() {
+        let h1 = Header {
+            size: 42,
+            recipients: vec![[1; Header::RECIPIENT_SIZE], [2; Header::RECIPIENT_SIZE]],
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        h1.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), Header::SIZE_V5 + 2 * Header::RECIPIENT_SIZE);
+        assert_eq!(buf.len(), h1.encoded_size());
+        assert_eq!(buf[4], 5);
+
+        assert_eq!(Header::read_from(buf.as_slice()).unwrap(), h1);
+        assert!(matches!(
+            Header::read_from(&buf[..buf.len() - 1]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_header_v6_read_write() {
+        let h1 = Header {
+            size: 42,
+            signature: Some([3; Header::SIGNATURE_SIZE]),
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        h1.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), Header::SIZE_V5 + Header::SIGNATURE_SIZE);
+        assert_eq!(buf.len(), h1.encoded_size());
+        assert_eq!(buf[4], 6);
+
+        assert_eq!(Header::read_from(buf.as_slice()).unwrap(), h1);
+        assert!(matches!(
+            Header::read_from(&buf[..buf.len() - 1]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_header_legacy() {
+        let buf = 42u64.to_be_bytes();
+
+        let h1 = Header::read_from(&buf[..]).unwrap();
+        assert_eq!(h1.size, 42);
+        assert!(h1.legacy);
+        assert_eq!(h1.version(), 0);
+        assert_eq!(h1.encoded_size(), Header::SIZE_LEGACY);
+
+        let mut buf2 = Vec::new();
+        h1.write_to(&mut buf2).unwrap();
+        assert_eq!(buf2, buf);
+
+        assert!(matches!(
+            Header::read_from(&buf[..Header::SIZE_LEGACY - 1]),
+            Err(Error::InvalidHeader)
+        ));
+        assert!(matches!(
+            Header::read_from(&[0u8; Header::SIZE][..]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_header_truncated() {
+        assert!(matches!(
+            Header::read_from(&Header::MAGIC[..]),
+            Err(Error::InvalidHeader)
+        ));
     }
 }
@@ -0,0 +1,611 @@
+use std::collections::hash_map::{Entry, HashMap};
+
+use crate::options::FountainOptions;
+use crate::progress::Ignore;
+use crate::traits::{Image, ImageMut};
+use crate::{Dimensions, EncodeOptions, Error, Result};
+
+/// Tag in the header of each image of a fountain-coded set.
+const TAG: u64 = u64::from_be_bytes(*b"fountain");
+
+/// Number of source symbols the data is split into, unless that would
+/// make them smaller than [`MIN_SYMBOL_SIZE`] or larger than an image.
+const TARGET_SYMBOLS: u64 = 1000;
+/// Smallest size of a source symbol in bytes.
+const MIN_SYMBOL_SIZE: u64 = 16;
+/// Largest number of source symbols, which bounds the memory needed to
+/// decode the data to a few tens of megabytes, as each row of the decoder
+/// has a bit for every source symbol.
+const MAX_SOURCE_SYMBOLS: u64 = 1 << 14;
+
+/// Header at the start of the data of each image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PacketHeader {
+    /// Size of the data in bytes.
+    size: u64,
+    /// CRC-32 of the data.
+    checksum: u32,
+    /// Size of each symbol in bytes.
+    symbol_size: u32,
+    /// Index of the first coded symbol in the image.
+    first: u32,
+    /// Number of coded symbols in the image.
+    count: u32,
+}
+
+impl PacketHeader {
+    const MAGIC: [u8; 4] = *b"IMGF";
+    const VERSION: u8 = 1;
+
+    const SIZE: usize = 29;
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::SIZE);
+        buf.extend(Self::MAGIC);
+        buf.push(Self::VERSION);
+        buf.extend(self.size.to_be_bytes());
+        buf.extend(self.checksum.to_be_bytes());
+        buf.extend(self.symbol_size.to_be_bytes());
+        buf.extend(self.first.to_be_bytes());
+        buf.extend(self.count.to_be_bytes());
+        buf
+    }
+
+    fn from_bytes(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..Self::SIZE)?;
+        if buf[0..4] != Self::MAGIC || buf[4] != Self::VERSION {
+            return None;
+        }
+
+        let u32_at = |i: usize| u32::from_be_bytes(buf[i..i + 4].try_into().unwrap());
+
+        let header = Self {
+            size: u64::from_be_bytes(buf[5..13].try_into().unwrap()),
+            checksum: u32_at(13),
+            symbol_size: u32_at(17),
+            first: u32_at(21),
+            count: u32_at(25),
+        };
+
+        // Data is only split into more than `TARGET_SYMBOLS` source symbols
+        // when a single coded symbol fills each image, so a header claiming
+        // more alongside several coded symbols was not written by
+        // `to_fountain_images()`, and never into more than
+        // `MAX_SOURCE_SYMBOLS`.
+        let valid = header.symbol_size != 0
+            && header.first.checked_add(header.count).is_some()
+            && header.source_symbols().is_some_and(|x| {
+                u64::from(x) <= MAX_SOURCE_SYMBOLS
+                    && (u64::from(x) <= TARGET_SYMBOLS || header.count == 1)
+            });
+        valid.then_some(header)
+    }
+
+    /// Get the number of source symbols the data is split into.
+    fn source_symbols(&self) -> Option<u32> {
+        let symbols = self.size.div_ceil(self.symbol_size.into()).max(1);
+        u32::try_from(symbols).ok()
+    }
+
+    /// Get the number of bytes of coded symbols following the header.
+    fn symbols_size(&self) -> Option<usize> {
+        usize::try_from(self.count)
+            .ok()?
+            .checked_mul(usize::try_from(self.symbol_size).ok()?)
+    }
+}
+
+/// Pseudo-random numbers picking the source symbols of a coded symbol.
+struct Rng(u64);
+
+impl Rng {
+    /// Get the next number, from the `SplitMix64` generator.
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a number below `n`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn below(&mut self, n: u32) -> u32 {
+        (((self.next() >> 32) * u64::from(n)) >> 32) as u32
+    }
+}
+
+/// Get the source symbols, out of `symbols`, combined into coded
+/// symbol `id`.
+///
+/// Each coded symbol combines a few more source symbols than twice the
+/// logarithm of their number, which is dense enough for almost any set
+/// of slightly more coded symbols than source symbols to decode.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn neighbours(id: u32, symbols: u32) -> Vec<u32> {
+    let mut rng = Rng(u64::from(id) << 32 | u64::from(symbols));
+
+    let mean = 2 * f64::from(symbols).ln().ceil() as u32 + 2;
+
+    // With few source symbols, most coded symbols would combine all of
+    // them, so each is picked with even odds instead.
+    if symbols <= 2 * mean {
+        loop {
+            let picked = (0..symbols)
+                .filter(|_| rng.next() & 1 != 0)
+                .collect::<Vec<_>>();
+            if !picked.is_empty() {
+                return picked;
+            }
+        }
+    }
+
+    let degree = mean / 2 + rng.below(mean + 1);
+    let mut picked = Vec::with_capacity(degree as usize);
+    while picked.len() < degree as usize {
+        let x = rng.below(symbols);
+        if !picked.contains(&x) {
+            picked.push(x);
+        }
+    }
+
+    picked
+}
+
+/// XOR the bytes of `other` into `bytes`.
+fn xor(bytes: &mut [u8], other: &[u8]) {
+    for (x, y) in bytes.iter_mut().zip(other) {
+        *x ^= y;
+    }
+}
+
+/// Get the size of the source symbols of `len` bytes of data coded
+/// into images holding `image_size` bytes each.
+fn symbol_size(len: u64, image_size: u64) -> Option<u32> {
+    let room = image_size.checked_sub(PacketHeader::SIZE as u64)?;
+    let size = len.div_ceil(TARGET_SYMBOLS).max(MIN_SYMBOL_SIZE).min(room);
+    u32::try_from(size).ok().filter(|&x| x != 0)
+}
+
+/// Code `data` into `count` images, any of which can be lost: any set
+/// of the images holding slightly more bytes in total than `data` can
+/// be decoded by [`from_fountain_images()`].
+///
+/// The data is split into source symbols, and each image holds coded
+/// symbols of the size given by `fountain`, which combine pseudo-random
+/// sets of source symbols. Images are written according to `options`
+/// and all have the same dimensions.
+///
+/// # Errors
+///
+/// - `count` is zero
+/// - The image size in `fountain` cannot hold a coded symbol
+/// - The data would be split into more than 16384 source symbols, as
+///   images of the size in `fountain` are too small for it
+/// - There are too many coded symbols to number them
+/// - See [`plan()`](crate::plan)
+#[allow(clippy::cast_possible_truncation)]
+pub fn to_fountain_images<I>(
+    data: impl AsRef<[u8]>,
+    count: u32,
+    fountain: &FountainOptions,
+    options: &EncodeOptions,
+) -> Result<Vec<I>>
+where
+    I: ImageMut,
+{
+    let data = data.as_ref();
+    if count == 0 {
+        return Err(Error::InvalidArgument);
+    }
+
+    let symbol_size =
+        symbol_size(data.len() as u64, fountain.image_size).ok_or(Error::InsufficientCapacity)?;
+    let header = PacketHeader {
+        size: data.len() as u64,
+        checksum: crc32fast::hash(data),
+        symbol_size,
+        first: 0,
+        count: 0,
+    };
+    let symbols = header
+        .source_symbols()
+        .filter(|&x| u64::from(x) <= MAX_SOURCE_SYMBOLS)
+        .ok_or(Error::SizeLimit)?;
+
+    let per_image = (fountain.image_size - PacketHeader::SIZE as u64) / u64::from(symbol_size);
+    let per_image = u32::try_from(per_image.min(symbols.into())).map_err(|_| Error::SizeLimit)?;
+    if per_image.checked_mul(count).is_none() {
+        return Err(Error::SizeLimit);
+    }
+
+    let symbol_size = symbol_size as usize;
+    let mut source = data.to_vec();
+    source.resize(symbols as usize * symbol_size, 0);
+
+    let packet_size = PacketHeader::SIZE + per_image as usize * symbol_size;
+    let size =
+        packet_size as u64 + crate::payload_overhead(&crate::file::Header::default(), options);
+    let plan = crate::plan(I::PIXEL_FORMAT, size, options)?;
+    let options = options
+        .clone()
+        .dimensions(Dimensions::Exact(plan.width, plan.height));
+
+    (0..count)
+        .map(|i| {
+            let header = PacketHeader {
+                first: i * per_image,
+                count: per_image,
+                ..header
+            };
+
+            let mut packet = header.to_bytes();
+            packet.reserve(packet_size);
+            for id in header.first..header.first + per_image {
+                let mut symbol = vec![0; symbol_size];
+                for x in neighbours(id, symbols) {
+                    let start = x as usize * symbol_size;
+                    xor(&mut symbol, &source[start..start + symbol_size]);
+                }
+                packet.extend(symbol);
+            }
+
+            crate::to_image_tagged(&packet, TAG, &options, &mut Ignore)
+        })
+        .collect()
+}
+
+/// A source symbol solved for in terms of the source symbols after it.
+struct Row {
+    /// Bits of the source symbols combined, by index.
+    coefficients: Vec<u64>,
+    data: Vec<u8>,
+}
+
+/// Solves for the source symbols by Gaussian elimination as coded
+/// symbols are added, keeping a row for each source symbol found as the
+/// first one of a combination.
+struct Decoder {
+    symbol_size: usize,
+    rows: Vec<Option<Row>>,
+    rank: usize,
+}
+
+impl Decoder {
+    fn new(symbols: u32, symbol_size: u32) -> Result<Self> {
+        let mut rows = Vec::new();
+        rows.try_reserve_exact(symbols as usize)
+            .map_err(|_| Error::SizeLimit)?;
+        rows.resize_with(symbols as usize, || None);
+
+        Ok(Self {
+            symbol_size: symbol_size as usize,
+            rows,
+            rank: 0,
+        })
+    }
+
+    fn is_complete(&self) -> bool {
+        self.rank == self.rows.len()
+    }
+
+    /// Add the coded symbol combining `neighbours` with bytes `data`.
+    fn add(&mut self, neighbours: &[u32], data: &[u8]) -> Result<()> {
+        let words = self.rows.len().div_ceil(64);
+        let mut coefficients = Vec::new();
+        coefficients
+            .try_reserve_exact(words)
+            .map_err(|_| Error::SizeLimit)?;
+        coefficients.resize(words, 0u64);
+        for &x in neighbours {
+            coefficients[x as usize / 64] ^= 1 << (x % 64);
+        }
+        let mut data = data.to_vec();
+
+        // Rows only have bits from their own source symbol onward, so
+        // eliminating the first bit never sets an earlier one.
+        let mut word = 0;
+        while word < coefficients.len() {
+            if coefficients[word] == 0 {
+                word += 1;
+                continue;
+            }
+
+            let first = word * 64 + coefficients[word].trailing_zeros() as usize;
+            let Some(row) = &self.rows[first] else {
+                self.rows[first] = Some(Row { coefficients, data });
+                self.rank += 1;
+                return Ok(());
+            };
+
+            for (x, y) in coefficients[word..]
+                .iter_mut()
+                .zip(&row.coefficients[word..])
+            {
+                *x ^= y;
+            }
+            xor(&mut data, &row.data);
+        }
+
+        Ok(())
+    }
+
+    /// Get the source symbols, once there is a row for each of them.
+    fn finish(mut self) -> Option<Vec<u8>> {
+        if !self.is_complete() {
+            return None;
+        }
+
+        // Substitute the later symbols, which are solved first.
+        for i in (0..self.rows.len()).rev() {
+            let (rows, solved) = self.rows.split_at_mut(i + 1);
+            let row = rows[i].as_mut()?;
+
+            for (word, &bits) in row.coefficients.iter().enumerate().skip(i / 64) {
+                let mut bits = bits;
+                while bits != 0 {
+                    let j = word * 64 + bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    if j > i {
+                        xor(&mut row.data, &solved[j - i - 1].as_ref()?.data);
+                    }
+                }
+            }
+        }
+
+        let mut data = Vec::with_capacity(self.rows.len() * self.symbol_size);
+        for row in self.rows {
+            data.extend(row?.data);
+        }
+        Some(data)
+    }
+}
+
+/// Read the data coded by [`to_fountain_images()`] from any set of its
+/// images, in any order.
+///
+/// Images are grouped into sets by the data they code, and the data of
+/// the first set holding enough coded symbols to decode it is returned.
+/// Images which cannot be read, or whose set there is not enough memory
+/// to decode, are skipped.
+///
+/// # Errors
+///
+/// - None of the images belong to a fountain-coded set
+/// - The images do not hold enough coded symbols to decode the data
+/// - There is not enough memory to decode the data
+/// - The data does not match its checksum
+pub fn from_fountain_images<I, F>(images: F) -> Result<Vec<u8>>
+where
+    I: Image,
+    F: IntoIterator<Item = I>,
+{
+    // Sets whose data does not match its checksum are kept without a
+    // decoder, so that their other images are skipped.
+    let mut sets: HashMap<(u64, u32, u32), Option<Decoder>> = HashMap::new();
+    let mut error = None;
+
+    for image in images {
+        let Ok((header, packet)) =
+            crate::read_frame(image, &crate::DecodeOptions::new(), &mut Ignore)
+        else {
+            continue;
+        };
+        if header.tag != TAG || header.frames > 1 {
+            continue;
+        }
+
+        let Some(header) = PacketHeader::from_bytes(&packet) else {
+            continue;
+        };
+        let Some(symbols) =
+            packet[PacketHeader::SIZE..].get(..header.symbols_size().unwrap_or(usize::MAX))
+        else {
+            continue;
+        };
+
+        let source_symbols = header.source_symbols().ok_or(Error::SizeLimit)?;
+
+        // Skip images whose set there is not enough memory to decode.
+        let key = (header.size, header.checksum, header.symbol_size);
+        let set = match sets.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match Decoder::new(source_symbols, header.symbol_size) {
+                Ok(decoder) => entry.insert(Some(decoder)),
+                Err(_) => continue,
+            },
+        };
+        let Some(decoder) = set else {
+            continue;
+        };
+
+        for (id, symbol) in (header.first..).zip(symbols.chunks_exact(decoder.symbol_size)) {
+            if decoder.is_complete() {
+                break;
+            }
+            decoder.add(&neighbours(id, source_symbols), symbol)?;
+        }
+
+        if !decoder.is_complete() {
+            continue;
+        }
+        if let Some(decoder) = set.take() {
+            match finish(decoder, &header) {
+                Ok(data) => return Ok(data),
+                Err(e) => error = Some(e),
+            }
+        }
+    }
+
+    Err(match error {
+        Some(e) => e,
+        None if sets.is_empty() => Error::InvalidHeader,
+        None => Error::MissingData,
+    })
+}
+
+/// Get the data of the set of `header` from its complete `decoder`.
+fn finish(decoder: Decoder, header: &PacketHeader) -> Result<Vec<u8>> {
+    let mut data = decoder.finish().ok_or(Error::MissingData)?;
+    data.truncate(usize::try_from(header.size).map_err(|_| Error::SizeLimit)?);
+
+    if crc32fast::hash(&data) != header.checksum {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::RgbImage;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn data(len: usize) -> Vec<u8> {
+        let mut rng = Rng(7);
+        (0..len).map(|_| rng.next() as u8).collect()
+    }
+
+    #[test]
+    fn test_any_subset_decodes() {
+        let data = data(3000);
+        let fountain = FountainOptions::new().image_size(400);
+        let images: Vec<RgbImage> =
+            to_fountain_images(&data, 20, &fountain, &EncodeOptions::new()).unwrap();
+        assert!(images
+            .iter()
+            .all(|x| x.dimensions() == images[0].dimensions()));
+
+        // Each image holds 23 of the 188 source symbols' worth of data.
+        let odd = images.iter().skip(1).step_by(2).cloned();
+        assert_eq!(from_fountain_images(odd).unwrap(), data);
+
+        let last = images.iter().rev().take(9).cloned();
+        assert_eq!(from_fountain_images(last).unwrap(), data);
+
+        let few = images.iter().take(6).cloned();
+        assert!(matches!(from_fountain_images(few), Err(Error::MissingData)));
+    }
+
+    #[test]
+    fn test_other_images_skipped() {
+        let data = data(500);
+        let fountain = FountainOptions::new().image_size(200);
+        let options = EncodeOptions::new().block_size(2);
+        let images: Vec<RgbImage> = to_fountain_images(&data, 8, &fountain, &options).unwrap();
+        let others: Vec<RgbImage> = to_fountain_images(&data[1..], 8, &fountain, &options).unwrap();
+
+        let plain = crate::to_image::<RgbImage>(&data, 1.0);
+        // The data is the one of the first set with enough images.
+        let mixed = [plain.clone(), others[0].clone(), images[2].clone()]
+            .into_iter()
+            .chain(images[3..].iter().cloned())
+            .chain(others[1..].iter().cloned());
+        assert_eq!(from_fountain_images(mixed).unwrap(), data);
+
+        let mixed = others[..2].iter().chain(&images[2..]).chain(&others[2..]);
+        assert_eq!(from_fountain_images(mixed.cloned()).unwrap(), data);
+
+        let mixed = images[..2].iter().chain(&others);
+        assert_eq!(from_fountain_images(mixed.cloned()).unwrap(), &data[1..]);
+
+        assert!(matches!(
+            from_fountain_images([plain]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_headers_skipped() {
+        let data = data(500);
+        let fountain = FountainOptions::new().image_size(200);
+        let options = EncodeOptions::new();
+        let images: Vec<RgbImage> = to_fountain_images(&data, 8, &fountain, &options).unwrap();
+
+        let header = PacketHeader {
+            size: 500,
+            checksum: crc32fast::hash(&data),
+            symbol_size: 16,
+            first: 0,
+            count: 2,
+        };
+        let tampered = [
+            // Billions of source symbols, which the decoder would allocate.
+            PacketHeader {
+                size: 16 * u64::from(u32::MAX - 1),
+                ..header
+            },
+            // Coded symbols numbered past the largest number.
+            PacketHeader {
+                first: u32::MAX,
+                ..header
+            },
+            // A single coded symbol of billions of source symbols.
+            PacketHeader {
+                size: 16 * u64::from(u32::MAX - 1),
+                count: 1,
+                ..header
+            },
+            // A single coded symbol of one more source symbol than the
+            // largest number.
+            PacketHeader {
+                size: 16 * (MAX_SOURCE_SYMBOLS + 1),
+                count: 1,
+                ..header
+            },
+        ];
+
+        for header in tampered {
+            let mut packet = header.to_bytes();
+            packet.extend([0; 32]);
+            let image: RgbImage =
+                crate::to_image_tagged(&packet, TAG, &options, &mut Ignore).unwrap();
+
+            let set = std::iter::once(image).chain(images.iter().cloned());
+            assert_eq!(from_fountain_images(set).unwrap(), data, "{header:?}");
+        }
+    }
+
+    #[test]
+    fn test_small_data() {
+        for len in [0, 1, 16, 40] {
+            let data = data(len);
+            let images: Vec<RgbImage> =
+                to_fountain_images(&data, 4, &FountainOptions::new(), &EncodeOptions::new())
+                    .unwrap();
+            assert_eq!(from_fountain_images(images).unwrap(), data, "{len}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let options = EncodeOptions::new();
+        assert!(matches!(
+            to_fountain_images::<RgbImage>([1, 2, 3], 0, &FountainOptions::new(), &options),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            to_fountain_images::<RgbImage>(
+                [1, 2, 3],
+                4,
+                &FountainOptions::new().image_size(29),
+                &options
+            ),
+            Err(Error::InsufficientCapacity)
+        ));
+
+        // Each image holds a single byte of the data.
+        let data = data(usize::try_from(MAX_SOURCE_SYMBOLS).unwrap() + 1);
+        assert!(matches!(
+            to_fountain_images::<RgbImage>(
+                data,
+                4,
+                &FountainOptions::new().image_size(30),
+                &options
+            ),
+            Err(Error::SizeLimit)
+        ));
+    }
+}
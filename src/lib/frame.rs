@@ -0,0 +1,459 @@
+use crate::block::{self, Grid};
+use crate::label::pixel_bytes;
+use crate::traits::{Image, ImageMut};
+
+/// Width in blocks of the white quiet zone around the markers.
+const QUIET: u32 = 2;
+/// Side in blocks of a finder pattern.
+pub const FINDER: u32 = 7;
+/// Row of the timing row and column of the timing column, in blocks.
+/// They line up with the inner edges of the finder patterns.
+const TIMING: u32 = QUIET + FINDER - 1;
+/// Number of blocks between each edge of a framed image and the data.
+pub const BORDER: u32 = QUIET + FINDER + 1;
+
+/// Check whether block `x`,`y` of a finder pattern is dark: a ring
+/// around a 3x3 square.
+pub fn is_finder_dark(x: u32, y: u32) -> bool {
+    let ring = x == 0 || y == 0 || x == FINDER - 1 || y == FINDER - 1;
+    let centre = (2..=4).contains(&x) && (2..=4).contains(&y);
+    ring || centre
+}
+
+/// Get whether block `x`,`y` of a frame around `width`x`height` blocks
+/// of data is dark. Returns `None` for blocks holding data.
+fn frame_block(x: u32, y: u32, width: u32, height: u32) -> Option<bool> {
+    let data = |p: u32, n: u32| p.checked_sub(BORDER).filter(|&p| p < n);
+    let (data_x, data_y) = (data(x, width), data(y, height));
+
+    if data_x.is_some() && data_y.is_some() {
+        return None;
+    }
+
+    // Finder patterns sit in the corners, inside the quiet zone.
+    let finder = |p: u32, n: u32| {
+        let far = n + 2 * BORDER - QUIET - FINDER;
+        match p {
+            p if (QUIET..QUIET + FINDER).contains(&p) => Some(p - QUIET),
+            p if (far..far + FINDER).contains(&p) => Some(p - far),
+            _ => None,
+        }
+    };
+
+    if let (Some(x), Some(y)) = (finder(x, width), finder(y, height)) {
+        return Some(is_finder_dark(x, y));
+    }
+
+    // Timing patterns alternate along the top and left of the data,
+    // starting with a dark block.
+    match (x, y, data_x, data_y) {
+        (_, TIMING, Some(x), _) => Some(x % 2 == 0),
+        (TIMING, _, _, Some(y)) => Some(y % 2 == 0),
+        _ => Some(false),
+    }
+}
+
+/// Draw each pixel of `image` as a `size`x`size` block inside a frame
+/// with a finder pattern in each corner and timing patterns along the
+/// top and left of the data.
+///
+/// The frame lets the decoder find the data in an image with a border
+/// added around it, or with some of its quiet zone cropped off.
+pub fn add<I>(image: &I, size: u32) -> I
+where
+    I: ImageMut,
+{
+    let (white, black) = (pixel_bytes::<I>(true), pixel_bytes::<I>(false));
+    let (width, height) = (image.width(), image.height());
+
+    block::draw(
+        width + 2 * BORDER,
+        height + 2 * BORDER,
+        size,
+        |x, y| match frame_block(x, y, width, height) {
+            Some(true) => Some(black.as_slice()),
+            Some(false) => Some(white.as_slice()),
+            None => image.get_pixel(x - BORDER, y - BORDER),
+        },
+    )
+}
+
+/// Whether each pixel of an image is dark.
+pub struct Bits {
+    width: u32,
+    height: u32,
+    dark: Vec<bool>,
+}
+
+impl Bits {
+    pub fn new<I>(image: &I) -> Self
+    where
+        I: Image,
+    {
+        let (width, height) = (image.width(), image.height());
+        let dark = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y).is_some_and(is_dark::<I>))
+            .collect();
+
+        Self {
+            width,
+            height,
+            dark,
+        }
+    }
+
+    /// Get the pixels of a grayscale `image` darker than `threshold`.
+    pub fn from_luma(image: &image::GrayImage, threshold: u8) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            dark: image.pixels().map(|x| x.0[0] < threshold).collect(),
+        }
+    }
+
+    fn get(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.dark[(y * self.width + x) as usize]
+    }
+
+    fn row(&self, y: u32) -> Vec<bool> {
+        (0..self.width).map(|x| self.get(x, y)).collect()
+    }
+
+    fn column(&self, x: u32) -> Vec<bool> {
+        (0..self.height).map(|y| self.get(x, y)).collect()
+    }
+}
+
+/// Check whether a pixel of `I` is closer to black than to white.
+pub fn is_dark<I>(pixel: &[u8]) -> bool
+where
+    I: Image,
+{
+    let channel_size = (I::PIXEL_SIZE / I::CHANNEL_NUM) as usize;
+
+    let brightness: f32 = pixel
+        .chunks_exact(channel_size)
+        .take(3)
+        .map(|x| match <[u8; 4]>::try_from(x) {
+            Ok(x) => f32::from_ne_bytes(x),
+            Err(_) => f32::from(x[0]) / 255.0,
+        })
+        .sum();
+
+    brightness < 1.5
+}
+
+/// Run of pixels of the same colour along a line.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    start: u32,
+    len: u32,
+    dark: bool,
+}
+
+impl Run {
+    fn end(self) -> u32 {
+        self.start + self.len
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn runs(line: &[bool]) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+
+    for (i, &dark) in line.iter().enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.dark == dark => run.len += 1,
+            _ => runs.push(Run {
+                start: i as u32,
+                len: 1,
+                dark,
+            }),
+        }
+    }
+
+    runs
+}
+
+/// Check whether five runs, starting with a dark one, have the 1:1:3:1:1
+/// proportions of a line through the centre of a finder pattern.
+#[allow(clippy::cast_precision_loss)]
+fn is_finder_line(runs: &[Run]) -> bool {
+    if runs.len() != 5 || !runs[0].dark {
+        return false;
+    }
+
+    let module = (runs.iter().map(|x| x.len).sum::<u32>() as f32) / FINDER as f32;
+
+    runs.iter()
+        .zip([1.0, 1.0, 3.0, 1.0, 1.0])
+        .all(|(run, n)| (run.len as f32 - n * module).abs() <= module * 0.6)
+}
+
+/// Finder pattern found in an image, as the pixels it spans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Finder {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+impl Finder {
+    #[allow(clippy::cast_precision_loss)]
+    fn module_width(self) -> f32 {
+        (self.x1 - self.x0) as f32 / FINDER as f32
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn module_height(self) -> f32 {
+        (self.y1 - self.y0) as f32 / FINDER as f32
+    }
+
+    /// Get the average side of a block of the pattern.
+    // `f32::midpoint()` is newer than the compilers the crate supports.
+    #[allow(clippy::manual_midpoint)]
+    pub fn module_size(self) -> f32 {
+        (self.module_width() + self.module_height()) / 2.0
+    }
+
+    /// Get the position of the centre of the pattern.
+    #[allow(clippy::cast_precision_loss, clippy::manual_midpoint)]
+    pub fn centre(self) -> (f32, f32) {
+        (
+            (self.x0 as f32 + self.x1 as f32) / 2.0,
+            (self.y0 as f32 + self.y1 as f32) / 2.0,
+        )
+    }
+
+    fn contains(self, x: u32, y: u32) -> bool {
+        (self.x0..self.x1).contains(&x) && (self.y0..self.y1).contains(&y)
+    }
+
+    /// Check that the centres of the blocks of the pattern have the
+    /// colours they should have, allowing for a few blocks damaged by
+    /// noise or slightly off because the image is rotated.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    fn is_valid(self, bits: &Bits) -> bool {
+        let (width, height) = (self.module_width(), self.module_height());
+
+        let wrong = (0..FINDER)
+            .flat_map(|y| (0..FINDER).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                let px = self.x0 + ((x as f32 + 0.5) * width) as u32;
+                let py = self.y0 + ((y as f32 + 0.5) * height) as u32;
+                bits.get(px, py) != is_finder_dark(x, y)
+            })
+            .count();
+
+        wrong <= 2
+    }
+}
+
+/// Find the window of five runs around the run containing `pos` which
+/// looks like a line through the centre of a finder pattern.
+fn finder_line(runs: &[Run], pos: u32) -> Option<&[Run]> {
+    let i = runs
+        .iter()
+        .position(|r| (r.start..r.end()).contains(&pos))?;
+    let window = runs.get(i.checked_sub(2)?..i + 3)?;
+    is_finder_line(window).then_some(window)
+}
+
+/// Find all finder patterns in `bits`.
+#[allow(clippy::manual_midpoint)]
+pub fn find_finders(bits: &Bits) -> Vec<Finder> {
+    let mut finders: Vec<Finder> = Vec::new();
+
+    for y in 0..bits.height {
+        let row = runs(&bits.row(y));
+
+        for window in row.windows(5) {
+            if !is_finder_line(window) {
+                continue;
+            }
+
+            let (x0, x1) = (window[0].start, window[4].end());
+            let x = (x0 + x1) / 2;
+            if finders.iter().any(|f| f.contains(x, y)) {
+                continue;
+            }
+
+            // Check the column through the centre of the candidate too.
+            let column = runs(&bits.column(x));
+            let Some(window) = finder_line(&column, y) else {
+                continue;
+            };
+            let (y0, y1) = (window[0].start, window[4].end());
+
+            // Measure the width again through the centre, as the first
+            // row found may be off centre.
+            let row = runs(&bits.row((y0 + y1) / 2));
+            let (x0, x1) = finder_line(&row, x).map_or((x0, x1), |x| (x[0].start, x[4].end()));
+
+            let finder = Finder { x0, y0, x1, y1 };
+
+            if finder.is_valid(bits) {
+                finders.push(finder);
+            }
+        }
+    }
+
+    finders
+}
+
+/// Get the pixel at the centre of each of `count` blocks of size `module`
+/// along `line`, which starts with a white block followed by the blocks
+/// of a timing pattern.
+///
+/// The edges of the blocks are taken from the timing pattern if it is
+/// intact, so that images scaled by a fraction are sampled correctly.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss, clippy::manual_midpoint)]
+fn centres(line: &[bool], count: u32, module: f32) -> Vec<u32> {
+    let count = count as usize;
+
+    let edges = (1..line.len())
+        .filter(|&i| line[i] != line[i - 1])
+        .map(|i| i as f32);
+    let mut edges = edges.take(count + 1).collect::<Vec<_>>();
+
+    // The last block merges with the white block after it if it is white.
+    if edges.len() == count {
+        if let Some(&first) = edges.first() {
+            edges.push(line.len() as f32 - first);
+        }
+    }
+
+    let fits = edges.len() == count + 1
+        && edges
+            .windows(2)
+            .all(|x| (x[1] - x[0] - module).abs() <= module * 0.5);
+
+    if fits {
+        edges
+            .windows(2)
+            .map(|x| ((x[0] + x[1]) / 2.0) as u32)
+            .collect()
+    } else {
+        (0..count)
+            .map(|i| ((i as f32 + 1.5) * module) as u32)
+            .collect()
+    }
+}
+
+/// Find the blocks holding data in an image written by [`add()`], which
+/// may have a border added around it or some of its quiet zone cropped off.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss, clippy::manual_midpoint)]
+pub fn find<I>(image: &I) -> Option<Grid>
+where
+    I: Image,
+{
+    let bits = Bits::new(image);
+    let finders = find_finders(&bits);
+
+    let corner = |key: fn(&Finder) -> i64| finders.iter().copied().min_by_key(key);
+    let top_left = corner(|f| i64::from(f.x0) + i64::from(f.y0))?;
+    let top_right = corner(|f| i64::from(f.y0) - i64::from(f.x1))?;
+    let bottom_left = corner(|f| i64::from(f.x0) - i64::from(f.y1))?;
+
+    if top_right.x0 <= top_left.x1 || bottom_left.y0 <= top_left.y1 {
+        return None;
+    }
+
+    let module_width = (top_left.module_width() + top_right.module_width()) / 2.0;
+    let module_height = (top_left.module_height() + bottom_left.module_height()) / 2.0;
+
+    // The finder patterns span the data, the timing patterns and
+    // a white block on each side of them.
+    let blocks = |span: u32, module: f32| {
+        let n = (span as f32 / module).round() as u32;
+        n.checked_sub(2 * (FINDER + 1)).filter(|&n| n != 0)
+    };
+    let width = blocks(top_right.x1 - top_left.x0, module_width)?;
+    let height = blocks(bottom_left.y1 - top_left.y0, module_height)?;
+
+    let timing_row = top_left.y0 + ((FINDER as f32 - 0.5) * module_height) as u32;
+    let timing_column = top_left.x0 + ((FINDER as f32 - 0.5) * module_width) as u32;
+
+    let row = bits.row(timing_row);
+    let column = bits.column(timing_column);
+    let row = &row[top_left.x1 as usize..top_right.x0 as usize];
+    let column = &column[top_left.y1 as usize..bottom_left.y0 as usize];
+
+    let columns = centres(row, width, module_width);
+    let rows = centres(column, height, module_height);
+
+    Some(Grid {
+        columns: columns.into_iter().map(|x| top_left.x1 + x).collect(),
+        rows: rows.into_iter().map(|y| top_left.y1 + y).collect(),
+        block_size: module_width.round() as u32,
+        markers: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framed(size: u32) -> (image::RgbImage, image::RgbImage) {
+        let image = image::RgbImage::from_raw(5, 4, (0..60).collect()).unwrap();
+        let framed = add(&image, size);
+        (image, framed)
+    }
+
+    fn check(image: &image::RgbImage, framed: &image::RgbImage) {
+        let grid = find(framed).unwrap();
+        let blocks = block::Blocks::new(framed, grid);
+
+        assert_eq!((blocks.width(), blocks.height()), image.dimensions());
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(blocks.get_pixel(x, y), Some(pixel.0.as_slice()), "{x},{y}");
+        }
+    }
+
+    #[test]
+    fn test_add_find() {
+        let (image, framed) = framed(1);
+        assert_eq!(framed.dimensions(), (5 + 2 * BORDER, 4 + 2 * BORDER));
+        assert_eq!(framed.get_pixel(QUIET, QUIET).0, [0; 3]);
+        assert_eq!(framed.get_pixel(BORDER, TIMING).0, [0; 3]);
+        assert_eq!(framed.get_pixel(BORDER + 1, TIMING).0, [255; 3]);
+        assert_eq!(framed.get_pixel(BORDER, BORDER).0, [0, 1, 2]);
+
+        check(&image, &framed);
+        assert_eq!(find(&image), None);
+    }
+
+    #[test]
+    fn test_find_border_crop() {
+        let (image, framed) = framed(3);
+        let (width, height) = framed.dimensions();
+
+        // A black border around the image.
+        let mut bordered = image::RgbImage::new(width + 23, height + 10);
+        image::imageops::replace(&mut bordered, &framed, 11, 4);
+        check(&image, &bordered);
+
+        // Part of the quiet zone cropped off.
+        let cropped = image::imageops::crop_imm(&framed, 4, 2, width - 9, height - 5).to_image();
+        check(&image, &cropped);
+    }
+
+    #[test]
+    fn test_find_fractional_scale() {
+        let (image, framed) = framed(2);
+        let (width, height) = framed.dimensions();
+
+        let scaled = image::imageops::resize(
+            &framed,
+            width * 3 / 2,
+            height * 3 / 2,
+            image::imageops::Nearest,
+        );
+        check(&image, &scaled);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::traits::{Image, ImageMut};
+
+/// Number of rows taken up by a label strip.
+pub const ROWS: u32 = 24;
+
+/// Factor by which glyphs are scaled up.
+const SCALE: u32 = 2;
+/// Width of a glyph, before scaling.
+const GLYPH_WIDTH: u32 = 5;
+/// Height of a glyph, before scaling.
+pub const GLYPH_HEIGHT: u32 = 7;
+/// Space between the glyphs and the edges of the strip.
+const MARGIN: u32 = (ROWS - GLYPH_HEIGHT * SCALE) / 2;
+
+/// Get the rows of the 5x7 glyph of `c`, most significant bit on the left.
+///
+/// Lowercase letters are drawn as uppercase ones and characters without
+/// a glyph as `?`.
+#[rustfmt::skip]
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ' ' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '_' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        '=' => [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000],
+        '#' => [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010],
+        '%' => [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+/// Width of the space taken up by a glyph, before scaling.
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+
+/// Get the width in pixels a strip needs to show all of `text`.
+pub fn width(text: &str) -> u32 {
+    let len = u32::try_from(text.chars().count()).unwrap_or(u32::MAX);
+    len.saturating_mul(CELL_WIDTH * SCALE)
+        .saturating_add(2 * MARGIN)
+}
+
+/// Check whether the pixel at `x`,`y` of a strip with `text` is lit.
+fn is_lit(text: &[char], x: u32, y: u32) -> bool {
+    x >= MARGIN && y >= MARGIN && is_text_pixel(text, SCALE, x - MARGIN, y - MARGIN)
+}
+
+/// Check whether the pixel at `x`,`y` of `text` drawn with glyphs scaled
+/// up by `scale`, starting from the top left corner, is part of a glyph.
+pub fn is_text_pixel(text: &[char], scale: u32, x: u32, y: u32) -> bool {
+    let cell_width = CELL_WIDTH * scale;
+
+    let y = y / scale;
+    let Some(&c) = text.get((x / cell_width) as usize) else {
+        return false;
+    };
+
+    let column = (x % cell_width) / scale;
+    if y >= GLYPH_HEIGHT || column >= GLYPH_WIDTH {
+        return false;
+    }
+
+    glyph(c)[y as usize] & (1 << (GLYPH_WIDTH - 1 - column)) != 0
+}
+
+/// Get the bytes of an opaque pixel of `I` which is white if `lit`
+/// or black otherwise.
+pub fn pixel_bytes<I>(lit: bool) -> Vec<u8>
+where
+    I: Image,
+{
+    let channel_size = I::PIXEL_SIZE / I::CHANNEL_NUM;
+
+    (0..I::CHANNEL_NUM)
+        .flat_map(|i| {
+            let on = lit || i == 3;
+            match channel_size {
+                1 => vec![if on { u8::MAX } else { 0 }],
+                _ => f32::from(u8::from(on)).to_ne_bytes().to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Draw `text` in white on black into the top [`ROWS`] rows of `image`.
+///
+/// The first `skip` pixels of the image, in row order, are left as they are.
+pub fn render<I>(image: &mut I, text: &str, skip: u64)
+where
+    I: ImageMut,
+{
+    let text = text.chars().collect::<Vec<_>>();
+    let (on, off) = (pixel_bytes::<I>(true), pixel_bytes::<I>(false));
+
+    let width = image.width();
+    let rows = ROWS.min(image.height());
+
+    for y in 0..rows {
+        for x in 0..width {
+            if u64::from(y) * u64::from(width) + u64::from(x) < skip {
+                continue;
+            }
+
+            let bytes = if is_lit(&text, x, y) { &on } else { &off };
+            if let Some(pixel) = image.get_pixel_mut(x, y) {
+                pixel.copy_from_slice(bytes);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut image = image::RgbaImage::from_pixel(40, 30, image::Rgba([1, 2, 3, 4]));
+        render(&mut image, "I", 3);
+
+        // Pixels holding the header and below the strip are untouched.
+        assert_eq!(image.get_pixel(2, 0).0, [1, 2, 3, 4]);
+        assert_eq!(image.get_pixel(0, ROWS).0, [1, 2, 3, 4]);
+
+        assert_eq!(image.get_pixel(3, 0).0, [0, 0, 0, 255]);
+
+        // The top bar of the `I` starts one glyph column in.
+        let (x, y) = (MARGIN + SCALE, MARGIN);
+        assert_eq!(image.get_pixel(x, y).0, [255; 4]);
+        assert_eq!(image.get_pixel(x - 1, y).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width(""), 2 * MARGIN);
+        assert_eq!(width("ab"), 2 * MARGIN + 24);
+    }
+
+    #[test]
+    fn test_pixel_bytes() {
+        assert_eq!(pixel_bytes::<image::RgbImage>(false), [0; 3]);
+        assert_eq!(
+            pixel_bytes::<image::Rgba32FImage>(true),
+            [1.0f32; 4]
+                .iter()
+                .flat_map(|x| x.to_ne_bytes())
+                .collect::<Vec<_>>()
+        );
+    }
+}
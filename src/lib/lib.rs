@@ -6,19 +6,53 @@
     clippy::style
 )]
 
-use std::io::prelude::*;
+use std::io::{Seek, SeekFrom};
 
 mod private {
     pub trait Sealed {}
 }
 
+mod bilevel;
+mod block;
+mod channels;
 mod cursor;
+mod deniable;
 mod error;
 mod file;
+mod fountain;
+mod frame;
+mod label;
+mod options;
+mod paper;
+mod plan;
+mod probe;
+mod progress;
+mod recipient;
+mod region;
+mod rs;
+mod serialize;
+mod shamir;
+mod signature;
 mod traits;
 
+pub use channels::Channels;
+pub use deniable::{from_deniable_image, from_deniable_image_with, to_deniable_image};
 pub use error::{Error, Result};
-use traits::Image;
+pub use fountain::{from_fountain_images, to_fountain_images};
+pub use options::{
+    DecodeOptions, Dimensions, EncodeOptions, FountainOptions, PageSize, PaperOptions,
+};
+pub use paper::{from_pages, to_pages};
+pub use plan::{plan, plan_for_dimensions, plan_for_size, Plan};
+pub use probe::{probe, Info};
+pub use progress::{Control, Progress};
+pub use recipient::{PublicKey, SecretKey};
+pub use region::Region;
+pub use serialize::{from_image_deserialized, to_image_serialized, TypeTag};
+pub use shamir::{from_shares, from_shares_with, to_shares};
+pub use signature::{SigningKey, VerifyingKey};
+pub use traits::PixelFormat;
+use traits::{Image, ImageMut};
 
 use crate::cursor::ImageCursor;
 
@@ -32,15 +66,25 @@ pub fn image_dimensions<I>(data: impl AsRef<[u8]>, aspect_ratio: f64) -> (u32, u
 where
     I: Image,
 {
-    let data = data.as_ref();
+    let plan = plan_for_size(I::PIXEL_FORMAT, data.as_ref().len() as u64, aspect_ratio);
+    (plan.width, plan.height)
+}
 
-    if data.is_empty() {
-        (0, 0)
-    } else {
-        let total_bytes = (file::Header::SIZE as u64) + (data.len() as u64);
-        let pixel_num = total_bytes / u64::from(I::PIXEL_SIZE);
+/// Get the minimum dimensions for a bilevel image, with one bit per pixel,
+/// that fits `data`.
+///
+/// # Panics
+///
+/// If `aspect_ratio` is not greater than (`>`) 0.
+#[must_use = "unused image dimensions"]
+pub fn bilevel_dimensions(data: impl AsRef<[u8]>, aspect_ratio: f64) -> (u32, u32) {
+    let options = EncodeOptions::new()
+        .dimensions(Dimensions::Ratio(aspect_ratio))
+        .bilevel(true);
 
-        min_dimensions_from_pixels(pixel_num, aspect_ratio)
+    match plan(PixelFormat::Rgb8, data.as_ref().len() as u64, &options) {
+        Ok(x) => (x.width, x.height),
+        Err(e) => panic!("{e}"),
     }
 }
 
@@ -61,42 +105,693 @@ where
 /// See [`image_dimensions`]
 pub fn to_image<I>(data: impl AsRef<[u8]>, aspect_ratio: f64) -> I
 where
-    I: Image,
+    I: ImageMut,
+{
+    let options = EncodeOptions::new().dimensions(Dimensions::Ratio(aspect_ratio));
+
+    match to_image_tagged(data.as_ref(), 0, &options, &mut progress::Ignore) {
+        Ok(x) => x,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Write `data` to an image with dimensions chosen according to `options`
+/// and return it.
+///
+/// # Errors
+///
+/// See [`plan()`]
+pub fn to_image_with<I>(data: impl AsRef<[u8]>, options: &EncodeOptions) -> Result<I>
+where
+    I: ImageMut,
+{
+    to_image_tagged(data.as_ref(), 0, options, &mut progress::Ignore)
+}
+
+/// Like [`to_image_with()`], but report the number of bytes written
+/// to `progress`, which may cancel encoding.
+///
+/// # Errors
+///
+/// - See [`plan()`]
+/// - `progress` returned [`Control::Cancel`]
+pub fn to_image_with_progress<I, P>(
+    data: impl AsRef<[u8]>,
+    options: &EncodeOptions,
+    mut progress: P,
+) -> Result<I>
+where
+    I: ImageMut,
+    P: Progress,
+{
+    to_image_tagged(data.as_ref(), 0, options, &mut progress)
+}
+
+/// Write `data` along with a header carrying `tag` to a new image.
+fn to_image_tagged<I>(
+    data: &[u8],
+    tag: u64,
+    options: &EncodeOptions,
+    progress: &mut dyn Progress,
+) -> Result<I>
+where
+    I: ImageMut,
+{
+    let header = file::Header {
+        tag,
+        ..Default::default()
+    };
+    to_image_headed(data, header, options, progress)
+}
+
+/// Write `data` to a new image along with `header`, whose size and
+/// number of label rows are filled in according to `options`.
+///
+/// If `options` has recipients, `data` is encrypted for them first and
+/// the header carries the wrapped keys. If it has a signing key, the
+/// header carries a signature of itself and the digest of `data`.
+fn to_image_headed<I>(
+    data: &[u8],
+    header: file::Header,
+    options: &EncodeOptions,
+    progress: &mut dyn Progress,
+) -> Result<I>
+where
+    I: ImageMut,
+{
+    if !options.recipients.is_empty() && header.recipients.is_empty() {
+        let (recipients, data) = recipient::encrypt(data, &header, &options.recipients)?;
+        let header = file::Header {
+            recipients,
+            ..header
+        };
+        return to_image_headed(&data, header, options, progress);
+    }
+
+    // The signature is only made once the rest of the header is known.
+    let header = file::Header {
+        signature: options
+            .signing_key
+            .as_ref()
+            .map(|_| [0; file::Header::SIGNATURE_SIZE]),
+        ..header
+    };
+
+    let size = data.len() as u64 + header_overhead(&header);
+    let plan = plan(I::PIXEL_FORMAT, size, options)?;
+
+    if let Some(size) = plan.block_size {
+        let options = plan::unblocked(options, size)?;
+        let image: I = to_image_headed(data, header, &options, progress)?;
+
+        return Ok(if plan.markers {
+            frame::add(&image, size)
+        } else {
+            block::scale_up(&image, size)
+        });
+    }
+
+    let mut header = file::Header {
+        size: data.len() as u64,
+        label_rows: plan.label_rows,
+        ..header
+    };
+    if let Some(key) = &options.signing_key {
+        header.signature = Some(signature::sign(&header, data, key));
+    }
+
+    if plan.bilevel {
+        let mut packed = ImageCursor::new(bilevel::packed_image(plan.width, plan.height)?);
+        write_payload(&mut packed, &header, data, progress)?;
+
+        return Ok(bilevel::expand(
+            &packed.into_image(),
+            plan.width,
+            plan.height,
+        ));
+    }
+
+    let mut image = ImageCursor::new(I::new_with_dimensions(plan.width, plan.height));
+    if let Some(channels) = options.channels {
+        image = image.with_channels(channels)?;
+    }
+
+    write_payload(&mut image, &header, data, progress)?;
+
+    let pixel_size = u64::from(image.pixel_size());
+    let mut image = image.into_image();
+
+    if let Some(text) = &options.label {
+        let header_pixels = (header.encoded_size() as u64).div_ceil(pixel_size);
+        label::render(&mut image, text, header_pixels);
+    }
+
+    Ok(image)
+}
+
+/// Write `data` spread across `frames` images and return them, for
+/// containers holding several frames like animated images.
+///
+/// All of the frames have the dimensions chosen according to `options`
+/// for the largest part of `data`. The header of each frame records its
+/// index and the number of frames, so that [`from_frames()`] can read
+/// them back in any order. A single frame is the image written by
+/// [`to_image_with()`].
+///
+/// # Errors
+///
+/// - `frames` is zero
+/// - See [`plan()`]
+#[allow(clippy::cast_possible_truncation)]
+pub fn to_frames<I>(data: impl AsRef<[u8]>, frames: u32, options: &EncodeOptions) -> Result<Vec<I>>
+where
+    I: ImageMut,
 {
     let data = data.as_ref();
+    if frames == 0 {
+        return Err(Error::InvalidArgument);
+    }
+
+    // Parts differ in size by at most one byte.
+    let len = data.len() as u64;
+    let bounds = |i: u32| {
+        let start = len * u64::from(i) / u64::from(frames);
+        let end = len * (u64::from(i) + 1) / u64::from(frames);
+        (start as usize, end as usize)
+    };
 
-    let (image_x, image_y) = image_dimensions::<I>(data, aspect_ratio);
+    let header = |frame: u32| file::Header {
+        frame,
+        frames,
+        ..Default::default()
+    };
+
+    let largest = len.div_ceil(u64::from(frames)) + payload_overhead(&header(0), options);
+    let plan = plan(I::PIXEL_FORMAT, largest, options)?;
+    let options = options
+        .clone()
+        .dimensions(Dimensions::Exact(plan.width, plan.height));
 
-    let mut image = ImageCursor::new(I::new_with_dimensions(image_x, image_y));
+    (0..frames)
+        .map(|i| {
+            let (start, end) = bounds(i);
+            to_image_headed(
+                &data[start..end],
+                header(i),
+                &options,
+                &mut progress::Ignore,
+            )
+        })
+        .collect()
+}
 
+/// Get the number of bytes `header` and the encryption and signature
+/// requested by `options` add to a payload beyond a version 1 header,
+/// like [`header_overhead()`].
+fn payload_overhead(header: &file::Header, options: &EncodeOptions) -> u64 {
     let header = file::Header {
+        recipients: vec![[0; file::Header::RECIPIENT_SIZE]; options.recipients.len()],
+        signature: options
+            .signing_key
+            .as_ref()
+            .map(|_| [0; file::Header::SIGNATURE_SIZE]),
+        ..header.clone()
+    };
+
+    let tag = match options.recipients.len() {
+        0 => 0,
+        _ => recipient::TAG_SIZE as u64,
+    };
+    header_overhead(&header) + tag
+}
+
+/// Get the number of bytes `header` takes up beyond a version 1 header,
+/// besides the room for label rows counted by [`plan()`], which is
+/// planned for as if it were part of the payload.
+fn header_overhead(header: &file::Header) -> u64 {
+    let header = file::Header {
+        label_rows: 0,
+        ..header.clone()
+    };
+    (header.encoded_size() - file::Header::SIZE) as u64
+}
+
+/// Write `data` to `region` of an existing `image`, leaving the pixels
+/// outside of it intact, and return the image.
+///
+/// # Errors
+///
+/// See [`to_image_region_with()`]
+pub fn to_image_region<I>(image: I, region: Region, data: impl AsRef<[u8]>) -> Result<I>
+where
+    I: ImageMut,
+{
+    to_image_region_with(image, region, data, &EncodeOptions::new())
+}
+
+/// Write `data` to `region` of an existing `image` and return the image.
+///
+/// Only the channels in `options` are written to, and the data is
+/// signed with the signing key in `options` if it has one. The
+/// dimensions in `options` are ignored.
+///
+/// # Errors
+///
+/// - `region` is empty or does not fit in `image`
+/// - The pixels of `image` do not have the channels in `options`
+/// - `options` has a label, a block size, markers, bilevel pixels or
+///   recipients, none of which can be written to a region
+/// - `data` does not fit in `region`
+pub fn to_image_region_with<I>(
+    image: I,
+    region: Region,
+    data: impl AsRef<[u8]>,
+    options: &EncodeOptions,
+) -> Result<I>
+where
+    I: ImageMut,
+{
+    if options.label.is_some()
+        || options.block_size.is_some()
+        || options.markers
+        || options.bilevel
+        || !options.recipients.is_empty()
+    {
+        return Err(Error::UnsupportedFormat);
+    }
+
+    let data = data.as_ref();
+    let mut image = ImageCursor::with_region(image, region)?;
+    if let Some(channels) = options.channels {
+        image = image.with_channels(channels)?;
+    }
+
+    let mut header = file::Header {
         size: data.len() as u64,
+        signature: options
+            .signing_key
+            .as_ref()
+            .map(|_| [0; file::Header::SIGNATURE_SIZE]),
+        ..Default::default()
     };
 
-    header.write_to(&mut image).expect("write to image failed");
-    image.write_all(data).expect("write to image failed");
+    let size = (header.encoded_size() as u64).saturating_add(data.len() as u64);
+    if size > image.capacity() {
+        return Err(Error::InsufficientCapacity);
+    }
+
+    if let Some(key) = &options.signing_key {
+        header.signature = Some(signature::sign(&header, data, key));
+    }
+
+    write_payload(&mut image, &header, data, &mut progress::Ignore)?;
+
+    Ok(image.into_image())
+}
+
+/// Write `header` at the start of `image`, followed by `data` either
+/// right after it or below the label strip the header describes.
+fn write_payload<I>(
+    image: &mut ImageCursor<I>,
+    header: &file::Header,
+    data: &[u8],
+    progress: &mut dyn Progress,
+) -> Result<()>
+where
+    I: ImageMut,
+{
+    header.write_to(&mut *image)?;
+    image.seek(SeekFrom::Start(payload_start(image, header)))?;
+    progress::write_all(image, data, progress)
+}
 
-    image.into_image()
+/// Get the position in `image` of the payload following `header`.
+fn payload_start<I>(image: &ImageCursor<I>, header: &file::Header) -> u64
+where
+    I: Image,
+{
+    match header.label_rows {
+        0 => header.encoded_size() as u64,
+        rows => u64::from(rows) * image.row_size(),
+    }
 }
 
 /// Read an image of type `I` and return the contained data in it.
 ///
 /// # Errors
 ///
+/// - The image does not contain a valid header
 /// - Image data size is too large
 pub fn from_image<I>(image: I) -> Result<Vec<u8>>
 where
     I: Image,
 {
-    let mut image = ImageCursor::new(image);
+    from_image_tagged(image, &mut progress::Ignore).map(|(_, data)| data)
+}
+
+/// Like [`from_image()`], but report the number of bytes read
+/// to `progress`, which may cancel decoding.
+///
+/// # Errors
+///
+/// - See [`from_image()`]
+/// - `progress` returned [`Control::Cancel`]
+pub fn from_image_with_progress<I, P>(image: I, mut progress: P) -> Result<Vec<u8>>
+where
+    I: Image,
+    P: Progress,
+{
+    from_image_tagged(image, &mut progress).map(|(_, data)| data)
+}
+
+/// Read an image of type `I` and return the tag from its header
+/// along with the contained data.
+fn from_image_tagged<I>(image: I, progress: &mut dyn Progress) -> Result<(u64, Vec<u8>)>
+where
+    I: Image,
+{
+    let (header, data) = read_frame(image, &DecodeOptions::new(), progress)?;
+
+    check_whole_payload(&header)?;
+    Ok((header.tag, data))
+}
+
+/// Check that `header` describes a whole payload rather than the part
+/// of it held by one of several frames, or a share of a secret.
+fn check_whole_payload(header: &file::Header) -> Result<()> {
+    if header.frames > 1 || header.threshold != 0 {
+        return Err(Error::MissingData);
+    }
+
+    Ok(())
+}
+
+/// Read a header and the data following it from the region and channels
+/// of `image` given by `options`, which may be one of several frames.
+///
+/// The signature of signed images is checked, against the verifying key
+/// in `options` if it has one, and data encrypted for recipients is
+/// decrypted with the identity in `options`.
+fn read_frame<I>(
+    image: I,
+    options: &DecodeOptions,
+    progress: &mut dyn Progress,
+) -> Result<(file::Header, Vec<u8>)>
+where
+    I: Image,
+{
+    let (header, data) = read_stored(image, options, progress)?;
+    signature::check(&header, &data, options.verifying_key.as_ref())?;
+
+    if header.recipients.is_empty() {
+        return Ok((header, data));
+    }
+
+    let data = recipient::decrypt(&data, &header, options.identity.as_ref())?;
+    Ok((header, data))
+}
+
+/// Read a header and the data following it as it is stored in `image`,
+/// before checking its signature or decrypting it.
+fn read_stored<I>(
+    image: I,
+    options: &DecodeOptions,
+    progress: &mut dyn Progress,
+) -> Result<(file::Header, Vec<u8>)>
+where
+    I: Image,
+{
+    // Images with blocks and bilevel images are always written whole.
+    if options.region.is_some() {
+        return read_payload_with(image, options, progress);
+    }
+
+    match find_blocks(&image) {
+        Some(grid) => read_whole(block::Blocks::new(image, grid), options, progress),
+        None => read_whole(image, options, progress),
+    }
+}
+
+/// Read a header and the data following it from `image`, which has one
+/// pixel per block and was written whole, either with bytes in the
+/// channels of its pixels given by `options` or as a bilevel image.
+fn read_whole<I>(
+    image: I,
+    options: &DecodeOptions,
+    progress: &mut dyn Progress,
+) -> Result<(file::Header, Vec<u8>)>
+where
+    I: Image,
+{
+    match packed_bilevel(&image) {
+        Some(packed) => read_payload(&mut ImageCursor::new(packed), progress),
+        None => read_payload_with(image, options, progress),
+    }
+}
 
-    let header = file::Header::read_from(&mut image)?;
+/// Get the bytes held by `image` if it is a bilevel image.
+fn packed_bilevel<I>(image: &I) -> Option<image::RgbImage>
+where
+    I: Image,
+{
+    // Bilevel images start with black and white pixels instead of the header.
+    if file::Header::read_from(ImageCursor::new(image)).is_ok_and(|header| !header.legacy) {
+        return None;
+    }
+
+    bilevel::pack(image)
+}
+
+/// Find the pixels to sample from each block of `image`, if it was
+/// written with blocks.
+fn find_blocks<I>(image: &I) -> Option<block::Grid>
+where
+    I: Image,
+{
+    // Images with blocks start with a white pixel instead of the header.
+    if file::Header::read_from(ImageCursor::new(image)).is_ok_and(|header| !header.legacy) {
+        return None;
+    }
+
+    match block::detect(image) {
+        Some(size) => Some(block::Grid::uniform(image.width(), image.height(), size)),
+        None => frame::find(image),
+    }
+}
+
+/// Read the data written by [`to_image_region()`] to `region` of `image`.
+///
+/// # Errors
+///
+/// See [`from_image_with()`]
+pub fn from_image_region<I>(image: I, region: Region) -> Result<Vec<u8>>
+where
+    I: Image,
+{
+    from_image_with(image, &DecodeOptions::new().region(region))
+}
+
+/// Read the data in the region and channels of `image` given by `options`.
+///
+/// # Errors
+///
+/// - The region in `options` is empty or does not fit in `image`
+/// - The pixels of `image` do not have the channels in `options`
+/// - See [`from_image()`]
+pub fn from_image_with<I>(image: I, options: &DecodeOptions) -> Result<Vec<u8>>
+where
+    I: Image,
+{
+    let (header, data) = read_frame(image, options, &mut progress::Ignore)?;
+
+    check_whole_payload(&header)?;
+    Ok(data)
+}
+
+/// Read the data in `image` and check that it was signed by `trusted`.
+///
+/// # Errors
+///
+/// - The image is not signed
+/// - The image was altered after it was signed
+/// - The image was signed by another key than `trusted`
+/// - See [`from_image()`]
+pub fn from_image_verified<I>(image: I, trusted: &VerifyingKey) -> Result<Vec<u8>>
+where
+    I: Image,
+{
+    from_image_with(image, &DecodeOptions::new().verifying_key(*trusted))
+}
+
+/// Check the signature of the region and channels of `image` given by
+/// `options`, against the verifying key in `options` if it has one, and
+/// return the key which signed it.
+///
+/// Unlike decoding, this neither decrypts the payload nor needs it to be
+/// whole, so that each frame or share can be checked on its own.
+///
+/// # Errors
+///
+/// - The image is not signed
+/// - The image was altered after it was signed
+/// - The image was signed by another key than the one in `options`
+/// - See [`from_image_with()`]
+pub fn verify_signature_with<I>(image: I, options: &DecodeOptions) -> Result<VerifyingKey>
+where
+    I: Image,
+{
+    let (header, data) = read_stored(image, options, &mut progress::Ignore)?;
+    let signer = signature::signer(&header).ok_or(Error::Unsigned)?;
+
+    signature::check(&header, &data, options.verifying_key.as_ref())?;
+    Ok(signer)
+}
+
+/// Read the data spread by [`to_frames()`] across `frames`, which may be
+/// in any order.
+///
+/// # Errors
+///
+/// See [`from_frames_with()`]
+pub fn from_frames<I, F>(frames: F) -> Result<Vec<u8>>
+where
+    I: Image,
+    F: IntoIterator<Item = I>,
+{
+    from_frames_with(frames, &DecodeOptions::new())
+}
+
+/// Read the data spread across `frames` from the region and channels
+/// of each frame given by `options`.
+///
+/// # Errors
+///
+/// - Some of the frames do not contain a valid header, or hold parts
+///   of different payloads
+/// - Some of the parts of the payload are missing from `frames`
+/// - See [`from_image_with()`]
+pub fn from_frames_with<I, F>(frames: F, options: &DecodeOptions) -> Result<Vec<u8>>
+where
+    I: Image,
+    F: IntoIterator<Item = I>,
+{
+    let mut first: Option<file::Header> = None;
+    let mut parts = std::collections::BTreeMap::new();
+
+    for image in frames {
+        let (header, data) = read_frame(image, options, &mut progress::Ignore)?;
+
+        let first = first.get_or_insert_with(|| header.clone());
+        if (header.frames, header.tag) != (first.frames, first.tag) {
+            return Err(Error::InvalidHeader);
+        }
+
+        parts.insert(header.frame, data);
+    }
+
+    // Headers only hold indices below the number of frames, so all of
+    // the parts are there if there are as many as frames.
+    match first {
+        Some(first) if parts.len() as u64 == u64::from(first.frames) => {
+            Ok(parts.into_values().flatten().collect())
+        }
+        _ => Err(Error::MissingData),
+    }
+}
+
+/// Read a header and the data following it from the region and channels
+/// of `image` given by `options`.
+fn read_payload_with<I>(
+    image: I,
+    options: &DecodeOptions,
+    progress: &mut dyn Progress,
+) -> Result<(file::Header, Vec<u8>)>
+where
+    I: Image,
+{
+    let mut image = match options.region {
+        Some(region) => ImageCursor::with_region(image, region)?,
+        None => ImageCursor::new(image),
+    };
+
+    if let Some(channels) = options.channels {
+        image = image.with_channels(channels)?;
+    }
+
+    read_payload(&mut image, progress)
+}
+
+/// Read a header and the data following it from the start of `image`.
+fn read_payload<I>(
+    image: &mut ImageCursor<I>,
+    progress: &mut dyn Progress,
+) -> Result<(file::Header, Vec<u8>)>
+where
+    I: Image,
+{
+    let header = file::Header::read_from(&mut *image)?;
+    let start = payload_start(image, &header);
+    if header.size > image.capacity().saturating_sub(start) {
+        return Err(Error::InvalidHeader);
+    }
+    image.seek(SeekFrom::Start(start))?;
 
     let size: usize = header.size.try_into().map_err(|_| Error::SizeLimit)?;
     let mut data = vec![0u8; size];
-    image.read_exact(&mut data)?;
+    progress::read_exact(image, &mut data, progress)?;
 
-    Ok(data)
+    Ok((header, data))
+}
+
+/// Get the `x` and `y` coordinates of the pixel of `image` holding
+/// byte `offset` of the payload written by [`to_image()`].
+///
+/// For images with blocks, this is the pixel sampled from the block.
+/// For bilevel images, this is the pixel holding the highest bit of the byte.
+///
+/// Returns `None` if `offset` is outside the image.
+#[must_use]
+pub fn payload_byte_position<I>(image: &I, offset: u64) -> Option<(u32, u32)>
+where
+    I: Image,
+{
+    match find_blocks(image) {
+        Some(grid) => {
+            let (columns, rows) = (grid.columns.clone(), grid.rows.clone());
+            let (x, y) = payload_position(&block::Blocks::new(image, grid), offset)?;
+            Some((columns[x as usize], rows[y as usize]))
+        }
+        None => payload_position(image, offset),
+    }
+}
+
+/// Get the `x` and `y` coordinates of the pixel of `image`, which has
+/// one pixel per block, holding byte `offset` of the payload.
+#[allow(clippy::cast_possible_truncation)]
+fn payload_position<I>(image: &I, offset: u64) -> Option<(u32, u32)>
+where
+    I: Image,
+{
+    if let Some(packed) = packed_bilevel(image) {
+        let mut cursor = ImageCursor::new(&packed);
+        let header = file::Header::read_from(&mut cursor).ok()?;
+
+        // Each byte is held by eight pixels, starting with its highest bit.
+        let pos = payload_start(&cursor, &header).checked_add(offset)?;
+        let bit = pos.checked_mul(8)?;
+        let width = u64::from(image.width());
+        let (x, y) = (bit % width, bit / width);
+
+        return (y < u64::from(image.height())).then_some((x as u32, y as u32));
+    }
+
+    let mut cursor = ImageCursor::new(image);
+    let header = file::Header::read_from(&mut cursor).unwrap_or_default();
+
+    let pos = payload_start(&cursor, &header).checked_add(offset)?;
+    let (x, y, _) = cursor.xyrem_from_pos(pos);
+
+    (x < image.width() && y < image.height()).then_some((x, y))
 }
 
 /// Find the minimum dimensions of an image
@@ -219,3 +914,397 @@ fn min_dimensions_from_pixels(pixel_num: u64, aspect_ratio: f64) -> (u32, u32) {
 
     (x, y)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_byte_position() {
+        let image: image::RgbImage = to_image([0u8; 1000], 1.0);
+        let width = u64::from(image.width());
+
+        // The header takes up the first 7 pixels.
+        assert_eq!(payload_byte_position(&image, 0), Some((7, 0)));
+        assert_eq!(payload_byte_position(&image, 2), Some((7, 0)));
+        assert_eq!(payload_byte_position(&image, 3), Some((8, 0)));
+
+        let last_row_start = 3 * width - file::Header::SIZE as u64;
+        assert_eq!(payload_byte_position(&image, last_row_start), Some((0, 1)));
+
+        let capacity = image_capacity::<image::RgbImage>(image.width(), image.height());
+        assert_eq!(
+            payload_byte_position(&image, capacity - file::Header::SIZE as u64),
+            None
+        );
+    }
+
+    #[test]
+    fn test_legacy_image() {
+        // Written before headers started with a magic number.
+        let image = image::load_from_memory(include_bytes!("fixtures/legacy.png"))
+            .unwrap()
+            .into_rgb8();
+
+        assert_eq!(
+            from_image(&image).unwrap(),
+            b"Hello from an image written before headers had a magic number.\n"
+        );
+        assert_eq!(probe(&image).unwrap().version, 0);
+        assert_eq!(payload_byte_position(&image, 0), Some((2, 0)));
+
+        // Sizes past the end of the image are not a legacy header.
+        let noise = image::RgbImage::from_pixel(5, 5, image::Rgb([0x80, 0x00, 0x00]));
+        assert!(matches!(from_image(&noise), Err(Error::InvalidHeader)));
+        assert!(matches!(probe(&noise), Err(Error::InvalidHeader)));
+    }
+
+    #[test]
+    fn test_channels_round_trip() {
+        let data = (0..200u8).collect::<Vec<_>>();
+        let options = EncodeOptions::new().channels(Channels::BLUE);
+
+        let image: image::RgbaImage = to_image_with(&data, &options).unwrap();
+        assert!(image
+            .pixels()
+            .all(|x| x.0[0] == 0 && x.0[1] == 0 && x.0[3] == 0));
+
+        let decode = DecodeOptions::new().channels(Channels::BLUE);
+        assert_eq!(from_image_with(&image, &decode).unwrap(), data);
+
+        let background = image::RgbaImage::from_pixel(40, 40, image::Rgba([9, 8, 7, 6]));
+        let options = EncodeOptions::new().channels(Channels::ALPHA);
+        let image =
+            to_image_region_with(background, Region::full(40, 40), &data, &options).unwrap();
+        assert!(image.pixels().all(|x| x.0[..3] == [9, 8, 7]));
+
+        let decode = DecodeOptions::new().channels(Channels::ALPHA);
+        assert_eq!(from_image_with(&image, &decode).unwrap(), data);
+    }
+
+    #[test]
+    fn test_region_round_trip() {
+        let background = image::RgbImage::from_pixel(20, 10, image::Rgb([1, 2, 3]));
+        let region = Region::new(12, 2, 8, 5);
+        let data = (0..90u8).collect::<Vec<_>>();
+
+        let image = to_image_region(background.clone(), region, &data).unwrap();
+        assert_eq!(from_image_region(&image, region).unwrap(), data);
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if !region.contains(x, y) {
+                assert_eq!(pixel, background.get_pixel(x, y));
+            }
+        }
+
+        assert!(matches!(
+            to_image_region(background.clone(), region, [0u8; 100]),
+            Err(Error::InsufficientCapacity)
+        ));
+        assert!(matches!(
+            to_image_region(background.clone(), Region::new(15, 0, 8, 5), &data),
+            Err(Error::InvalidDimensions)
+        ));
+
+        let recipient = SecretKey::generate().unwrap().public_key();
+        for options in [
+            EncodeOptions::new().label("region"),
+            EncodeOptions::new().block_size(2),
+            EncodeOptions::new().markers(true),
+            EncodeOptions::new().bilevel(true),
+            EncodeOptions::new().recipients([recipient]),
+        ] {
+            assert!(matches!(
+                to_image_region_with(background.clone(), region, &data, &options),
+                Err(Error::UnsupportedFormat)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_label_round_trip() {
+        let data = (0..=255u8).collect::<Vec<_>>();
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Width(60))
+            .label("data.bin  256 B");
+
+        let image: image::RgbImage = to_image_with(&data, &options).unwrap();
+        assert_eq!(image.height(), label::ROWS + 2);
+        assert_eq!(from_image(&image).unwrap(), data);
+
+        let info = probe(&image).unwrap();
+        assert_eq!((info.version, info.label_rows), (2, label::ROWS));
+        assert_eq!(info.capacity, 60 * 2 * 3);
+
+        assert_eq!(payload_byte_position(&image, 0), Some((0, label::ROWS)));
+    }
+
+    #[test]
+    fn test_blocks_round_trip() {
+        let data = (0..200u8).collect::<Vec<_>>();
+        let options = EncodeOptions::new().block_size(3).label("blocks");
+
+        let image: image::RgbaImage = to_image_with(&data, &options).unwrap();
+        assert_eq!(from_image(&image).unwrap(), data);
+
+        let (width, height) = (image.width() * 2, image.height() * 2);
+        let upscaled = image::imageops::resize(&image, width, height, image::imageops::Nearest);
+        assert_eq!(from_image(&upscaled).unwrap(), data);
+        assert_eq!(
+            from_image_with(&upscaled, &DecodeOptions::new()).unwrap(),
+            data
+        );
+
+        let info = probe(&upscaled).unwrap();
+        assert_eq!(info.block_size, Some(6));
+        assert_eq!(info.payload_size, 200);
+
+        assert_eq!(
+            payload_byte_position(&upscaled, 0),
+            Some((3, 6 * (label::ROWS + 1) + 3))
+        );
+    }
+
+    #[test]
+    fn test_markers_round_trip() {
+        let data = (0..200u8).collect::<Vec<_>>();
+        let options = EncodeOptions::new().markers(true).block_size(2);
+
+        let image: image::Rgb32FImage = to_image_with(&data, &options).unwrap();
+        let (width, height) = image.dimensions();
+
+        let mut bordered =
+            image::Rgb32FImage::from_pixel(width + 9, height + 4, image::Rgb([0.5; 3]));
+        image::imageops::replace(&mut bordered, &image, 5, 1);
+        assert_eq!(from_image(&bordered).unwrap(), data);
+
+        let info = probe(&bordered).unwrap();
+        assert_eq!((info.block_size, info.markers), (Some(2), true));
+
+        let data = (0..3000u32)
+            .map(|x| (x * 7919 % 251) as u8)
+            .collect::<Vec<_>>();
+        let image: image::RgbImage =
+            to_image_with(&data, &EncodeOptions::new().markers(true)).unwrap();
+        let (width, height) = (image.width() * 5 / 2, image.height() * 5 / 2);
+        let scaled = image::imageops::resize(&image, width, height, image::imageops::Nearest);
+        assert_eq!(from_image(&scaled).unwrap(), data);
+    }
+
+    #[test]
+    fn test_bilevel_round_trip() {
+        let data = (0..200u8).collect::<Vec<_>>();
+        let options = EncodeOptions::new().bilevel(true);
+
+        let image: image::RgbImage = to_image_with(&data, &options).unwrap();
+        assert_eq!(
+            image.dimensions(),
+            bilevel_dimensions(&data, 1.0),
+            "bilevel images are sized for one bit per pixel"
+        );
+        assert!(image.pixels().all(|x| x.0 == [0; 3] || x.0 == [255; 3]));
+        assert_eq!(from_image(&image).unwrap(), data);
+        assert_eq!(
+            from_image_with(&image, &DecodeOptions::new()).unwrap(),
+            data
+        );
+
+        // Grey levels are read as whichever of black and white they are closer to.
+        let faded = image::RgbImage::from_fn(image.width(), image.height(), |x, y| {
+            image::Rgb(image.get_pixel(x, y).0.map(|c| c / 2 + 60))
+        });
+        assert_eq!(from_image(&faded).unwrap(), data);
+
+        let info = probe(&image).unwrap();
+        assert!(info.bilevel);
+        assert_eq!(
+            info.capacity,
+            u64::from(image.width() * image.height() / 8) - file::Header::SIZE as u64
+        );
+
+        let width = image.width();
+        let first = 8 * u32::try_from(file::Header::SIZE).unwrap();
+        assert_eq!(
+            payload_byte_position(&image, 0),
+            Some((first % width, first / width))
+        );
+
+        let options = options.block_size(3).markers(true);
+        let image: image::Rgba32FImage = to_image_with(&data, &options).unwrap();
+        assert_eq!(from_image(&image).unwrap(), data);
+        assert!(probe(&image).unwrap().bilevel);
+
+        assert!(matches!(
+            to_image_with::<image::RgbImage>(&data, &EncodeOptions::new().bilevel(true).label("x")),
+            Err(Error::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let data = (0..=255u8).cycle().take(1000).collect::<Vec<_>>();
+        let options = EncodeOptions::new();
+
+        let frames: Vec<image::RgbImage> = to_frames(&data, 3, &options).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(frames
+            .iter()
+            .all(|x| x.dimensions() == frames[0].dimensions()));
+        assert_eq!(from_frames(&frames).unwrap(), data);
+        assert_eq!(from_frames(frames.iter().rev()).unwrap(), data);
+
+        let info = probe(&frames[1]).unwrap();
+        assert_eq!((info.frame, info.frames), (1, 3));
+        assert_eq!(info.version, 3);
+
+        // A single frame does not hold the whole payload.
+        assert!(matches!(from_image(&frames[0]), Err(Error::MissingData)));
+        assert!(matches!(from_frames(&frames[..2]), Err(Error::MissingData)));
+
+        // Frames of different payloads are not mixed up.
+        let other: Vec<image::RgbImage> = to_frames(&data, 2, &options).unwrap();
+        assert!(matches!(
+            from_frames([&frames[0], &other[1]]),
+            Err(Error::InvalidHeader)
+        ));
+
+        // A single frame is a plain image.
+        let single: Vec<image::RgbImage> = to_frames(&data, 1, &options).unwrap();
+        assert_eq!(
+            single,
+            [to_image_with::<image::RgbImage>(&data, &options).unwrap()]
+        );
+        assert_eq!(from_image(&single[0]).unwrap(), data);
+
+        assert!(matches!(
+            to_frames::<image::RgbImage>(&data, 0, &options),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_frames_with_options() {
+        let data = (0..=255u8).cycle().take(700).collect::<Vec<_>>();
+
+        for options in [
+            EncodeOptions::new().label("frames"),
+            EncodeOptions::new().block_size(2).markers(true),
+            EncodeOptions::new().bilevel(true).block_size(3),
+            EncodeOptions::new().dimensions(Dimensions::Width(40)),
+        ] {
+            let frames: Vec<image::RgbaImage> = to_frames(&data, 4, &options).unwrap();
+            assert!(frames
+                .iter()
+                .all(|x| x.dimensions() == frames[0].dimensions()));
+            assert_eq!(from_frames(frames).unwrap(), data, "{options:?}");
+        }
+
+        let options = EncodeOptions::new().channels(Channels::RED);
+        let frames: Vec<image::RgbImage> = to_frames(&data, 2, &options).unwrap();
+        let decode_options = DecodeOptions::new().channels(Channels::RED);
+        assert_eq!(from_frames_with(&frames, &decode_options).unwrap(), data);
+    }
+
+    #[test]
+    fn test_recipients_round_trip() {
+        let data = (0..=255u8).cycle().take(700).collect::<Vec<_>>();
+        let alice = SecretKey::generate().unwrap();
+        let eve = SecretKey::generate().unwrap();
+
+        for options in [
+            EncodeOptions::new(),
+            EncodeOptions::new().block_size(2).label("secret"),
+            EncodeOptions::new().bilevel(true),
+        ] {
+            let options = options.recipients([alice.public_key()]);
+            let image: image::RgbImage = to_image_with(&data, &options).unwrap();
+
+            let info = probe(&image).unwrap();
+            assert_eq!((info.recipients, info.version), (1, 5));
+
+            let decode_options = DecodeOptions::new().identity(alice.clone());
+            assert_eq!(from_image_with(&image, &decode_options).unwrap(), data);
+
+            assert!(matches!(from_image(&image), Err(Error::Encrypted)));
+            assert!(matches!(
+                from_image_with(&image, &DecodeOptions::new().identity(eve.clone())),
+                Err(Error::WrongIdentity)
+            ));
+        }
+
+        let options = EncodeOptions::new().recipients([eve.public_key(), alice.public_key()]);
+        let frames: Vec<image::RgbImage> = to_frames(&data, 3, &options).unwrap();
+        let decode_options = DecodeOptions::new().identity(alice);
+        assert_eq!(from_frames_with(&frames, &decode_options).unwrap(), data);
+    }
+
+    #[test]
+    fn test_signed_round_trip() {
+        let data = (0..=255u8).cycle().take(700).collect::<Vec<_>>();
+        let signing = SigningKey::generate().unwrap();
+        let trusted = signing.verifying_key();
+        let other = SigningKey::generate().unwrap().verifying_key();
+        let identity = SecretKey::generate().unwrap();
+
+        for options in [
+            EncodeOptions::new(),
+            EncodeOptions::new().block_size(2).label("signed"),
+            EncodeOptions::new().bilevel(true),
+            EncodeOptions::new().recipients([identity.public_key()]),
+        ] {
+            let options = options.signing_key(signing.clone());
+            let image: image::RgbImage = to_image_with(&data, &options).unwrap();
+
+            let info = probe(&image).unwrap();
+            assert_eq!((info.signer, info.version), (Some(trusted), 6));
+            assert_eq!(
+                verify_signature_with(&image, &DecodeOptions::new().verifying_key(trusted))
+                    .unwrap(),
+                trusted
+            );
+
+            let decode_options = DecodeOptions::new()
+                .identity(identity.clone())
+                .verifying_key(trusted);
+            assert_eq!(from_image_with(&image, &decode_options).unwrap(), data);
+            assert!(matches!(
+                from_image_verified(&image, &other),
+                Err(Error::UntrustedSigner)
+            ));
+        }
+
+        let options = EncodeOptions::new().signing_key(signing);
+        let mut image: image::RgbImage = to_image_with(&data, &options).unwrap();
+        assert_eq!(from_image_verified(&image, &trusted).unwrap(), data);
+
+        // Any change to the payload breaks the signature.
+        let (x, y) = payload_byte_position(&image, 300).unwrap();
+        image.get_pixel_mut(x, y).0[0] ^= 1;
+        assert!(matches!(from_image(&image), Err(Error::BadSignature)));
+
+        let unsigned: image::RgbImage = to_image(&data, 1.0);
+        assert_eq!(from_image(&unsigned).unwrap(), data);
+        assert!(matches!(
+            from_image_verified(&unsigned, &trusted),
+            Err(Error::Unsigned)
+        ));
+        assert!(matches!(
+            verify_signature_with(&unsigned, &DecodeOptions::new()),
+            Err(Error::Unsigned)
+        ));
+
+        let background = image::RgbImage::from_pixel(40, 40, image::Rgb([9, 9, 9]));
+        let region = Region::new(4, 4, 32, 32);
+        let image = to_image_region_with(background, region, &data, &options).unwrap();
+        let decode_options = DecodeOptions::new().region(region).verifying_key(trusted);
+        assert_eq!(from_image_with(&image, &decode_options).unwrap(), data);
+        assert_eq!(
+            verify_signature_with(&image, &decode_options).unwrap(),
+            trusted
+        );
+
+        let frames: Vec<image::RgbImage> = to_frames(&data, 3, &options).unwrap();
+        let decode_options = DecodeOptions::new().verifying_key(trusted);
+        assert_eq!(from_frames_with(&frames, &decode_options).unwrap(), data);
+    }
+}
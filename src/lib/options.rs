@@ -0,0 +1,282 @@
+use crate::channels::Channels;
+use crate::recipient::{PublicKey, SecretKey};
+use crate::region::Region;
+use crate::signature::{SigningKey, VerifyingKey};
+
+/// How the dimensions of a new image are chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dimensions {
+    /// Dimensions close to the aspect ratio (`width / height`) that
+    /// leave as little capacity unused as possible.
+    Ratio(f64),
+    /// Fixed width and the smallest height that fits the data.
+    Width(u32),
+    /// Fixed height and the smallest width that fits the data.
+    Height(u32),
+    /// Fixed width and height.
+    Exact(u32, u32),
+}
+
+impl Default for Dimensions {
+    fn default() -> Self {
+        Self::Ratio(1.0)
+    }
+}
+
+/// Options controlling how data is written to a new image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EncodeOptions {
+    pub(crate) dimensions: Dimensions,
+    pub(crate) max_dimension: Option<u32>,
+    pub(crate) channels: Option<Channels>,
+    pub(crate) label: Option<String>,
+    pub(crate) block_size: Option<u32>,
+    pub(crate) markers: bool,
+    pub(crate) bilevel: bool,
+    pub(crate) recipients: Vec<PublicKey>,
+    pub(crate) signing_key: Option<SigningKey>,
+}
+
+impl EncodeOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how the dimensions of the image are chosen.
+    #[must_use]
+    pub fn dimensions(mut self, dimensions: Dimensions) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    /// Limit both the width and the height of the image to `max`.
+    #[must_use]
+    pub fn max_dimension(mut self, max: u32) -> Self {
+        self.max_dimension = Some(max);
+        self
+    }
+
+    /// Only write data to `channels` of each pixel, leaving the other
+    /// channels untouched.
+    #[must_use]
+    pub fn channels(mut self, channels: Channels) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Draw `text` into a strip at the top of the image, above the data.
+    ///
+    /// The header records the height of the strip so that it is
+    /// skipped when decoding.
+    #[must_use]
+    pub fn label(mut self, text: impl Into<String>) -> Self {
+        self.label = Some(text.into());
+        self
+    }
+
+    /// Draw each pixel holding data as a `size`x`size` block, below
+    /// a calibration row the decoder finds the size of the blocks from.
+    ///
+    /// This lets the data survive nearest neighbour upscaling of the
+    /// image by any integer factor. The dimensions given to
+    /// [`dimensions()`](Self::dimensions) and
+    /// [`max_dimension()`](Self::max_dimension) are those of the
+    /// upscaled image.
+    #[must_use]
+    pub fn block_size(mut self, size: u32) -> Self {
+        self.block_size = Some(size);
+        self
+    }
+
+    /// Surround the data with a frame of finder patterns in the corners
+    /// and timing patterns along its edges, instead of a calibration row.
+    ///
+    /// This lets the decoder find the data in an image with a border
+    /// added around it or with a few pixels cropped off its edges, and
+    /// sample it after scaling by a fraction. The blocks of the frame are
+    /// the size given to [`block_size()`](Self::block_size), or one pixel.
+    #[must_use]
+    pub fn markers(mut self, enabled: bool) -> Self {
+        self.markers = enabled;
+        self
+    }
+
+    /// Store one bit of data per pixel, as black or white pixels, for
+    /// pipelines which only keep two levels like faxes and printers.
+    ///
+    /// The decoder reads the bits back by taking each pixel as black or
+    /// white, whichever it is closer to. Cannot be combined with
+    /// [`channels()`](Self::channels) or [`label()`](Self::label).
+    #[must_use]
+    pub fn bilevel(mut self, enabled: bool) -> Self {
+        self.bilevel = enabled;
+        self
+    }
+
+    /// Encrypt the data so that only the holders of the secret keys of
+    /// `recipients` can read it, with [`DecodeOptions::identity()`].
+    ///
+    /// The data is encrypted with a new random key, which the header
+    /// holds wrapped for each recipient.
+    #[must_use]
+    pub fn recipients(mut self, recipients: impl IntoIterator<Item = PublicKey>) -> Self {
+        self.recipients = recipients.into_iter().collect();
+        self
+    }
+
+    /// Sign the header and the digest of the data with `key`, so that
+    /// readers can check who wrote the image with
+    /// [`DecodeOptions::verifying_key()`].
+    ///
+    /// The header holds the signature along with the verifying key of
+    /// `key`. The signature covers the data as it is stored, after any
+    /// encryption.
+    #[must_use]
+    pub fn signing_key(mut self, key: SigningKey) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+}
+
+/// Options controlling where data is read from in an image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodeOptions {
+    pub(crate) region: Option<Region>,
+    pub(crate) channels: Option<Channels>,
+    pub(crate) identity: Option<SecretKey>,
+    pub(crate) verifying_key: Option<VerifyingKey>,
+}
+
+impl DecodeOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only read data from `region` of the image.
+    #[must_use]
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Only read data from `channels` of each pixel.
+    #[must_use]
+    pub fn channels(mut self, channels: Channels) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Decrypt data encrypted for recipients with `identity`, the secret
+    /// key of one of them.
+    #[must_use]
+    pub fn identity(mut self, identity: SecretKey) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Only read images signed by `key`, the verifying key of a trusted
+    /// signer.
+    ///
+    /// The signatures of signed images are checked either way, but
+    /// unsigned images are only rejected with a trusted key.
+    #[must_use]
+    pub fn verifying_key(mut self, key: VerifyingKey) -> Self {
+        self.verifying_key = Some(key);
+        self
+    }
+}
+
+/// Size of the pages of a paper backup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PageSize {
+    /// 210x297 mm
+    #[default]
+    A4,
+    /// 8.5x11 in
+    Letter,
+}
+
+impl PageSize {
+    /// Get the width and height of the page in millimetres.
+    #[must_use]
+    pub fn millimetres(self) -> (f32, f32) {
+        match self {
+            Self::A4 => (210.0, 297.0),
+            Self::Letter => (215.9, 279.4),
+        }
+    }
+}
+
+/// Options controlling how data is laid out on the pages of a paper backup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperOptions {
+    pub(crate) dpi: u32,
+    pub(crate) page_size: PageSize,
+}
+
+impl Default for PaperOptions {
+    fn default() -> Self {
+        Self {
+            dpi: 300,
+            page_size: PageSize::default(),
+        }
+    }
+}
+
+impl PaperOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the resolution the pages are rendered at, in dots per inch.
+    ///
+    /// The blocks holding data have the same physical size at any
+    /// resolution, so this does not change how much data fits on a page.
+    #[must_use]
+    pub fn dpi(mut self, dpi: u32) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    /// Set the size of the pages.
+    #[must_use]
+    pub fn page_size(mut self, page_size: PageSize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+}
+
+/// Options controlling how data is coded across the images of a
+/// fountain-coded set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FountainOptions {
+    pub(crate) image_size: u64,
+}
+
+impl Default for FountainOptions {
+    fn default() -> Self {
+        Self {
+            image_size: 64 * 1024,
+        }
+    }
+}
+
+impl FountainOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of bytes of coded data each image holds.
+    ///
+    /// Smaller images split the data into more pieces, which makes the
+    /// set slower to decode.
+    #[must_use]
+    pub fn image_size(mut self, size: u64) -> Self {
+        self.image_size = size;
+        self
+    }
+}
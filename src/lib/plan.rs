@@ -0,0 +1,651 @@
+use serde::Serialize;
+
+use crate::channels::Channels;
+use crate::file::Header;
+use crate::label;
+use crate::options::{Dimensions, EncodeOptions};
+use crate::traits::PixelFormat;
+use crate::{bilevel, frame};
+use crate::{Error, Result};
+
+/// How far, as a fraction of the ideal width, the width of an image
+/// may stray from it to reduce unused capacity.
+const WIDTH_TOLERANCE: f64 = 0.05;
+
+/// Layout of an image holding a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    pub pixel_format: PixelFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Number of bytes the pixels of the image can hold.
+    pub capacity: u64,
+    /// Number of bytes taken up by the header, or by the label strip
+    /// holding it if the image has one.
+    pub header_size: u64,
+    /// Number of rows at the top of the image taken up by a label strip.
+    pub label_rows: u32,
+    /// Side in pixels of the block each pixel holding data is drawn as,
+    /// if the image has blocks. The other fields describe the image
+    /// with one pixel per block.
+    pub block_size: Option<u32>,
+    /// Whether the data is surrounded by a frame of markers.
+    pub markers: bool,
+    /// Whether each pixel holds one bit, as a black or white pixel.
+    pub bilevel: bool,
+    /// Maximum payload size in bytes the image can hold.
+    pub max_payload_size: u64,
+    /// Size of the planned payload in bytes.
+    pub payload_size: u64,
+    /// Number of unused bytes after the payload.
+    pub padding: u64,
+}
+
+impl Plan {
+    fn new(
+        pixel_format: PixelFormat,
+        channels: Channels,
+        width: u32,
+        height: u32,
+        label_rows: u32,
+        payload_size: u64,
+    ) -> Self {
+        let pixel_size = channels.pixel_size(pixel_format);
+        let row_size = u64::from(width) * u64::from(pixel_size);
+        let capacity = row_size * u64::from(height);
+        let header_size = match label_rows {
+            0 => Header::SIZE as u64,
+            rows => row_size * u64::from(rows),
+        };
+        let max_payload_size = capacity.saturating_sub(header_size);
+
+        Self {
+            pixel_format,
+            width,
+            height,
+            capacity,
+            header_size,
+            label_rows,
+            block_size: None,
+            markers: false,
+            bilevel: false,
+            max_payload_size,
+            payload_size,
+            padding: max_payload_size.saturating_sub(payload_size),
+        }
+    }
+}
+
+/// Plan the smallest image with pixels in `pixel_format` that can hold
+/// `payload_size` bytes.
+///
+/// # Panics
+///
+/// If `aspect_ratio` is not greater than (`>`) 0.
+#[must_use]
+pub fn plan_for_size(pixel_format: PixelFormat, payload_size: u64, aspect_ratio: f64) -> Plan {
+    let options = EncodeOptions::new().dimensions(Dimensions::Ratio(aspect_ratio));
+
+    match plan(pixel_format, payload_size, &options) {
+        Ok(x) => x,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Plan an image with pixels in `pixel_format` that can hold
+/// `payload_size` bytes with dimensions chosen according to `options`.
+///
+/// # Errors
+///
+/// - The dimensions in `options` are zero, not positive or exceed the maximum dimension
+/// - The payload does not fit in the dimensions allowed by `options`
+/// - Pixels in `pixel_format` do not have the channels in `options`
+/// - A bilevel image is requested along with channels or a label strip
+pub fn plan(pixel_format: PixelFormat, payload_size: u64, options: &EncodeOptions) -> Result<Plan> {
+    let channels = options
+        .channels
+        .unwrap_or_else(|| Channels::all(pixel_format));
+
+    if !channels.fits(pixel_format) {
+        return Err(Error::InvalidChannels);
+    }
+
+    if options.block_size.is_some() || options.markers {
+        let size = options.block_size.unwrap_or(1);
+        let plan = plan(pixel_format, payload_size, &unblocked(options, size)?)?;
+
+        let (extra_width, extra_height) = blocks_around(options);
+        let max = options.max_dimension.unwrap_or(u32::MAX);
+
+        let (width, height) = (plan.width.checked_add(extra_width))
+            .zip(plan.height.checked_add(extra_height))
+            .and_then(|(x, y)| x.checked_mul(size).zip(y.checked_mul(size)))
+            .filter(|&(x, y)| x <= max && y <= max)
+            .ok_or(Error::InsufficientCapacity)?;
+
+        return Ok(Plan {
+            width,
+            height,
+            block_size: Some(size),
+            markers: options.markers,
+            ..plan
+        });
+    }
+
+    if options.bilevel {
+        return plan_bilevel(pixel_format, payload_size, options);
+    }
+
+    let pixel_size = u64::from(channels.pixel_size(pixel_format));
+
+    if options.label.is_none() {
+        let total_bytes = (Header::SIZE as u64).saturating_add(payload_size);
+        let pixel_num = total_bytes.div_ceil(pixel_size);
+
+        let (width, height) = choose_dimensions(pixel_num, options)?;
+
+        return Ok(Plan::new(
+            pixel_format,
+            channels,
+            width,
+            height,
+            0,
+            payload_size,
+        ));
+    }
+
+    // The header is in the label strip, so the rows below it only
+    // need to fit the payload.
+    let pixel_num = payload_size.div_ceil(pixel_size).max(1);
+    let (width, height) = choose_labelled_dimensions(pixel_num, options)?;
+
+    Ok(Plan::new(
+        pixel_format,
+        channels,
+        width,
+        height,
+        label::ROWS,
+        payload_size,
+    ))
+}
+
+/// Plan a bilevel image holding one bit per pixel.
+fn plan_bilevel(
+    pixel_format: PixelFormat,
+    payload_size: u64,
+    options: &EncodeOptions,
+) -> Result<Plan> {
+    if options.channels.is_some() {
+        return Err(Error::InvalidChannels);
+    }
+
+    if options.label.is_some() {
+        return Err(Error::UnsupportedFormat);
+    }
+
+    let total_bytes = (Header::SIZE as u64).saturating_add(payload_size);
+    let (width, height) = choose_dimensions(total_bytes.saturating_mul(8), options)?;
+
+    let capacity = bilevel::capacity(width, height);
+    let max_payload_size = capacity.saturating_sub(Header::SIZE as u64);
+
+    Ok(Plan {
+        pixel_format,
+        width,
+        height,
+        capacity,
+        header_size: Header::SIZE as u64,
+        label_rows: 0,
+        block_size: None,
+        markers: false,
+        bilevel: true,
+        max_payload_size,
+        payload_size,
+        padding: max_payload_size.saturating_sub(payload_size),
+    })
+}
+
+/// Get the options for the image with one pixel per block of `size`
+/// pixels that is scaled up into the image described by `options`.
+pub(crate) fn unblocked(options: &EncodeOptions, size: u32) -> Result<EncodeOptions> {
+    if size == 0 {
+        return Err(Error::InvalidDimensions);
+    }
+
+    let (extra_width, extra_height) = blocks_around(options);
+    let blocks = |x: u32, extra: u32| match (x / size).checked_sub(extra) {
+        _ if x == 0 => Err(Error::InvalidDimensions),
+        None | Some(0) => Err(Error::InsufficientCapacity),
+        Some(x) => Ok(x),
+    };
+    let width = |x| blocks(x, extra_width);
+    let height = |y| blocks(y, extra_height);
+
+    let dimensions = match options.dimensions {
+        Dimensions::Width(x) => Dimensions::Width(width(x)?),
+        Dimensions::Height(y) => Dimensions::Height(height(y)?),
+        Dimensions::Exact(x, y) => Dimensions::Exact(width(x)?, height(y)?),
+        x @ Dimensions::Ratio(_) => x,
+    };
+
+    Ok(EncodeOptions {
+        dimensions,
+        max_dimension: options.max_dimension.map(|x| (x / size).max(1)),
+        block_size: None,
+        markers: false,
+        ..options.clone()
+    })
+}
+
+/// Get the number of columns and rows of blocks around the data in
+/// an image with blocks: either a frame of markers, or a calibration row.
+fn blocks_around(options: &EncodeOptions) -> (u32, u32) {
+    if options.markers {
+        (2 * frame::BORDER, 2 * frame::BORDER)
+    } else {
+        (0, 1)
+    }
+}
+
+/// Choose the dimensions of an image with a label strip above
+/// at least `pixel_num` pixels.
+///
+/// Images sized by an aspect ratio are widened to fit the whole label.
+fn choose_labelled_dimensions(pixel_num: u64, options: &EncodeOptions) -> Result<(u32, u32)> {
+    let rows = label::ROWS;
+    let max = options.max_dimension.unwrap_or(u32::MAX);
+
+    let below_strip = |y: u32| {
+        if y == 0 || y > max {
+            return Err(Error::InvalidDimensions);
+        }
+
+        y.checked_sub(rows)
+            .filter(|&y| y != 0)
+            .ok_or(Error::InsufficientCapacity)
+    };
+
+    let dimensions = match options.dimensions {
+        Dimensions::Height(y) => Dimensions::Height(below_strip(y)?),
+        Dimensions::Exact(x, y) => Dimensions::Exact(x, below_strip(y)?),
+        x => x,
+    };
+
+    let label_width = options.label.as_deref().map_or(0, label::width).min(max);
+    let mut options = EncodeOptions {
+        dimensions,
+        max_dimension: options.max_dimension,
+        ..Default::default()
+    };
+
+    let (mut width, mut height) = choose_dimensions(pixel_num, &options)?;
+
+    if matches!(dimensions, Dimensions::Ratio(_)) && width < label_width {
+        options.dimensions = Dimensions::Width(label_width);
+        (width, height) = choose_dimensions(pixel_num, &options)?;
+    }
+
+    let height = height
+        .checked_add(rows)
+        .filter(|&y| y <= max)
+        .ok_or(Error::InsufficientCapacity)?;
+
+    Ok((width, height))
+}
+
+/// Choose the dimensions of an image with at least `pixel_num` pixels.
+fn choose_dimensions(pixel_num: u64, options: &EncodeOptions) -> Result<(u32, u32)> {
+    let max = u64::from(options.max_dimension.unwrap_or(u32::MAX));
+
+    if max == 0 {
+        return Err(Error::InvalidDimensions);
+    }
+
+    let fixed = |side: u32| {
+        if side == 0 || u64::from(side) > max {
+            return Err(Error::InvalidDimensions);
+        }
+
+        let other = pixel_num.div_ceil(u64::from(side));
+        if other > max {
+            return Err(Error::InsufficientCapacity);
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(other.max(1) as u32)
+    };
+
+    match options.dimensions {
+        Dimensions::Width(x) => Ok((x, fixed(x)?)),
+        Dimensions::Height(y) => Ok((fixed(y)?, y)),
+        Dimensions::Exact(x, y) => {
+            if x == 0 || y == 0 || u64::from(x) > max || u64::from(y) > max {
+                return Err(Error::InvalidDimensions);
+            }
+
+            if u64::from(x) * u64::from(y) < pixel_num {
+                return Err(Error::InsufficientCapacity);
+            }
+
+            Ok((x, y))
+        }
+        Dimensions::Ratio(aspect_ratio) => {
+            if !(aspect_ratio.is_finite() && aspect_ratio > 0.0) {
+                return Err(Error::InvalidDimensions);
+            }
+
+            optimize_dimensions(pixel_num, aspect_ratio, max)
+        }
+    }
+}
+
+/// Find dimensions with at least `pixel_num` pixels whose width is within
+/// [`WIDTH_TOLERANCE`] of the ideal width for `aspect_ratio`, leaving as few
+/// pixels unused as possible.
+///
+/// Among equally good candidates, the one closest to `aspect_ratio` wins.
+/// If `max` prevents using any width near the ideal one, the closest allowed
+/// width is used instead.
+#[allow(
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation
+)]
+fn optimize_dimensions(pixel_num: u64, aspect_ratio: f64, max: u64) -> Result<(u32, u32)> {
+    // Any width in this range keeps the height within `max`.
+    let min_width = pixel_num.div_ceil(max).max(1);
+    let max_width = max.min(pixel_num);
+
+    if min_width > max_width {
+        return Err(Error::InsufficientCapacity);
+    }
+
+    let (ideal_width, _) = crate::min_dimensions_from_pixels(pixel_num, aspect_ratio);
+    let ideal_width = f64::from(ideal_width);
+
+    let mut lo =
+        ((ideal_width * (1.0 - WIDTH_TOLERANCE)).floor() as u64).clamp(min_width, max_width);
+    let mut hi =
+        ((ideal_width * (1.0 + WIDTH_TOLERANCE)).ceil() as u64).clamp(min_width, max_width);
+
+    if lo > hi {
+        (lo, hi) = (hi, lo);
+    }
+
+    let ratio_error = |x: u64, y: u64| ((x as f64 / y as f64) / aspect_ratio).ln().abs();
+
+    let (x, y) = (lo..=hi)
+        .map(|x| (x, pixel_num.div_ceil(x)))
+        .min_by(|&(x1, y1), &(x2, y2)| {
+            (x1 * y1)
+                .cmp(&(x2 * y2))
+                .then_with(|| ratio_error(x1, y1).total_cmp(&ratio_error(x2, y2)))
+        })
+        .expect("range of widths is never empty");
+
+    Ok((x as u32, y as u32))
+}
+
+/// Plan an image of `width`x`height` with pixels in `pixel_format`
+/// holding the largest payload that fits.
+#[must_use]
+pub fn plan_for_dimensions(pixel_format: PixelFormat, width: u32, height: u32) -> Plan {
+    let plan = Plan::new(
+        pixel_format,
+        Channels::all(pixel_format),
+        width,
+        height,
+        0,
+        0,
+    );
+
+    Plan {
+        payload_size: plan.max_payload_size,
+        padding: 0,
+        ..plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_for_size() {
+        for pixel_format in PixelFormat::ALL {
+            for payload_size in [0, 1, 100, 4095, 1 << 20] {
+                let plan = plan_for_size(pixel_format, payload_size, 1.5);
+
+                assert!(plan.max_payload_size >= payload_size);
+                assert_eq!(
+                    plan.header_size + plan.payload_size + plan.padding,
+                    plan.capacity
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_for_dimensions() {
+        let plan = plan_for_dimensions(PixelFormat::Rgba8, 1920, 1080);
+
+        assert_eq!(plan.capacity, 1920 * 1080 * 4);
+        assert_eq!(plan.max_payload_size, plan.capacity - Header::SIZE as u64);
+        assert_eq!(plan.payload_size, plan.max_payload_size);
+        assert_eq!(plan.padding, 0);
+    }
+
+    #[test]
+    fn test_plan_too_small() {
+        let plan = plan_for_dimensions(PixelFormat::Rgb8, 1, 1);
+
+        assert_eq!(plan.max_payload_size, 0);
+    }
+
+    #[test]
+    fn test_plan_ratio_minimises_padding() {
+        // Rounding both sides of the ideal square up gives 32x32,
+        // while 30x34 or 34x30 waste 4 fewer pixels.
+        let options = EncodeOptions::new().dimensions(Dimensions::Ratio(1.0));
+        let (x, y) = choose_dimensions(1000, &options).unwrap();
+
+        assert_eq!(x * y, 1020);
+
+        for aspect_ratio in [0.3, 1.0, 16.0 / 9.0, 4.0] {
+            let options = EncodeOptions::new().dimensions(Dimensions::Ratio(aspect_ratio));
+
+            for pixel_num in [1, 7, 997, 12_345, 1 << 20] {
+                let (x, y) = choose_dimensions(pixel_num, &options).unwrap();
+                let (naive_x, naive_y) = crate::min_dimensions_from_pixels(pixel_num, aspect_ratio);
+
+                assert!(u64::from(x) * u64::from(y) >= pixel_num);
+                assert!(u64::from(x) * u64::from(y) <= u64::from(naive_x) * u64::from(naive_y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_fixed_dimensions() {
+        let plan = |dimensions| {
+            super::plan(
+                PixelFormat::Rgb8,
+                1000,
+                &EncodeOptions::new()
+                    .dimensions(dimensions)
+                    .max_dimension(100),
+            )
+        };
+
+        let width = plan(Dimensions::Width(20)).unwrap();
+        assert_eq!((width.width, width.height), (20, 18));
+
+        let height = plan(Dimensions::Height(20)).unwrap();
+        assert_eq!((height.width, height.height), (18, 20));
+
+        let exact = plan(Dimensions::Exact(30, 30)).unwrap();
+        assert_eq!((exact.width, exact.height), (30, 30));
+
+        assert!(matches!(
+            plan(Dimensions::Exact(10, 10)),
+            Err(Error::InsufficientCapacity)
+        ));
+        assert!(matches!(
+            plan(Dimensions::Width(3)),
+            Err(Error::InsufficientCapacity)
+        ));
+        assert!(matches!(
+            plan(Dimensions::Width(0)),
+            Err(Error::InvalidDimensions)
+        ));
+        assert!(matches!(
+            plan(Dimensions::Height(101)),
+            Err(Error::InvalidDimensions)
+        ));
+    }
+
+    #[test]
+    fn test_plan_max_dimension() {
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Ratio(10.0))
+            .max_dimension(64);
+
+        let plan = plan(PixelFormat::Rgba8, 10_000, &options).unwrap();
+        assert!(plan.width <= 64 && plan.height <= 64);
+        assert!(plan.max_payload_size >= 10_000);
+
+        assert!(matches!(
+            super::plan(PixelFormat::Rgba8, 100_000, &options),
+            Err(Error::InsufficientCapacity)
+        ));
+    }
+
+    #[test]
+    fn test_plan_channels() {
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Width(10))
+            .channels(Channels::BLUE);
+
+        let plan = plan(PixelFormat::Rgb8, 100, &options).unwrap();
+        assert_eq!(plan.capacity, 10 * u64::from(plan.height));
+        assert!(plan.max_payload_size >= 100);
+
+        let options = EncodeOptions::new().channels(Channels::ALPHA);
+        assert!(matches!(
+            super::plan(PixelFormat::Rgb8, 100, &options),
+            Err(Error::InvalidChannels)
+        ));
+    }
+
+    #[test]
+    fn test_plan_label() {
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Width(100))
+            .label("x");
+
+        let plan = plan(PixelFormat::Rgb8, 1000, &options).unwrap();
+        assert_eq!(plan.label_rows, label::ROWS);
+        assert_eq!((plan.width, plan.height), (100, label::ROWS + 4));
+        assert_eq!(plan.header_size, 300 * u64::from(label::ROWS));
+        assert_eq!(
+            plan.header_size + plan.payload_size + plan.padding,
+            plan.capacity
+        );
+
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Exact(100, label::ROWS))
+            .label("x");
+        assert!(matches!(
+            super::plan(PixelFormat::Rgb8, 1000, &options),
+            Err(Error::InsufficientCapacity)
+        ));
+    }
+
+    #[test]
+    fn test_plan_label_width() {
+        let text = "a label wider than the payload";
+        let options = EncodeOptions::new().label(text);
+
+        let plan = plan(PixelFormat::Rgba8, 100, &options).unwrap();
+        assert_eq!(plan.width, label::width(text));
+        assert_eq!(plan.height, label::ROWS + 1);
+
+        let options = options.max_dimension(100);
+        let plan = super::plan(PixelFormat::Rgba8, 100, &options).unwrap();
+        assert_eq!(plan.width, 100);
+    }
+
+    #[test]
+    fn test_plan_blocks() {
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Exact(100, 60))
+            .block_size(10);
+
+        let plan = plan(PixelFormat::Rgb8, 100, &options).unwrap();
+        assert_eq!((plan.width, plan.height), (100, 60));
+        assert_eq!(plan.block_size, Some(10));
+        assert_eq!(plan.capacity, 10 * 5 * 3);
+
+        let options = EncodeOptions::new().block_size(3).max_dimension(30);
+        let plan = super::plan(PixelFormat::Rgb8, 100, &options).unwrap();
+        assert_eq!((plan.width, plan.height), (3 * 6, 3 * (7 + 1)));
+
+        let options = options.block_size(0);
+        assert!(matches!(
+            super::plan(PixelFormat::Rgb8, 100, &options),
+            Err(Error::InvalidDimensions)
+        ));
+
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Height(15))
+            .block_size(10);
+        assert!(matches!(
+            super::plan(PixelFormat::Rgb8, 100, &options),
+            Err(Error::InsufficientCapacity)
+        ));
+    }
+
+    #[test]
+    fn test_plan_markers() {
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Exact(100, 60))
+            .markers(true);
+
+        let plan = plan(PixelFormat::Rgb8, 100, &options).unwrap();
+        assert_eq!((plan.width, plan.height), (100, 60));
+        assert_eq!((plan.block_size, plan.markers), (Some(1), true));
+        let border = 2 * frame::BORDER;
+        assert_eq!(plan.capacity, u64::from((100 - border) * (60 - border) * 3));
+
+        let options = options.block_size(2);
+        let plan = super::plan(PixelFormat::Rgb8, 100, &options).unwrap();
+        assert_eq!(plan.capacity, u64::from((50 - border) * (30 - border) * 3));
+    }
+
+    #[test]
+    fn test_plan_bilevel() {
+        let options = EncodeOptions::new()
+            .dimensions(Dimensions::Width(100))
+            .bilevel(true);
+
+        let plan = plan(PixelFormat::Rgba8, 100, &options).unwrap();
+        let bits = (Header::SIZE as u64 + 100) * 8;
+        assert_eq!((plan.width, plan.height), (100, 10));
+        assert!(u64::from(plan.width * plan.height) >= bits);
+        assert_eq!(plan.capacity, 125);
+        assert_eq!(plan.max_payload_size, 125 - Header::SIZE as u64);
+        assert!(plan.bilevel);
+
+        let options = options.channels(Channels::RED);
+        assert!(matches!(
+            super::plan(PixelFormat::Rgba8, 100, &options),
+            Err(Error::InvalidChannels)
+        ));
+    }
+
+    #[test]
+    fn test_empty_round_trip() {
+        let image: image::RgbImage = crate::to_image([], 1.0);
+
+        assert!(crate::from_image(image).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+use serde::Serialize;
+
+use crate::bilevel;
+use crate::block::Blocks;
+use crate::cursor::ImageCursor;
+use crate::file::Header;
+use crate::traits::{Image, PixelFormat};
+use crate::{Error, Result, VerifyingKey};
+
+/// Information about an image gathered from its header.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Info {
+    /// Version of the format the image was written with, or `0` for
+    /// images written before headers started with a magic number.
+    pub version: u8,
+    /// Type tag of the payload. `0` means raw bytes.
+    pub tag: u64,
+    /// Size of the payload in bytes.
+    pub payload_size: u64,
+    /// Maximum payload size in bytes the image can hold.
+    pub capacity: u64,
+    /// Fraction of the capacity used by the payload.
+    pub utilisation: f64,
+    pub width: u32,
+    pub height: u32,
+    /// Number of rows at the top of the image taken up by a label strip.
+    pub label_rows: u32,
+    /// Side in pixels of the block each pixel holding data is drawn as,
+    /// if the image has blocks.
+    pub block_size: Option<u32>,
+    /// Whether the blocks were found with the markers of a frame.
+    pub markers: bool,
+    /// Whether each pixel holds one bit, as a black or white pixel.
+    pub bilevel: bool,
+    /// Index of the frame the image is among the frames the payload is
+    /// spread across.
+    pub frame: u32,
+    /// Number of frames the payload is spread across. For more than one,
+    /// `payload_size` is the size of the part held by this frame.
+    pub frames: u32,
+    /// Index of the share of a secret the payload is, starting from `1`.
+    pub share: u8,
+    /// Number of shares needed to recover the secret, or `0` if the
+    /// payload is not a share.
+    pub threshold: u8,
+    /// Number of recipients the payload is encrypted for, or `0` if it
+    /// is not encrypted.
+    pub recipients: usize,
+    /// Key which signed the image, if it is signed. The signature is
+    /// not checked, which needs the payload.
+    pub signer: Option<VerifyingKey>,
+    pub pixel_format: PixelFormat,
+}
+
+/// Read the header of `image` without reading its payload.
+///
+/// # Errors
+///
+/// - The image does not contain a valid header
+/// - The header describes a payload larger than the image
+#[allow(clippy::cast_precision_loss)]
+pub fn probe<I>(image: &I) -> Result<Info>
+where
+    I: Image,
+{
+    match crate::find_blocks(image) {
+        Some(grid) => Ok(Info {
+            width: image.width(),
+            height: image.height(),
+            block_size: Some(grid.block_size),
+            markers: grid.markers,
+            ..probe_header(&Blocks::new(image, grid))?
+        }),
+        None => probe_header(image),
+    }
+}
+
+/// Read the header of `image`, which has one pixel per block.
+#[allow(clippy::cast_precision_loss)]
+fn probe_header<I>(image: &I) -> Result<Info>
+where
+    I: Image,
+{
+    let packed = crate::packed_bilevel(image);
+    let header = match &packed {
+        Some(packed) => Header::read_from(ImageCursor::new(packed))?,
+        None => Header::read_from(ImageCursor::new(image))?,
+    };
+
+    let header_size = header.encoded_size() as u64;
+    let capacity = match header.label_rows {
+        _ if packed.is_some() => {
+            bilevel::capacity(image.width(), image.height()).saturating_sub(header_size)
+        }
+        0 => crate::image_capacity::<I>(image.width(), image.height()).saturating_sub(header_size),
+        rows => crate::image_capacity::<I>(image.width(), image.height().saturating_sub(rows)),
+    };
+
+    if header.size > capacity {
+        return Err(Error::InvalidHeader);
+    }
+
+    let utilisation = if capacity == 0 {
+        0.0
+    } else {
+        header.size as f64 / capacity as f64
+    };
+
+    Ok(Info {
+        version: header.version(),
+        tag: header.tag,
+        payload_size: header.size,
+        capacity,
+        utilisation,
+        width: image.width(),
+        height: image.height(),
+        label_rows: header.label_rows,
+        block_size: None,
+        markers: false,
+        bilevel: packed.is_some(),
+        frame: header.frame,
+        frames: header.frames,
+        share: header.share,
+        threshold: header.threshold,
+        recipients: header.recipients.len(),
+        signer: crate::signature::signer(&header),
+        pixel_format: I::PIXEL_FORMAT,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe() {
+        let data = vec![0xaa; 100];
+        let image: image::RgbImage = crate::to_image(&data, 1.0);
+
+        let info = probe(&image).unwrap();
+
+        assert_eq!(info.payload_size, 100);
+        assert_eq!(info.tag, 0);
+        assert_eq!(info.pixel_format, PixelFormat::Rgb8);
+        assert_eq!(
+            info.capacity,
+            u64::from(image.width() * image.height() * 3) - Header::SIZE as u64
+        );
+        assert!(info.utilisation > 0.0 && info.utilisation <= 1.0);
+    }
+
+    #[test]
+    fn test_probe_not_an_image() {
+        let image = image::RgbaImage::new(8, 8);
+
+        assert!(matches!(probe(&image), Err(Error::InvalidHeader)));
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::prelude::*;
+
+use crate::{Error, Result};
+
+/// Number of payload bytes copied between progress reports.
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Whether an operation should go on after reporting its progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Cancel,
+}
+
+/// Observer of the progress of reading or writing a payload.
+///
+/// Implemented for closures taking the number of bytes processed and
+/// the total number of bytes.
+pub trait Progress {
+    /// Called after every chunk of the payload with the number of bytes
+    /// processed so far and the size of the payload.
+    ///
+    /// Returning [`Control::Cancel`] aborts the operation with
+    /// [`Error::Cancelled`].
+    fn progress(&mut self, processed: u64, total: u64) -> Control;
+}
+
+impl<F> Progress for F
+where
+    F: FnMut(u64, u64) -> Control,
+{
+    fn progress(&mut self, processed: u64, total: u64) -> Control {
+        self(processed, total)
+    }
+}
+
+/// Progress observer which never cancels.
+pub(crate) struct Ignore;
+
+impl Progress for Ignore {
+    fn progress(&mut self, _: u64, _: u64) -> Control {
+        Control::Continue
+    }
+}
+
+fn report(progress: &mut dyn Progress, processed: usize, total: usize) -> Result<()> {
+    match progress.progress(processed as u64, total as u64) {
+        Control::Continue => Ok(()),
+        Control::Cancel => Err(Error::Cancelled),
+    }
+}
+
+/// Write all of `data` to `writer` in chunks, reporting to `progress`
+/// after each one.
+pub(crate) fn write_all<W>(writer: &mut W, data: &[u8], progress: &mut dyn Progress) -> Result<()>
+where
+    W: Write,
+{
+    report(progress, 0, data.len())?;
+
+    let mut processed = 0;
+    for chunk in data.chunks(CHUNK_SIZE) {
+        writer.write_all(chunk)?;
+        processed += chunk.len();
+        report(progress, processed, data.len())?;
+    }
+
+    Ok(())
+}
+
+/// Fill all of `buf` from `reader` in chunks, reporting to `progress`
+/// after each one.
+pub(crate) fn read_exact<R>(
+    reader: &mut R,
+    buf: &mut [u8],
+    progress: &mut dyn Progress,
+) -> Result<()>
+where
+    R: Read,
+{
+    let total = buf.len();
+    report(progress, 0, total)?;
+
+    let mut processed = 0;
+    for chunk in buf.chunks_mut(CHUNK_SIZE) {
+        reader.read_exact(chunk)?;
+        processed += chunk.len();
+        report(progress, processed, total)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_reports() {
+        let data = vec![7u8; CHUNK_SIZE * 2 + 10];
+        let mut out = Vec::new();
+        let mut reports = Vec::new();
+
+        write_all(&mut out, &data, &mut |processed, total| {
+            reports.push((processed, total));
+            Control::Continue
+        })
+        .unwrap();
+
+        let total = data.len() as u64;
+        assert_eq!(out, data);
+        assert_eq!(
+            reports,
+            [
+                (0, total),
+                (CHUNK_SIZE as u64, total),
+                (CHUNK_SIZE as u64 * 2, total),
+                (total, total)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let data = vec![7u8; CHUNK_SIZE * 4];
+        let mut out = Vec::new();
+
+        let result = write_all(&mut out, &data, &mut |processed, _| {
+            if processed >= CHUNK_SIZE as u64 {
+                Control::Cancel
+            } else {
+                Control::Continue
+            }
+        });
+
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(out.len(), CHUNK_SIZE);
+    }
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::StaticSecret;
+
+use crate::file::Header;
+use crate::{Error, Result};
+
+const PUBLIC_PREFIX: &str = "imgcode-public-";
+const SECRET_PREFIX: &str = "IMGCODE-SECRET-";
+
+/// Context of the key wrapping the payload key for a recipient.
+const WRAP_INFO: &[u8] = b"imgcode x25519 recipient";
+/// Context of the key encrypting the payload.
+const PAYLOAD_INFO: &[u8] = b"imgcode payload";
+
+/// Size of the authentication tag added to encrypted data.
+pub(crate) const TAG_SIZE: usize = 16;
+
+/// Public key of a recipient data can be encrypted for, written as
+/// `imgcode-public-` followed by the key in hexadecimal.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PublicKey([u8; 32]);
+
+/// Secret key of a recipient, which decrypts the data encrypted for its
+/// public key. Written as `IMGCODE-SECRET-` followed by the key in
+/// hexadecimal.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretKey([u8; 32]);
+
+impl SecretKey {
+    /// Generate a new secret key from the random numbers of the
+    /// operating system.
+    ///
+    /// # Errors
+    ///
+    /// Random numbers could not be read from the operating system.
+    pub fn generate() -> Result<Self> {
+        Ok(Self(random_key()?))
+    }
+
+    /// Get the public key data is encrypted for to be decrypted with
+    /// this key.
+    #[must_use]
+    pub fn public_key(&self) -> PublicKey {
+        let secret = StaticSecret::from(self.0);
+        PublicKey(x25519_dalek::PublicKey::from(&secret).to_bytes())
+    }
+}
+
+/// Get a random 32-byte key.
+pub(crate) fn random_key() -> Result<[u8; 32]> {
+    let mut key = [0; 32];
+    getrandom::getrandom(&mut key).map_err(|e| Error::Io(e.into()))?;
+    Ok(key)
+}
+
+/// Write `bytes` in lowercase hexadecimal.
+pub(crate) fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|x| write!(f, "{x:02x}"))
+}
+
+/// Parse the 32 bytes of a key written in hexadecimal after `prefix`.
+pub(crate) fn parse_key(s: &str, prefix: &str) -> Result<[u8; 32]> {
+    let hex = s
+        .trim()
+        .strip_prefix(prefix)
+        .filter(|x| x.len() == 64 && x.is_ascii())
+        .ok_or(Error::InvalidKey)?;
+
+    let mut key = [0; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| Error::InvalidKey)?;
+    }
+    Ok(key)
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PUBLIC_PREFIX)?;
+        write_hex(f, &self.0)
+    }
+}
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PublicKey({self})")
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_key(s, PUBLIC_PREFIX).map(Self)
+    }
+}
+
+impl fmt::Display for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(SECRET_PREFIX)?;
+        write_hex(f, &self.0)
+    }
+}
+
+/// Secret keys are left out of debug output, so that they do not end up
+/// in logs.
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKey({})", self.public_key())
+    }
+}
+
+impl FromStr for SecretKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_key(s, SECRET_PREFIX).map(Self)
+    }
+}
+
+/// Derive a 32-byte key from `secret` for `info`.
+fn derive_key(secret: &[u8], salt: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut key = [0; 32];
+    Hkdf::<Sha256>::new(Some(salt), secret)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid length for HKDF-SHA256");
+    key
+}
+
+/// Encrypt `data` with `key`, which is only ever used once, and
+/// authenticate it along with `aad`.
+fn seal(key: &[u8; 32], data: &[u8], aad: &[u8]) -> Vec<u8> {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt(&Nonce::default(), Payload { msg: data, aad })
+        .expect("data fits in a single message")
+}
+
+/// Decrypt `data` sealed with `key` and `aad`, if neither was altered.
+fn open(key: &[u8; 32], data: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(&Nonce::default(), Payload { msg: data, aad })
+        .ok()
+}
+
+/// Get the data authenticated along with the payload of `header`, which
+/// holds `size` bytes once encrypted: the header without its recipient
+/// stanzas, so that the fields describing the payload cannot be altered.
+///
+/// The label rows and signature are left out, as they are only known
+/// once the payload is encrypted.
+fn associated_data(header: &Header, size: u64) -> Vec<u8> {
+    let header = Header {
+        size,
+        label_rows: 0,
+        recipients: Vec::new(),
+        signature: None,
+        ..header.clone()
+    };
+
+    let mut aad = Vec::with_capacity(header.encoded_size());
+    header
+        .write_to(&mut aad)
+        .expect("headers without recipients can be written");
+    aad
+}
+
+/// Encrypt `data` for `recipients` with a new random key, and return
+/// the key wrapped for each of them, to store in `header`, along with
+/// the encrypted data, which is authenticated along with `header`.
+///
+/// The key of each recipient is wrapped with a key agreed between a new
+/// ephemeral X25519 key, which is stored with the wrapped key, and the
+/// public key of the recipient.
+pub(crate) fn encrypt(
+    data: &[u8],
+    header: &Header,
+    recipients: &[PublicKey],
+) -> Result<(Vec<[u8; Header::RECIPIENT_SIZE]>, Vec<u8>)> {
+    if recipients.len() > usize::from(u8::MAX) {
+        return Err(Error::SizeLimit);
+    }
+
+    let file_key = random_key()?;
+
+    let mut wrapped = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let ephemeral = StaticSecret::from(random_key()?);
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral).to_bytes();
+        let shared = ephemeral.diffie_hellman(&x25519_dalek::PublicKey::from(recipient.0));
+
+        let salt = [ephemeral_public, recipient.0].concat();
+        let wrap_key = derive_key(shared.as_bytes(), &salt, WRAP_INFO);
+
+        let mut stanza = [0; Header::RECIPIENT_SIZE];
+        stanza[..32].copy_from_slice(&ephemeral_public);
+        stanza[32..].copy_from_slice(&seal(&wrap_key, &file_key, &[]));
+        wrapped.push(stanza);
+    }
+
+    let payload_key = derive_key(&file_key, &[], PAYLOAD_INFO);
+    let aad = associated_data(header, (data.len() + TAG_SIZE) as u64);
+    Ok((wrapped, seal(&payload_key, data, &aad)))
+}
+
+/// Decrypt `data` encrypted for the recipients in `header` with
+/// `identity`.
+///
+/// # Errors
+///
+/// - There is no identity to decrypt the data with
+/// - The data was not encrypted for `identity`
+/// - The data or the header was altered after the data was encrypted
+pub(crate) fn decrypt(
+    data: &[u8],
+    header: &Header,
+    identity: Option<&SecretKey>,
+) -> Result<Vec<u8>> {
+    let identity = identity.ok_or(Error::Encrypted)?;
+    let secret = StaticSecret::from(identity.0);
+    let public = identity.public_key();
+
+    let file_key = header
+        .recipients
+        .iter()
+        .find_map(|stanza| {
+            let ephemeral_public: [u8; 32] = stanza[..32].try_into().unwrap();
+            let shared = secret.diffie_hellman(&x25519_dalek::PublicKey::from(ephemeral_public));
+
+            let salt = [ephemeral_public, public.0].concat();
+            let wrap_key = derive_key(shared.as_bytes(), &salt, WRAP_INFO);
+            open(&wrap_key, &stanza[32..], &[])
+        })
+        .ok_or(Error::WrongIdentity)?;
+
+    let file_key: [u8; 32] = file_key.try_into().map_err(|_| Error::WrongIdentity)?;
+    let payload_key = derive_key(&file_key, &[], PAYLOAD_INFO);
+
+    let aad = associated_data(header, header.size);
+    open(&payload_key, data, &aad).ok_or(Error::ChecksumMismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_text() {
+        let secret = SecretKey::generate().unwrap();
+        let public = secret.public_key();
+
+        assert_eq!(secret.to_string().parse::<SecretKey>().unwrap(), secret);
+        assert_eq!(public.to_string().parse::<PublicKey>().unwrap(), public);
+        assert!(public.to_string().starts_with(PUBLIC_PREFIX));
+        assert!(!format!("{secret:?}").contains(&secret.to_string()));
+
+        assert!(matches!(
+            public.to_string().parse::<SecretKey>(),
+            Err(Error::InvalidKey)
+        ));
+        assert!(matches!(
+            "imgcode-public-00".parse::<PublicKey>(),
+            Err(Error::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn test_encrypt_for_recipients() {
+        let alice = SecretKey::generate().unwrap();
+        let bob = SecretKey::generate().unwrap();
+        let eve = SecretKey::generate().unwrap();
+
+        let data = b"meet at noon";
+        let recipients = [alice.public_key(), bob.public_key()];
+        let header = Header {
+            tag: 7,
+            ..Default::default()
+        };
+        let (wrapped, encrypted) = encrypt(data, &header, &recipients).unwrap();
+        let header = Header {
+            size: encrypted.len() as u64,
+            recipients: wrapped,
+            ..header
+        };
+        assert_eq!(header.recipients.len(), 2);
+        assert_eq!(encrypted.len(), data.len() + TAG_SIZE);
+
+        for identity in [&alice, &bob] {
+            assert_eq!(decrypt(&encrypted, &header, Some(identity)).unwrap(), data);
+        }
+
+        assert!(matches!(
+            decrypt(&encrypted, &header, Some(&eve)),
+            Err(Error::WrongIdentity)
+        ));
+        assert!(matches!(
+            decrypt(&encrypted, &header, None),
+            Err(Error::Encrypted)
+        ));
+
+        let mut altered = encrypted.clone();
+        altered[0] ^= 1;
+        assert!(matches!(
+            decrypt(&altered, &header, Some(&alice)),
+            Err(Error::ChecksumMismatch)
+        ));
+
+        // The header is authenticated along with the data.
+        let retagged = Header {
+            tag: 8,
+            ..header.clone()
+        };
+        assert!(matches!(
+            decrypt(&encrypted, &retagged, Some(&alice)),
+            Err(Error::ChecksumMismatch)
+        ));
+    }
+}
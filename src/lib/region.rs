@@ -0,0 +1,72 @@
+/// Rectangle of pixels inside an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// Column of the top left pixel.
+    pub x: u32,
+    /// Row of the top left pixel.
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    #[must_use]
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Get the region covering a whole image with dimensions `width`x`height`.
+    #[must_use]
+    pub fn full(width: u32, height: u32) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    /// Check whether the region is not empty and lies entirely inside an
+    /// image with dimensions `width`x`height`.
+    #[must_use]
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        let right = self.x.checked_add(self.width);
+        let bottom = self.y.checked_add(self.height);
+
+        self.width != 0
+            && self.height != 0
+            && right.is_some_and(|x| x <= width)
+            && bottom.is_some_and(|y| y <= height)
+    }
+
+    /// Check whether the pixel at `x`,`y` of the image is inside the region.
+    #[must_use]
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.x..self.x.saturating_add(self.width)).contains(&x)
+            && (self.y..self.y.saturating_add(self.height)).contains(&y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits() {
+        assert!(Region::full(10, 5).fits(10, 5));
+        assert!(Region::new(8, 4, 2, 1).fits(10, 5));
+        assert!(!Region::new(8, 4, 3, 1).fits(10, 5));
+        assert!(!Region::new(0, 0, 0, 5).fits(10, 5));
+        assert!(!Region::new(u32::MAX, 0, 2, 1).fits(u32::MAX, 5));
+    }
+
+    #[test]
+    fn test_contains() {
+        let region = Region::new(2, 3, 4, 1);
+        assert!(region.contains(2, 3));
+        assert!(region.contains(5, 3));
+        assert!(!region.contains(6, 3));
+        assert!(!region.contains(2, 4));
+        assert!(!region.contains(1, 3));
+    }
+}
@@ -0,0 +1,249 @@
+//! Reed-Solomon codes over GF(256), with the generator polynomial having
+//! the roots `α⁰..αᵖ⁻¹` for `p` parity bytes.
+//!
+//! Codewords are stored with the coefficient of the highest power first,
+//! data followed by parity.
+
+/// Primitive polynomial `x⁸ + x⁴ + x³ + x² + 1` the field is built from.
+const PRIMITIVE: u16 = 0x11d;
+
+/// Powers of `α`, repeated so that sums of two logarithms can be looked up.
+#[allow(clippy::cast_possible_truncation)]
+const EXP: [u8; 512] = {
+    let mut exp = [0u8; 512];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        exp[i + 255] = x as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= PRIMITIVE;
+        }
+        i += 1;
+    }
+    exp
+};
+
+/// Logarithms to the base `α`. The logarithm of `0` is undefined.
+#[allow(clippy::cast_possible_truncation)]
+const LOG: [u8; 256] = {
+    let mut log = [0u8; 256];
+    let mut i = 0;
+    while i < 255 {
+        log[EXP[i] as usize] = i as u8;
+        i += 1;
+    }
+    log
+};
+
+fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+
+    EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+}
+
+/// Divide `a` by `b`, which must not be `0`.
+fn div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+
+    EXP[LOG[a as usize] as usize + 255 - LOG[b as usize] as usize]
+}
+
+/// Get `α` to the power of `n`.
+fn pow(n: usize) -> u8 {
+    EXP[n % 255]
+}
+
+/// Evaluate the polynomial with coefficients `poly`, lowest power first, at `x`.
+fn eval(poly: &[u8], x: u8) -> u8 {
+    poly.iter().rev().fold(0, |acc, &c| mul(acc, x) ^ c)
+}
+
+/// Get the generator polynomial for `parity` parity bytes, highest power first.
+fn generator(parity: usize) -> Vec<u8> {
+    let mut gen = vec![1u8];
+
+    for i in 0..parity {
+        // Multiply by (x - αⁱ).
+        let root = pow(i);
+        let mut next = vec![0u8; gen.len() + 1];
+        for (j, &c) in gen.iter().enumerate() {
+            next[j] ^= c;
+            next[j + 1] ^= mul(c, root);
+        }
+        gen = next;
+    }
+
+    gen
+}
+
+/// Get the `parity` parity bytes of a codeword holding `data`.
+///
+/// `data` and the parity must fit in 255 bytes together.
+pub fn encode(data: &[u8], parity: usize) -> Vec<u8> {
+    debug_assert!(data.len() + parity <= 255);
+
+    let gen = generator(parity);
+    let mut remainder = vec![0u8; parity];
+
+    for &byte in data {
+        let factor = byte ^ remainder.first().copied().unwrap_or(0);
+        remainder.rotate_left(1);
+        if let Some(last) = remainder.last_mut() {
+            *last = 0;
+        }
+
+        for (r, &g) in remainder.iter_mut().zip(&gen[1..]) {
+            *r ^= mul(g, factor);
+        }
+    }
+
+    remainder
+}
+
+/// Get the syndromes of `codeword`, which are all `0` if it has no errors.
+fn syndromes(codeword: &[u8], parity: usize) -> Vec<u8> {
+    (0..parity)
+        .map(|i| {
+            let x = pow(i);
+            codeword.iter().fold(0, |acc, &c| mul(acc, x) ^ c)
+        })
+        .collect()
+}
+
+/// Find the error locator polynomial from `syndromes` with the
+/// Berlekamp-Massey algorithm, lowest power first.
+fn error_locator(syndromes: &[u8]) -> Vec<u8> {
+    let mut locator = vec![1u8];
+    let mut previous = vec![1u8];
+    let mut previous_discrepancy = 1u8;
+    let mut len = 0;
+    let mut shift = 1;
+
+    for n in 0..syndromes.len() {
+        let discrepancy = (1..=len).fold(syndromes[n], |acc, i| {
+            acc ^ mul(locator.get(i).copied().unwrap_or(0), syndromes[n - i])
+        });
+
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+
+        // locator -= discrepancy / previous_discrepancy * xˢʰⁱᶠᵗ * previous
+        let factor = div(discrepancy, previous_discrepancy);
+        let mut next = locator.clone();
+        next.resize(next.len().max(previous.len() + shift), 0);
+        for (i, &c) in previous.iter().enumerate() {
+            next[i + shift] ^= mul(factor, c);
+        }
+
+        if 2 * len <= n {
+            len = n + 1 - len;
+            previous = std::mem::replace(&mut locator, next);
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            locator = next;
+            shift += 1;
+        }
+    }
+
+    locator.truncate(len + 1);
+    locator
+}
+
+/// Correct the errors in `codeword`, which ends with `parity` parity bytes.
+///
+/// Returns the number of corrected bytes, or `None` if there are more
+/// errors than the code can correct: at most half the number of parity bytes.
+pub fn decode(codeword: &mut [u8], parity: usize) -> Option<usize> {
+    let n = codeword.len();
+    let syndromes = syndromes(codeword, parity);
+    if syndromes.iter().all(|&x| x == 0) {
+        return Some(0);
+    }
+
+    let locator = error_locator(&syndromes);
+    let errors = locator.len() - 1;
+    if errors == 0 || 2 * errors > parity {
+        return None;
+    }
+
+    // The error evaluator is S(x)Λ(x) mod xᵖ.
+    let mut evaluator = vec![0u8; parity];
+    for (i, &s) in syndromes.iter().enumerate() {
+        for (j, &l) in locator.iter().enumerate().take(parity - i) {
+            evaluator[i + j] ^= mul(s, l);
+        }
+    }
+
+    // Only the odd powers are left in the formal derivative over GF(2⁸).
+    let derivative = locator
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &c)| if i % 2 == 1 { c } else { 0 })
+        .collect::<Vec<_>>();
+
+    let mut corrected = 0;
+    for (i, byte) in codeword.iter_mut().enumerate() {
+        // The byte at index `i` is the coefficient of xⁿ⁻¹⁻ⁱ.
+        let power = n - 1 - i;
+        let x_inverse = pow(255 - power % 255);
+        if eval(&locator, x_inverse) != 0 {
+            continue;
+        }
+
+        let denominator = eval(&derivative, x_inverse);
+        if denominator == 0 {
+            return None;
+        }
+
+        *byte ^= mul(pow(power), div(eval(&evaluator, x_inverse), denominator));
+        corrected += 1;
+    }
+
+    (corrected == errors && self::syndromes(codeword, parity).iter().all(|&x| x == 0))
+        .then_some(corrected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        assert_eq!(mul(2, 128), 0x1d);
+        assert_eq!(div(mul(0x53, 0xca), 0xca), 0x53);
+        assert_eq!(LOG[EXP[200] as usize], 200);
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = (0..200u8).map(|x| x.wrapping_mul(37)).collect::<Vec<_>>();
+        let parity = 40;
+
+        let mut codeword = data.clone();
+        codeword.extend(encode(&data, parity));
+        assert_eq!(decode(&mut codeword.clone(), parity), Some(0));
+
+        let mut damaged = codeword.clone();
+        for (i, x) in (0..parity / 2).zip(1u8..) {
+            damaged[i * 11] ^= x;
+        }
+        assert_eq!(decode(&mut damaged, parity), Some(parity / 2));
+        assert_eq!(damaged, codeword);
+
+        let mut damaged = codeword.clone();
+        for i in 0..=parity / 2 {
+            damaged[i * 7] ^= 0xff;
+        }
+        assert_eq!(decode(&mut damaged, parity), None);
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::progress::Ignore;
+use crate::traits::{Image, ImageMut};
+use crate::{Dimensions, EncodeOptions, Error, Result};
+
+/// A type which can be stored in images by [`to_image_serialized()`].
+///
+/// The tag is stored in the header of the image and checked by
+/// [`from_image_deserialized()`], which rejects images holding a value
+/// with another tag. Pick a new tag whenever the serialized fields of
+/// the type change, so that images written with the old fields are
+/// rejected rather than misread. Tags must not be `0`, which is
+/// reserved for raw byte payloads.
+///
+/// Integers, floats, `bool`, `char`, strings, vectors, options and
+/// tuples of up to four types implementing it already do. Their tags
+/// are hashes of their names, combined with the tags of the types they
+/// hold, so that `Vec<u8>` and `Vec<u16>` are told apart.
+pub trait TypeTag {
+    const TAG: u64;
+}
+
+/// Get the 64-bit FNV-1a hash of `name` followed by `params`, the tags
+/// of the types a generic type holds.
+#[allow(clippy::cast_lossless)]
+const fn hash_tag(name: &str, params: &[u64]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+
+    let name = name.as_bytes();
+    let mut i = 0;
+    while i < name.len() {
+        hash = (hash ^ name[i] as u64).wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < params.len() {
+        let param = params[i].to_be_bytes();
+        let mut j = 0;
+        while j < param.len() {
+            hash = (hash ^ param[j] as u64).wrapping_mul(FNV_PRIME);
+            j += 1;
+        }
+        i += 1;
+    }
+
+    hash
+}
+
+macro_rules! impl_type_tag {
+    ($($t:ty),*) => {
+        $(
+            impl TypeTag for $t {
+                const TAG: u64 = hash_tag(stringify!($t), &[]);
+            }
+        )*
+    };
+}
+
+impl_type_tag!(
+    bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, String
+);
+
+/// Strings are serialized like `String`, so they can be read back as one.
+impl TypeTag for str {
+    const TAG: u64 = String::TAG;
+}
+
+impl<T: TypeTag> TypeTag for Vec<T> {
+    const TAG: u64 = hash_tag("Vec", &[T::TAG]);
+}
+
+/// Slices are serialized like `Vec`, so they can be read back as one.
+impl<T: TypeTag> TypeTag for [T] {
+    const TAG: u64 = Vec::<T>::TAG;
+}
+
+impl<T: TypeTag> TypeTag for Option<T> {
+    const TAG: u64 = hash_tag("Option", &[T::TAG]);
+}
+
+macro_rules! impl_tuple_type_tag {
+    ($($t:ident),+) => {
+        impl<$($t: TypeTag),+> TypeTag for ($($t,)+) {
+            const TAG: u64 = hash_tag("()", &[$($t::TAG),+]);
+        }
+    };
+}
+
+impl_tuple_type_tag!(A);
+impl_tuple_type_tag!(A, B);
+impl_tuple_type_tag!(A, B, C);
+impl_tuple_type_tag!(A, B, C, D);
+
+/// Serialize `value` with `bincode` and write it to an image
+/// with dimensions from [`image_dimensions()`](crate::image_dimensions).
+///
+/// # Errors
+///
+/// - `value` cannot be serialized
+/// - `aspect_ratio` is not greater than (`>`) 0
+///
+/// # Panics
+///
+/// The tag of `T` is `0`.
+pub fn to_image_serialized<I, T>(value: &T, aspect_ratio: f64) -> Result<I>
+where
+    I: ImageMut,
+    T: Serialize + TypeTag + ?Sized,
+{
+    assert!(T::TAG != 0, "tag 0 is reserved for raw byte payloads");
+
+    let data = bincode::serialize(value)?;
+    let options = EncodeOptions::new().dimensions(Dimensions::Ratio(aspect_ratio));
+
+    crate::to_image_tagged(&data, T::TAG, &options, &mut Ignore)
+}
+
+/// Read a value of type `T` from an image created with [`to_image_serialized()`].
+///
+/// # Errors
+///
+/// - The image holds a value with another tag than `T`
+/// - Image data size is too large
+/// - The contained value cannot be deserialized
+pub fn from_image_deserialized<I, T>(image: I) -> Result<T>
+where
+    I: Image,
+    T: DeserializeOwned + TypeTag,
+{
+    let (tag, data) = crate::from_image_tagged(image, &mut Ignore)?;
+
+    if tag != T::TAG {
+        return Err(Error::TypeMismatch);
+    }
+
+    Ok(bincode::deserialize(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct State {
+        name: String,
+        counters: Vec<u32>,
+        enabled: bool,
+    }
+
+    impl TypeTag for State {
+        const TAG: u64 = u64::from_be_bytes(*b"state-v1");
+    }
+
+    #[test]
+    fn test_serialized_round_trip() {
+        let state = State {
+            name: "worker-1".to_string(),
+            counters: vec![1, 2, 3],
+            enabled: true,
+        };
+
+        let image: image::RgbImage = to_image_serialized(&state, 1.0).unwrap();
+        let decoded: State = from_image_deserialized(image).unwrap();
+
+        assert_eq!(state, decoded);
+    }
+
+    #[test]
+    fn test_deserialize_type_mismatch() {
+        let image: image::RgbaImage = to_image_serialized(&42u32, 1.0).unwrap();
+
+        assert!(matches!(
+            from_image_deserialized::<_, u64>(image),
+            Err(Error::TypeMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_std_type_tags() {
+        let tags = [
+            u32::TAG,
+            u64::TAG,
+            Vec::<u8>::TAG,
+            Vec::<u16>::TAG,
+            <(u32, String)>::TAG,
+            <(String, u32)>::TAG,
+        ];
+        for (i, tag) in tags.iter().enumerate() {
+            assert_ne!(*tag, 0);
+            assert!(!tags[i + 1..].contains(tag));
+        }
+
+        // Tags are part of the stored format, so they must never change.
+        assert_eq!(u32::TAG, 0x4d2b_df19_3e85_26d1);
+        assert_eq!(str::TAG, String::TAG);
+
+        let image: image::RgbImage = to_image_serialized("read back as a string", 1.0).unwrap();
+        assert_eq!(
+            from_image_deserialized::<_, String>(image).unwrap(),
+            "read back as a string"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_raw_bytes() {
+        let image: image::RgbImage = crate::to_image(b"raw", 1.0);
+
+        assert!(matches!(
+            from_image_deserialized::<_, Vec<u8>>(image),
+            Err(Error::TypeMismatch)
+        ));
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::btree_map::{BTreeMap, Entry};
+
+use sha2::{Digest, Sha256};
+
+use crate::progress::Ignore;
+use crate::traits::{Image, ImageMut};
+use crate::{file, DecodeOptions, EncodeOptions, Error, Result};
+
+/// Size of the identifier at the start of each share, which tells
+/// shares of different secrets apart.
+const ID_SIZE: usize = 8;
+/// Size of the digest of the identifier and the secret after the
+/// identifier, which tells whether the shares recovered the secret.
+const DIGEST_SIZE: usize = 32;
+
+/// Get the digest of `secret` stored in each of its shares with `id`.
+///
+/// The identifier is random, so the digest cannot be looked up in
+/// precomputed tables, but secrets which can be guessed can still be
+/// checked against it.
+fn digest(id: &[u8], secret: &[u8]) -> [u8; DIGEST_SIZE] {
+    Sha256::new()
+        .chain_update(id)
+        .chain_update(secret)
+        .finalize()
+        .into()
+}
+
+/// Multiply `a` and `b` in GF(256), with the polynomial
+/// `x^8 + x^4 + x^3 + x + 1` of AES.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Get the inverse of `a` in GF(256), which is `a^254`.
+fn inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            result = mul(result, power);
+        }
+        power = mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Fill `buf` with random bytes from the operating system.
+fn random_bytes(buf: &mut [u8]) -> Result<()> {
+    getrandom::getrandom(buf).map_err(|e| Error::Io(e.into()))
+}
+
+/// Split `secret` into `count` shares, any `threshold` of which recover
+/// it. Share `x`, from `1`, holds for each byte of the secret the value
+/// at `x` of a random polynomial of degree `threshold - 1` whose constant
+/// term is that byte, so fewer shares tell nothing about the secret.
+fn split(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<Vec<u8>>> {
+    let mut coefficients = vec![0; secret.len() * usize::from(threshold - 1)];
+    random_bytes(&mut coefficients)?;
+
+    let shares = (1..=count)
+        .map(|x| {
+            secret
+                .iter()
+                .enumerate()
+                .map(|(i, &byte)| {
+                    let terms = &coefficients[i * usize::from(threshold - 1)..];
+                    // Horner's method, from the highest degree down.
+                    let higher = terms[..usize::from(threshold - 1)]
+                        .iter()
+                        .rev()
+                        .fold(0, |acc, &c| mul(acc, x) ^ c);
+                    mul(higher, x) ^ byte
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(shares)
+}
+
+/// Recover the secret from `shares` at distinct indices, as many as
+/// the threshold, by interpolating their polynomials at zero.
+fn combine(shares: &[(u8, &[u8])]) -> Vec<u8> {
+    // Lagrange basis polynomials at zero. Subtraction is XOR in GF(256).
+    let weights = shares
+        .iter()
+        .map(|&(x, _)| {
+            shares
+                .iter()
+                .filter(|&&(other, _)| other != x)
+                .fold(1, |acc, &(other, _)| mul(acc, mul(other, inv(other ^ x))))
+        })
+        .collect::<Vec<_>>();
+
+    let len = shares.first().map_or(0, |(_, bytes)| bytes.len());
+    (0..len)
+        .map(|i| {
+            shares
+                .iter()
+                .zip(&weights)
+                .fold(0, |acc, ((_, bytes), &weight)| acc ^ mul(bytes[i], weight))
+        })
+        .collect()
+}
+
+/// Split `data` into `count` shares written to new images, so that any
+/// `threshold` of them recover the data with [`from_shares()`] while
+/// fewer reveal nothing about it.
+///
+/// The header of each image records the index of its share and the
+/// threshold, and its payload starts with an identifier of the split and
+/// a digest of the data. Images are written according to `options`.
+///
+/// # Errors
+///
+/// - `threshold` is zero or more than `count`
+/// - Random numbers could not be read from the operating system
+/// - See [`plan()`](crate::plan)
+pub fn to_shares<I>(
+    data: impl AsRef<[u8]>,
+    threshold: u8,
+    count: u8,
+    options: &EncodeOptions,
+) -> Result<Vec<I>>
+where
+    I: ImageMut,
+{
+    if threshold == 0 || threshold > count {
+        return Err(Error::InvalidArgument);
+    }
+
+    let data = data.as_ref();
+    let mut id = [0; ID_SIZE];
+    random_bytes(&mut id)?;
+    let digest = digest(&id, data);
+
+    split(data, threshold, count)?
+        .into_iter()
+        .zip(1..)
+        .map(|(share, x)| {
+            let header = file::Header {
+                share: x,
+                threshold,
+                ..Default::default()
+            };
+
+            let payload = [&id[..], &digest, &share].concat();
+            crate::to_image_headed(&payload, header, options, &mut Ignore)
+        })
+        .collect()
+}
+
+/// Recover the data split by [`to_shares()`] from `shares`, which may
+/// be in any order and only need to hold as many shares as the threshold.
+///
+/// # Errors
+///
+/// See [`from_shares_with()`]
+pub fn from_shares<I, F>(shares: F) -> Result<Vec<u8>>
+where
+    I: Image,
+    F: IntoIterator<Item = I>,
+{
+    from_shares_with(shares, &DecodeOptions::new())
+}
+
+/// Recover the data split into `shares` from the region and channels of
+/// each image given by `options`.
+///
+/// # Errors
+///
+/// - Some of the images do not hold a share
+/// - Some of the shares belong to different secrets
+/// - Two shares at the same index differ
+/// - There are fewer distinct shares than the threshold
+/// - The recovered data does not match its digest, as some of the
+///   shares were altered
+/// - See [`from_image_with()`](crate::from_image_with)
+pub fn from_shares_with<I, F>(shares: F, options: &DecodeOptions) -> Result<Vec<u8>>
+where
+    I: Image,
+    F: IntoIterator<Item = I>,
+{
+    let mut first: Option<(file::Header, Vec<u8>)> = None;
+    let mut found = BTreeMap::<u8, Vec<u8>>::new();
+
+    for image in shares {
+        let (header, payload) = crate::read_frame(image, options, &mut Ignore)?;
+        if header.frames > 1 {
+            return Err(Error::MissingData);
+        }
+        if header.threshold == 0 || payload.len() < ID_SIZE + DIGEST_SIZE {
+            return Err(Error::TypeMismatch);
+        }
+
+        // The identifier and digest are the same in every share.
+        let (prefix, share) = payload.split_at(ID_SIZE + DIGEST_SIZE);
+        let (first, first_prefix) = first.get_or_insert_with(|| (header.clone(), prefix.to_vec()));
+        if (header.threshold, header.size, prefix)
+            != (first.threshold, first.size, &first_prefix[..])
+        {
+            return Err(Error::InvalidHeader);
+        }
+
+        match found.entry(header.share) {
+            Entry::Vacant(entry) => {
+                entry.insert(share.to_vec());
+            }
+            Entry::Occupied(entry) if entry.get() != share => return Err(Error::InvalidHeader),
+            Entry::Occupied(_) => {}
+        }
+    }
+
+    let Some((first, prefix)) = first else {
+        return Err(Error::MissingData);
+    };
+    if found.len() < usize::from(first.threshold) {
+        return Err(Error::MissingData);
+    }
+
+    let shares = found
+        .iter()
+        .take(usize::from(first.threshold))
+        .map(|(&x, share)| (x, share.as_slice()))
+        .collect::<Vec<_>>();
+
+    let secret = combine(&shares);
+    let (id, expected) = prefix.split_at(ID_SIZE);
+    if digest(id, &secret) != expected {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::RgbImage;
+
+    #[test]
+    fn test_field() {
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert!((1..=255u8).all(|a| mul(a, inv(a)) == 1));
+    }
+
+    #[test]
+    fn test_any_threshold_recovers() {
+        let secret = b"correct horse battery staple".to_vec();
+        let shares = split(&secret, 3, 5).unwrap();
+
+        for a in 0..5u8 {
+            for b in a + 1..5 {
+                for c in b + 1..5 {
+                    let picked = [a, b, c].map(|i| (i + 1, shares[usize::from(i)].as_slice()));
+                    assert_eq!(combine(&picked), secret, "{a} {b} {c}");
+                }
+            }
+        }
+
+        // Two shares interpolate a line, which misses the secret.
+        let picked = [(1, shares[0].as_slice()), (2, shares[1].as_slice())];
+        assert_ne!(combine(&picked), secret);
+    }
+
+    #[test]
+    fn test_shares_round_trip() {
+        let data = (0..=255u8).cycle().take(1000).collect::<Vec<_>>();
+        let options = EncodeOptions::new().block_size(2);
+        let images: Vec<RgbImage> = to_shares(&data, 2, 4, &options).unwrap();
+        assert_eq!(images.len(), 4);
+
+        let info = crate::probe(&images[2]).unwrap();
+        assert_eq!((info.share, info.threshold, info.version), (3, 2, 4));
+
+        assert_eq!(from_shares(images[2..].iter().rev()).unwrap(), data);
+        assert!(matches!(
+            crate::from_image(&images[0]),
+            Err(Error::MissingData)
+        ));
+        assert_eq!(from_shares([&images[1], &images[3]]).unwrap(), data);
+
+        assert!(matches!(
+            from_shares([&images[1], &images[1]]),
+            Err(Error::MissingData)
+        ));
+
+        // Shares of another split of the same data do not mix.
+        let others: Vec<RgbImage> = to_shares(&data, 2, 4, &options).unwrap();
+        assert!(matches!(
+            from_shares([&images[0], &others[1]]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_altered_shares() {
+        let data = b"correct horse battery staple";
+        let options = EncodeOptions::new();
+        let images: Vec<RgbImage> = to_shares(data, 2, 3, &options).unwrap();
+
+        let (header, mut payload) =
+            crate::read_frame(&images[1], &DecodeOptions::new(), &mut Ignore).unwrap();
+        payload[ID_SIZE + DIGEST_SIZE] ^= 1;
+        let altered: RgbImage =
+            crate::to_image_headed(&payload, header, &options, &mut Ignore).unwrap();
+
+        // Two different shares at the same index.
+        assert!(matches!(
+            from_shares([&images[1], &altered, &images[0]]),
+            Err(Error::InvalidHeader)
+        ));
+        assert!(matches!(
+            from_shares([&images[0], &altered]),
+            Err(Error::ChecksumMismatch)
+        ));
+        assert_eq!(
+            from_shares([&images[1], &images[1], &images[2]]).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_not_shares() {
+        let image: RgbImage = crate::to_image(b"not a share", 1.0);
+        assert!(matches!(from_shares([image]), Err(Error::TypeMismatch)));
+
+        assert!(matches!(
+            to_shares::<RgbImage>(b"secret", 4, 3, &EncodeOptions::new()),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            to_shares::<RgbImage>(b"secret", 0, 3, &EncodeOptions::new()),
+            Err(Error::InvalidArgument)
+        ));
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ed25519_dalek::{Signer, Verifier};
+use sha2::{Digest, Sha256};
+
+use crate::file::Header;
+use crate::recipient::{parse_key, random_key, write_hex};
+use crate::{Error, Result};
+
+const VERIFYING_PREFIX: &str = "imgcode-verifying-";
+const SIGNING_PREFIX: &str = "IMGCODE-SIGNING-";
+
+/// Context the header and payload digest are signed in.
+const CONTEXT: &[u8] = b"imgcode signature";
+
+/// Size of the public key at the start of the signature in the header.
+const KEY_SIZE: usize = 32;
+
+/// Public key images signed with its signing key are verified against,
+/// written as `imgcode-verifying-` followed by the key in hexadecimal.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VerifyingKey([u8; KEY_SIZE]);
+
+/// Secret key images are signed with. Written as `IMGCODE-SIGNING-`
+/// followed by the key in hexadecimal.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SigningKey([u8; 32]);
+
+impl SigningKey {
+    /// Generate a new signing key from the random numbers of the
+    /// operating system.
+    ///
+    /// # Errors
+    ///
+    /// Random numbers could not be read from the operating system.
+    pub fn generate() -> Result<Self> {
+        Ok(Self(random_key()?))
+    }
+
+    /// Get the key signatures made with this key are verified against.
+    #[must_use]
+    pub fn verifying_key(&self) -> VerifyingKey {
+        let key = ed25519_dalek::SigningKey::from_bytes(&self.0);
+        VerifyingKey(key.verifying_key().to_bytes())
+    }
+}
+
+impl fmt::Display for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(VERIFYING_PREFIX)?;
+        write_hex(f, &self.0)
+    }
+}
+
+impl fmt::Debug for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VerifyingKey({self})")
+    }
+}
+
+impl FromStr for VerifyingKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let key = parse_key(s, VERIFYING_PREFIX)?;
+
+        // Not every 32 bytes are a point on the curve.
+        ed25519_dalek::VerifyingKey::from_bytes(&key).map_err(|_| Error::InvalidKey)?;
+        Ok(Self(key))
+    }
+}
+
+impl serde::Serialize for VerifyingKey {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(SIGNING_PREFIX)?;
+        write_hex(f, &self.0)
+    }
+}
+
+/// Signing keys are left out of debug output, so that they do not end up
+/// in logs.
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SigningKey({})", self.verifying_key())
+    }
+}
+
+impl FromStr for SigningKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_key(s, SIGNING_PREFIX).map(Self)
+    }
+}
+
+/// Get the message signed for `header` and the payload `data`: the header
+/// with the signer but without the signature, and the digest of `data`.
+fn message(header: &Header, key: &VerifyingKey, data: &[u8]) -> Vec<u8> {
+    let mut signature = [0; Header::SIGNATURE_SIZE];
+    signature[..KEY_SIZE].copy_from_slice(&key.0);
+    let header = Header {
+        signature: Some(signature),
+        ..header.clone()
+    };
+
+    let mut message = CONTEXT.to_vec();
+    header
+        .write_to(&mut message)
+        .expect("headers with as many recipients as read can be written");
+    message.extend_from_slice(&Sha256::digest(data));
+    message
+}
+
+/// Get a signature of `header` and the payload `data` by `key`, to store
+/// in the header.
+pub(crate) fn sign(header: &Header, data: &[u8], key: &SigningKey) -> [u8; Header::SIGNATURE_SIZE] {
+    let verifying_key = key.verifying_key();
+    let message = message(header, &verifying_key, data);
+    let signature = ed25519_dalek::SigningKey::from_bytes(&key.0).sign(&message);
+
+    let mut stored = [0; Header::SIGNATURE_SIZE];
+    stored[..KEY_SIZE].copy_from_slice(&verifying_key.0);
+    stored[KEY_SIZE..].copy_from_slice(&signature.to_bytes());
+    stored
+}
+
+/// Get the key which signed `header`, if it is signed.
+pub(crate) fn signer(header: &Header) -> Option<VerifyingKey> {
+    let signature = header.signature.as_ref()?;
+    Some(VerifyingKey(signature[..KEY_SIZE].try_into().unwrap()))
+}
+
+/// Check the signature in `header` of the header and the payload `data`,
+/// and that it was made by `trusted` if given.
+///
+/// # Errors
+///
+/// - `trusted` is given and the image is not signed
+/// - The header or the payload were altered after they were signed
+/// - The image was signed by another key than `trusted`
+pub(crate) fn check(header: &Header, data: &[u8], trusted: Option<&VerifyingKey>) -> Result<()> {
+    let Some(stored) = &header.signature else {
+        return match trusted {
+            Some(_) => Err(Error::Unsigned),
+            None => Ok(()),
+        };
+    };
+    let signer = VerifyingKey(stored[..KEY_SIZE].try_into().unwrap());
+
+    let key =
+        ed25519_dalek::VerifyingKey::from_bytes(&signer.0).map_err(|_| Error::BadSignature)?;
+    let signature = ed25519_dalek::Signature::from_bytes(stored[KEY_SIZE..].try_into().unwrap());
+    key.verify(&message(header, &signer, data), &signature)
+        .map_err(|_| Error::BadSignature)?;
+
+    match trusted {
+        Some(trusted) if *trusted != signer => Err(Error::UntrustedSigner),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_text() {
+        let signing = SigningKey::generate().unwrap();
+        let verifying = signing.verifying_key();
+
+        assert_eq!(signing.to_string().parse::<SigningKey>().unwrap(), signing);
+        assert_eq!(
+            verifying.to_string().parse::<VerifyingKey>().unwrap(),
+            verifying
+        );
+        assert!(!format!("{signing:?}").contains(&signing.to_string()));
+
+        assert!(matches!(
+            verifying.to_string().parse::<SigningKey>(),
+            Err(Error::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn test_sign_and_check() {
+        let signing = SigningKey::generate().unwrap();
+        let trusted = signing.verifying_key();
+        let other = SigningKey::generate().unwrap().verifying_key();
+
+        let data = b"release config";
+        let mut header = Header {
+            size: data.len() as u64,
+            ..Default::default()
+        };
+        assert!(check(&header, data, None).is_ok());
+        assert!(matches!(
+            check(&header, data, Some(&trusted)),
+            Err(Error::Unsigned)
+        ));
+
+        header.signature = Some(sign(&header, data, &signing));
+        assert_eq!(signer(&header), Some(trusted));
+        assert!(check(&header, data, None).is_ok());
+        assert!(check(&header, data, Some(&trusted)).is_ok());
+        assert!(matches!(
+            check(&header, data, Some(&other)),
+            Err(Error::UntrustedSigner)
+        ));
+
+        assert!(matches!(
+            check(&header, b"release confiG", Some(&trusted)),
+            Err(Error::BadSignature)
+        ));
+
+        let tampered = Header {
+            tag: 1,
+            ..header.clone()
+        };
+        assert!(matches!(
+            check(&tampered, data, None),
+            Err(Error::BadSignature)
+        ));
+    }
+}
@@ -2,21 +2,71 @@ use std::mem;
 
 use crate::private::Sealed;
 
+/// Format of the pixels of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelFormat {
+    Rgb8,
+    Rgba8,
+    Rgb32,
+    Rgba32,
+}
+
+impl PixelFormat {
+    /// All supported pixel formats.
+    pub const ALL: [Self; 4] = [Self::Rgb8, Self::Rgba8, Self::Rgb32, Self::Rgba32];
+
+    /// Get the size of one pixel in bytes.
+    #[must_use]
+    pub fn pixel_size(self) -> u32 {
+        match self {
+            Self::Rgb8 => 3,
+            Self::Rgba8 => 4,
+            Self::Rgb32 => 12,
+            Self::Rgba32 => 16,
+        }
+    }
+
+    /// Get the number of channels in one pixel.
+    #[must_use]
+    pub fn channel_count(self) -> u32 {
+        match self {
+            Self::Rgb8 | Self::Rgb32 => 3,
+            Self::Rgba8 | Self::Rgba32 => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rgb8 => write!(f, "rgb8"),
+            Self::Rgba8 => write!(f, "rgba8"),
+            Self::Rgb32 => write!(f, "rgb32"),
+            Self::Rgba32 => write!(f, "rgba32"),
+        }
+    }
+}
+
 pub trait Image: Sealed {
     type ChannelType;
     const CHANNEL_NUM: u32;
+    const PIXEL_FORMAT: PixelFormat;
 
     #[allow(clippy::cast_possible_truncation)]
     const PIXEL_SIZE: u32 = (mem::size_of::<Self::ChannelType>() as u32) * Self::CHANNEL_NUM;
 
-    fn new_with_dimensions(x: u32, y: u32) -> Self
-    where
-        Self: Sized;
-
     fn width(&self) -> u32;
     fn height(&self) -> u32;
 
     fn get_pixel(&self, x: u32, y: u32) -> Option<&[u8]>;
+}
+
+pub trait ImageMut: Image {
+    fn new_with_dimensions(x: u32, y: u32) -> Self
+    where
+        Self: Sized;
+
     fn get_pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut [u8]>;
 }
 
@@ -25,19 +75,35 @@ mod impls {
     use std::slice;
 
     use crate::private::Sealed;
-    use crate::traits::Image;
+    use crate::traits::{Image, ImageMut, PixelFormat};
+
+    impl<T> Sealed for &T where T: Sealed {}
+    impl<T> Image for &T
+    where
+        T: Image,
+    {
+        type ChannelType = T::ChannelType;
+        const CHANNEL_NUM: u32 = T::CHANNEL_NUM;
+        const PIXEL_FORMAT: PixelFormat = T::PIXEL_FORMAT;
+
+        fn width(&self) -> u32 {
+            T::width(self)
+        }
+
+        fn height(&self) -> u32 {
+            T::height(self)
+        }
+
+        fn get_pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+            T::get_pixel(self, x, y)
+        }
+    }
 
     impl Sealed for image::RgbImage {}
     impl Image for image::RgbImage {
         type ChannelType = u8;
         const CHANNEL_NUM: u32 = 3;
-
-        fn new_with_dimensions(x: u32, y: u32) -> Self
-        where
-            Self: Sized,
-        {
-            Self::new(x, y)
-        }
+        const PIXEL_FORMAT: PixelFormat = PixelFormat::Rgb8;
 
         fn width(&self) -> u32 {
             self.width()
@@ -50,6 +116,15 @@ mod impls {
         fn get_pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
             self.get_pixel_checked(x, y).map(|x| x.0.as_slice())
         }
+    }
+
+    impl ImageMut for image::RgbImage {
+        fn new_with_dimensions(x: u32, y: u32) -> Self
+        where
+            Self: Sized,
+        {
+            Self::new(x, y)
+        }
 
         fn get_pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut [u8]> {
             self.get_pixel_mut_checked(x, y).map(|x| x.0.as_mut_slice())
@@ -60,13 +135,7 @@ mod impls {
     impl Image for image::RgbaImage {
         type ChannelType = u8;
         const CHANNEL_NUM: u32 = 4;
-
-        fn new_with_dimensions(x: u32, y: u32) -> Self
-        where
-            Self: Sized,
-        {
-            Self::new(x, y)
-        }
+        const PIXEL_FORMAT: PixelFormat = PixelFormat::Rgba8;
 
         fn width(&self) -> u32 {
             self.width()
@@ -79,6 +148,15 @@ mod impls {
         fn get_pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
             self.get_pixel_checked(x, y).map(|x| x.0.as_slice())
         }
+    }
+
+    impl ImageMut for image::RgbaImage {
+        fn new_with_dimensions(x: u32, y: u32) -> Self
+        where
+            Self: Sized,
+        {
+            Self::new(x, y)
+        }
 
         fn get_pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut [u8]> {
             self.get_pixel_mut_checked(x, y).map(|x| x.0.as_mut_slice())
@@ -89,13 +167,7 @@ mod impls {
     impl Image for image::Rgb32FImage {
         type ChannelType = f32;
         const CHANNEL_NUM: u32 = 3;
-
-        fn new_with_dimensions(x: u32, y: u32) -> Self
-        where
-            Self: Sized,
-        {
-            Self::new(x, y)
-        }
+        const PIXEL_FORMAT: PixelFormat = PixelFormat::Rgb32;
 
         fn width(&self) -> u32 {
             self.width()
@@ -109,6 +181,15 @@ mod impls {
             self.get_pixel_checked(x, y)
                 .map(|x| slice_to_u8_slice(&x.0))
         }
+    }
+
+    impl ImageMut for image::Rgb32FImage {
+        fn new_with_dimensions(x: u32, y: u32) -> Self
+        where
+            Self: Sized,
+        {
+            Self::new(x, y)
+        }
 
         fn get_pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut [u8]> {
             self.get_pixel_mut_checked(x, y)
@@ -120,13 +201,7 @@ mod impls {
     impl Image for image::Rgba32FImage {
         type ChannelType = f32;
         const CHANNEL_NUM: u32 = 4;
-
-        fn new_with_dimensions(x: u32, y: u32) -> Self
-        where
-            Self: Sized,
-        {
-            Self::new(x, y)
-        }
+        const PIXEL_FORMAT: PixelFormat = PixelFormat::Rgba32;
 
         fn width(&self) -> u32 {
             self.width()
@@ -140,6 +215,15 @@ mod impls {
             self.get_pixel_checked(x, y)
                 .map(|x| slice_to_u8_slice(&x.0))
         }
+    }
+
+    impl ImageMut for image::Rgba32FImage {
+        fn new_with_dimensions(x: u32, y: u32) -> Self
+        where
+            Self: Sized,
+        {
+            Self::new(x, y)
+        }
 
         fn get_pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut [u8]> {
             self.get_pixel_mut_checked(x, y)